@@ -0,0 +1,144 @@
+use std::{
+    io::{Error, Result},
+    path::Path,
+};
+
+use bytemuck::Pod;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{deserialize::GeoTree, Layer};
+
+use super::{from_extent, tiles, to_extent, Level, Pixel, CRS, DESCRIPTION};
+
+/// Bounds of an archive without `bounds` metadata, the extent of Web
+/// Mercator.
+const DEFAULT_EXTENT: [f64; 4] = [-180., -85.051_128_779_806_59, 180., 85.051_128_779_806_6];
+
+/// Writes the top `levels` levels of `tree` to a new MBTiles archive at
+/// `path`. Rows are numbered from the south, as MBTiles requires, but the
+/// tiles are equirectangular rather than Web Mercator, as the `crs` and
+/// `description` metadata state.
+pub fn export<D, P>(tree: &GeoTree<D>, levels: usize, path: P) -> Result<()>
+where
+    D: Layer<Type = Pixel>,
+    D::AggregateType: Pod,
+    P: AsRef<Path>,
+{
+    if path.as_ref().try_exists()? {
+        return Err(std::io::ErrorKind::AlreadyExists.into());
+    }
+
+    let mut connection = Connection::open(path).map_err(Error::other)?;
+    let transaction = connection.transaction().map_err(Error::other)?;
+
+    transaction
+        .execute_batch(
+            "CREATE TABLE metadata (name TEXT, value TEXT);
+             CREATE TABLE tiles (zoom_level INTEGER, tile_column INTEGER, tile_row INTEGER, tile_data BLOB);
+             CREATE UNIQUE INDEX tile_index ON tiles (zoom_level, tile_column, tile_row);",
+        )
+        .map_err(Error::other)?;
+
+    let mut extent = DEFAULT_EXTENT;
+    let mut max_zoom = 0;
+
+    for tile in tiles(tree, levels)? {
+        let (z, x, y, bounds, png) = tile?;
+
+        if z == 0 {
+            extent = to_extent(bounds);
+        }
+
+        max_zoom = max_zoom.max(z);
+
+        transaction
+            .execute(
+                "INSERT INTO tiles VALUES (?1, ?2, ?3, ?4)",
+                params![z, x, (1_u32 << z) - 1 - y, png],
+            )
+            .map_err(Error::other)?;
+    }
+
+    let [west, south, east, north] = extent;
+
+    for (name, value) in [
+        ("name", "globe"),
+        ("format", "png"),
+        ("type", "baselayer"),
+        ("description", DESCRIPTION),
+        ("crs", CRS),
+        ("minzoom", "0"),
+        ("maxzoom", &max_zoom.to_string()),
+        ("bounds", &format!("{west},{south},{east},{north}")),
+    ] {
+        transaction
+            .execute("INSERT INTO metadata VALUES (?1, ?2)", params![name, value])
+            .map_err(Error::other)?;
+    }
+
+    transaction.commit().map_err(Error::other)
+}
+
+/// Reads the tiles of the deepest zoom level of the MBTiles archive at
+/// `path`.
+pub fn read_level<P>(path: P) -> Result<Level>
+where
+    P: AsRef<Path>,
+{
+    let connection = Connection::open(path).map_err(Error::other)?;
+
+    let zoom = connection
+        .query_row("SELECT MAX(zoom_level) FROM tiles", [], |row| {
+            row.get::<_, Option<u8>>(0)
+        })
+        .map_err(Error::other)?
+        .ok_or_else(|| Error::other("archive has no tiles"))?;
+
+    if zoom >= 32 {
+        return Err(Error::other("zoom level too deep"));
+    }
+
+    let bounds = connection
+        .query_row(
+            "SELECT value FROM metadata WHERE name = 'bounds'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()
+        .map_err(Error::other)?
+        .and_then(|bounds| {
+            let extent = bounds
+                .split(',')
+                .map(|value| value.trim().parse().ok())
+                .collect::<Option<Vec<f64>>>()?;
+
+            extent.try_into().ok()
+        })
+        .unwrap_or(DEFAULT_EXTENT);
+
+    let mut statement = connection
+        .prepare("SELECT tile_column, tile_row, tile_data FROM tiles WHERE zoom_level = ?1")
+        .map_err(Error::other)?;
+
+    let tiles = statement
+        .query_map([zoom], |row| {
+            let row_from_south: u32 = row.get(1)?;
+            let y = ((1_u64 << zoom) - 1)
+                .checked_sub(row_from_south.into())
+                .ok_or(rusqlite::Error::IntegralValueOutOfRange(
+                    1,
+                    row_from_south.into(),
+                ))?;
+
+            Ok((row.get(0)?, y as u32, row.get(2)?))
+        })
+        .map_err(Error::other)?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(Error::other)?;
+
+    Ok(Level {
+        bounds: from_extent(bounds),
+        zoom,
+        tiles,
+    })
+}
@@ -0,0 +1,262 @@
+use std::{
+    borrow::Cow,
+    io::{Error, ErrorKind, Result},
+    path::Path,
+};
+
+use bytemuck::Pod;
+use common::codec::Codec;
+use geo::Coord;
+use image::{ImageBuffer, ImageFormat, Rgba};
+
+use crate::{
+    deserialize::GeoTree, earth_map::EarthmapDataset, slice, Bounds, Dataset, Layer, Tile,
+};
+
+pub mod mbtiles;
+pub mod pmtiles;
+
+type Pixel = [u8; 4];
+
+/// The tiles of the deepest level of an archive, as encoded images
+/// addressed by `x` and `y`, with `y` counting from the north.
+pub struct Level {
+    pub bounds: Bounds,
+    pub zoom: u8,
+    pub tiles: Vec<(u32, u32, Vec<u8>)>,
+}
+
+/// The projection of exported tiles, written to the metadata of archives.
+/// Tiles keep the equirectangular layout of the tree, while viewers of
+/// both formats assume Web Mercator unless told otherwise, and show them
+/// squashed towards the equator.
+const CRS: &str = "EPSG:4326";
+const DESCRIPTION: &str = "Equirectangular tiles (EPSG:4326), not Web Mercator";
+
+/// A tile as its `z`, `x`, `y`, bounds and PNG image.
+type ArchiveTile = (u8, u32, u32, Bounds, Vec<u8>);
+
+/// Every tile of the top `levels` levels of `tree`, encoded as PNG, in
+/// order of `z`, `y` and `x`. Archives only hold quadtrees.
+fn tiles<D>(
+    tree: &GeoTree<D>,
+    levels: usize,
+) -> Result<impl Iterator<Item = Result<ArchiveTile>> + '_>
+where
+    D: Layer<Type = Pixel>,
+    D::AggregateType: Pod,
+{
+    if D::CHILDREN_PER_AXIS != 2 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "archives can only hold trees with 2 children per axis",
+        ));
+    }
+
+    let levels = levels.min(tree.info()?.levels);
+
+    Ok((0..levels).flat_map(move |z| {
+        let size = 1 << z;
+
+        (0..size).flat_map(move |y| {
+            (0..size).filter_map(move |x| {
+                let tile = match tree.get_tile(x, y, z) {
+                    Ok(tile) => tile?,
+                    Err(err) => return Some(Err(err.into())),
+                };
+
+                Some(Ok((
+                    z as u8,
+                    x as u32,
+                    y as u32,
+                    tile.bounds,
+                    encode_png(&tile.data),
+                )))
+            })
+        })
+    }))
+}
+
+fn encode_png(tile: &[Cow<'_, [Pixel]>]) -> Vec<u8> {
+    let width = tile.first().map_or(0, |row| row.len());
+    let pixels = tile.concat();
+
+    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(
+        width as u32,
+        tile.len() as u32,
+        bytemuck::cast_vec::<Pixel, u8>(pixels),
+    )
+    .expect("rectangular tile");
+
+    let mut png = Vec::new();
+    image
+        .write_to(&mut std::io::Cursor::new(&mut png), ImageFormat::Png)
+        .expect("PNG encoding to memory");
+
+    png
+}
+
+/// The deepest level of an MBTiles or PMTiles archive of raster tiles,
+/// stitched into one image. The tiles are laid out evenly across the
+/// bounds of the archive, without reprojecting them, so Web Mercator
+/// pyramids end up stretched towards the poles.
+pub struct ArchiveDataset {
+    data: Tile<Pixel>,
+    bounds: Bounds,
+}
+
+impl ArchiveDataset {
+    /// Opens an archive by its extension, `.mbtiles` or `.pmtiles`.
+    pub fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+
+        let level = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("mbtiles") => mbtiles::read_level(path)?,
+            Some("pmtiles") => pmtiles::read_level(path)?,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "archives must end with .mbtiles or .pmtiles",
+                ))
+            }
+        };
+
+        Ok(Self {
+            data: stitch(&level)?,
+            bounds: level.bounds,
+        })
+    }
+}
+
+fn stitch(level: &Level) -> Result<Tile<Pixel>> {
+    let tiles = level
+        .tiles
+        .iter()
+        .map(|(x, y, data)| {
+            let image = image::load_from_memory(data)
+                .map_err(|err| Error::new(ErrorKind::InvalidData, err))?
+                .to_rgba8();
+
+            Ok((*x as usize, *y as usize, image))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let (tile_width, tile_height) = tiles
+        .first()
+        .map(|(_, _, image)| image.dimensions())
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "archive has no tiles"))?;
+
+    if tiles
+        .iter()
+        .any(|(_, _, image)| image.dimensions() != (tile_width, tile_height))
+    {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "tiles of the archive differ in size",
+        ));
+    }
+
+    if level.zoom >= 32 {
+        return Err(Error::new(ErrorKind::InvalidData, "zoom level too deep"));
+    }
+
+    let size = 1_usize << level.zoom;
+    let (tile_width, tile_height) = (tile_width as usize, tile_height as usize);
+
+    let (Some(width), Some(height)) = (size.checked_mul(tile_width), size.checked_mul(tile_height))
+    else {
+        return Err(Error::new(ErrorKind::InvalidData, "archive too large"));
+    };
+
+    // Reserved fallibly, as the size comes from the archive
+    let mut data = Vec::new();
+    data.try_reserve_exact(height).map_err(Error::other)?;
+
+    for _ in 0..height {
+        let mut row = Vec::new();
+        row.try_reserve_exact(width).map_err(Error::other)?;
+        row.resize(width, ArchiveDataset::default());
+
+        data.push(row);
+    }
+
+    for (x, y, image) in tiles {
+        if x >= size || y >= size {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("tile {x}/{y} outside of zoom level {}", level.zoom),
+            ));
+        }
+
+        for (row, pixels) in image.rows().enumerate() {
+            let start = x * tile_width;
+            let target = &mut data[y * tile_height + row][start..start + tile_width];
+
+            for (target, pixel) in target.iter_mut().zip(pixels) {
+                *target = pixel.0;
+            }
+        }
+    }
+
+    Ok(data)
+}
+
+impl Layer for ArchiveDataset {
+    type Type = Pixel;
+    type AggregateType = ();
+
+    const CHILDREN_PER_AXIS: usize = 2;
+}
+
+impl Dataset for ArchiveDataset {
+    fn downsample(data: &Tile<Pixel>) -> Tile<Pixel> {
+        EarthmapDataset::downsample(data)
+    }
+
+    fn default() -> Pixel {
+        [0; 4]
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (self.data[0].len(), self.data.len())
+    }
+
+    fn window(&self, x: usize, y: usize, width: usize, height: usize) -> Tile<Pixel> {
+        slice::<Self>(&self.data, x, y, width, height)
+    }
+
+    fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    const TILE_SIZE: u32 = 256;
+    const MAX_LEVEL: u32 = 0;
+
+    const CODEC: Codec = Codec::ShuffleDeltaLz4;
+}
+
+/// Bounds as written to archives, `[west, south, east, north]`.
+fn to_extent(bounds: Bounds) -> [f64; 4] {
+    [
+        bounds.min().x as f64,
+        bounds.min().y as f64,
+        bounds.max().x as f64,
+        bounds.max().y as f64,
+    ]
+}
+
+fn from_extent([west, south, east, north]: [f64; 4]) -> Bounds {
+    Bounds::new(
+        Coord {
+            x: west as f32,
+            y: south as f32,
+        },
+        Coord {
+            x: east as f32,
+            y: north as f32,
+        },
+    )
+}
@@ -0,0 +1,496 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Error, ErrorKind, Read, Result, Write},
+    path::Path,
+};
+
+use bytemuck::Pod;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+
+use crate::{deserialize::GeoTree, Layer};
+
+use super::{from_extent, tiles, to_extent, Level, Pixel, CRS, DESCRIPTION};
+
+const MAGIC: &[u8; 7] = b"PMTiles";
+const VERSION: u8 = 3;
+const HEADER_SIZE: usize = 127;
+
+/// The header and root directory must fit in the first 16 KiB, so that
+/// clients can fetch both with a single request.
+const ROOT_SIZE: usize = 16384;
+
+const COMPRESSION_NONE: u8 = 1;
+const COMPRESSION_GZIP: u8 = 2;
+
+const TILE_TYPE_PNG: u8 = 2;
+const TILE_TYPE_JPEG: u8 = 3;
+const TILE_TYPE_WEBP: u8 = 4;
+
+/// A directory entry. Entries with a `run_length` of 0 point at a leaf
+/// directory instead of tile data.
+#[derive(Clone, Copy)]
+struct Entry {
+    tile_id: u64,
+    offset: u64,
+    length: u64,
+    run_length: u64,
+}
+
+/// The position of the tile `x`, `y` on level `z` along the Hilbert curves
+/// of all levels, as used to address PMTiles.
+pub fn tile_id(z: u8, mut x: u64, mut y: u64) -> u64 {
+    // Number of tiles on the levels above
+    let base = ((1_u64 << (2 * z)) - 1) / 3;
+    let n = 1_u64 << z;
+
+    let mut d = 0;
+    let mut s = n / 2;
+
+    while s > 0 {
+        let rx = u64::from(x & s > 0);
+        let ry = u64::from(y & s > 0);
+
+        d += s * s * ((3 * rx) ^ ry);
+        rotate(n, &mut x, &mut y, rx, ry);
+
+        s /= 2;
+    }
+
+    base + d
+}
+
+/// The level, `x` and `y` of the tile at `tile_id`, which must be on one of
+/// the levels up to 31.
+fn tile_position(tile_id: u64) -> Result<(u8, u64, u64)> {
+    let mut base = 0;
+
+    for z in 0..32 {
+        let count = 1_u64 << (2 * z);
+
+        if base + count > tile_id {
+            let mut d = tile_id - base;
+            let (mut x, mut y) = (0, 0);
+            let mut s = 1;
+
+            while s < 1 << z {
+                let rx = 1 & (d / 2);
+                let ry = 1 & (d ^ rx);
+
+                rotate(s, &mut x, &mut y, rx, ry);
+                x += s * rx;
+                y += s * ry;
+
+                d /= 4;
+                s *= 2;
+            }
+
+            return Ok((z, x, y));
+        }
+
+        base += count;
+    }
+
+    Err(invalid_data("tile ID beyond level 31"))
+}
+
+fn rotate(n: u64, x: &mut u64, y: &mut u64, rx: u64, ry: u64) {
+    if ry == 0 {
+        if rx == 1 {
+            *x = n - 1 - *x;
+            *y = n - 1 - *y;
+        }
+
+        std::mem::swap(x, y);
+    }
+}
+
+/// Writes the top `levels` levels of `tree` to a new PMTiles archive at
+/// `path`. Tiles with identical images are stored once. The tiles are
+/// equirectangular rather than Web Mercator, as the `crs` and
+/// `description` metadata state.
+pub fn export<D, P>(tree: &GeoTree<D>, levels: usize, path: P) -> Result<()>
+where
+    D: Layer<Type = Pixel>,
+    D::AggregateType: Pod,
+    P: AsRef<Path>,
+{
+    let mut extent = [-180., -90., 180., 90.];
+    let mut max_zoom = 0;
+
+    let mut tiles = tiles(tree, levels)?
+        .map(|tile| {
+            let (z, x, y, bounds, png) = tile?;
+
+            if z == 0 {
+                extent = to_extent(bounds);
+            }
+
+            max_zoom = max_zoom.max(z);
+
+            Ok((tile_id(z, x.into(), y.into()), png))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    tiles.sort_unstable_by_key(|(tile_id, _)| *tile_id);
+
+    let mut data = Vec::new();
+    let mut contents = HashMap::new();
+    let mut entries = Vec::<Entry>::new();
+
+    for (tile_id, png) in &tiles {
+        let length = png.len() as u64;
+
+        let offset = *contents.entry(png).or_insert_with(|| {
+            let offset = data.len() as u64;
+            data.extend_from_slice(png);
+            offset
+        });
+
+        match entries.last_mut() {
+            Some(last) if last.offset == offset && last.tile_id + last.run_length == *tile_id => {
+                last.run_length += 1;
+            }
+            _ => entries.push(Entry {
+                tile_id: *tile_id,
+                offset,
+                length,
+                run_length: 1,
+            }),
+        }
+    }
+
+    let (root, leaves) = build_directories(&entries)?;
+    let metadata = gzip(
+        format!(
+            r#"{{"name":"globe","format":"png","type":"baselayer","description":"{DESCRIPTION}","crs":"{CRS}"}}"#
+        )
+        .as_bytes(),
+    )?;
+
+    let root_offset = HEADER_SIZE as u64;
+    let metadata_offset = root_offset + root.len() as u64;
+    let leaves_offset = metadata_offset + metadata.len() as u64;
+    let data_offset = leaves_offset + leaves.len() as u64;
+
+    let [west, south, east, north] = extent;
+    let e7 = |degrees: f64| ((degrees * 1e7).round() as i32).to_le_bytes();
+
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    header.extend_from_slice(MAGIC);
+    header.push(VERSION);
+
+    for value in [
+        root_offset,
+        root.len() as u64,
+        metadata_offset,
+        metadata.len() as u64,
+        leaves_offset,
+        leaves.len() as u64,
+        data_offset,
+        data.len() as u64,
+        tiles.len() as u64,
+        entries.len() as u64,
+        contents.len() as u64,
+    ] {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // Tile data is written in order of tile ID
+    header.push(1);
+    header.push(COMPRESSION_GZIP);
+    header.push(COMPRESSION_NONE);
+    header.push(TILE_TYPE_PNG);
+    header.push(0);
+    header.push(max_zoom);
+
+    for degrees in [west, south, east, north] {
+        header.extend_from_slice(&e7(degrees));
+    }
+
+    header.push(0);
+    header.extend_from_slice(&e7((west + east) / 2.));
+    header.extend_from_slice(&e7((south + north) / 2.));
+
+    debug_assert_eq!(header.len(), HEADER_SIZE);
+
+    let mut file = std::fs::File::create_new(path)?;
+
+    for section in [&header, &root, &metadata, &leaves, &data] {
+        file.write_all(section)?;
+    }
+
+    Ok(())
+}
+
+/// Serializes `entries` into a root directory and, if they do not fit into
+/// it, leaf directories pointed at by the root.
+fn build_directories(entries: &[Entry]) -> Result<(Vec<u8>, Vec<u8>)> {
+    let root = serialize_directory(entries)?;
+
+    if HEADER_SIZE + root.len() <= ROOT_SIZE {
+        return Ok((root, Vec::new()));
+    }
+
+    let mut leaf_size = 4096;
+
+    loop {
+        let mut leaves = Vec::new();
+        let mut root_entries = Vec::new();
+
+        for chunk in entries.chunks(leaf_size) {
+            let leaf = serialize_directory(chunk)?;
+
+            root_entries.push(Entry {
+                tile_id: chunk[0].tile_id,
+                offset: leaves.len() as u64,
+                length: leaf.len() as u64,
+                run_length: 0,
+            });
+
+            leaves.extend_from_slice(&leaf);
+        }
+
+        let root = serialize_directory(&root_entries)?;
+
+        if HEADER_SIZE + root.len() <= ROOT_SIZE {
+            return Ok((root, leaves));
+        }
+
+        leaf_size *= 2;
+    }
+}
+
+fn serialize_directory(entries: &[Entry]) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+
+    write_varint(&mut buffer, entries.len() as u64);
+
+    let mut last_id = 0;
+
+    for entry in entries {
+        write_varint(&mut buffer, entry.tile_id - last_id);
+        last_id = entry.tile_id;
+    }
+
+    for entry in entries {
+        write_varint(&mut buffer, entry.run_length);
+    }
+
+    for entry in entries {
+        write_varint(&mut buffer, entry.length);
+    }
+
+    for (i, entry) in entries.iter().enumerate() {
+        // Offsets directly following the previous entry are stored as 0
+        let contiguous = i > 0 && {
+            let previous = entries[i - 1];
+            entry.offset == previous.offset + previous.length
+        };
+
+        write_varint(&mut buffer, if contiguous { 0 } else { entry.offset + 1 });
+    }
+
+    gzip(&buffer)
+}
+
+fn deserialize_directory(mut bytes: &[u8]) -> Result<Vec<Entry>> {
+    let count = read_varint(&mut bytes)?;
+
+    // Every entry takes at least one byte for each of its four fields
+    if count > bytes.len() as u64 / 4 {
+        return Err(invalid_data(
+            "more directory entries than the directory holds",
+        ));
+    }
+
+    let count = count as usize;
+
+    let mut entries = vec![
+        Entry {
+            tile_id: 0,
+            offset: 0,
+            length: 0,
+            run_length: 0,
+        };
+        count
+    ];
+
+    let mut last_id = 0;
+
+    for entry in &mut entries {
+        last_id = read_varint(&mut bytes)?
+            .checked_add(last_id)
+            .ok_or_else(|| invalid_data("tile ID beyond 64 bits"))?;
+        entry.tile_id = last_id;
+    }
+
+    for entry in &mut entries {
+        entry.run_length = read_varint(&mut bytes)?;
+    }
+
+    for entry in &mut entries {
+        entry.length = read_varint(&mut bytes)?;
+    }
+
+    for i in 0..count {
+        let offset = read_varint(&mut bytes)?;
+
+        entries[i].offset = match (offset, i) {
+            (0, 0) => return Err(invalid_data("first directory entry without offset")),
+            (0, _) => entries[i - 1]
+                .offset
+                .checked_add(entries[i - 1].length)
+                .ok_or_else(|| invalid_data("directory entry beyond 64 bits"))?,
+            (offset, _) => offset - 1,
+        };
+    }
+
+    Ok(entries)
+}
+
+/// Reads the tiles of the deepest zoom level of the PMTiles archive at
+/// `path`.
+pub fn read_level<P>(path: P) -> Result<Level>
+where
+    P: AsRef<Path>,
+{
+    let file = std::fs::read(path)?;
+
+    let header = file
+        .get(..HEADER_SIZE)
+        .filter(|header| header.starts_with(MAGIC))
+        .ok_or_else(|| invalid_data("not a PMTiles archive"))?;
+
+    if header[7] != VERSION {
+        return Err(invalid_data("unsupported PMTiles version"));
+    }
+
+    let u64_at = |offset: usize| u64::from_le_bytes(header[offset..offset + 8].try_into().unwrap());
+    let e7_at = |offset: usize| {
+        f64::from(i32::from_le_bytes(
+            header[offset..offset + 4].try_into().unwrap(),
+        )) / 1e7
+    };
+
+    let section = |base: u64, offset: u64, length: u64| {
+        let start = base
+            .checked_add(offset)
+            .map(|start| (start, start.checked_add(length)));
+
+        match start {
+            Some((start, Some(end))) => file.get(start as usize..end as usize),
+            _ => None,
+        }
+        .ok_or_else(|| invalid_data("section beyond the end of the archive"))
+    };
+
+    let internal_compression = header[97];
+    let tile_compression = header[98];
+
+    if ![TILE_TYPE_PNG, TILE_TYPE_JPEG, TILE_TYPE_WEBP].contains(&header[99]) {
+        return Err(invalid_data("archive does not hold raster tiles"));
+    }
+
+    let zoom = header[101];
+
+    if zoom >= 32 {
+        return Err(invalid_data("zoom level too deep"));
+    }
+
+    // Runs may not reach past the deepest level, which bounds the tiles read
+    let first = tile_id(zoom, 0, 0);
+    let tile_count = first + (1 << (2 * u32::from(zoom)));
+    let (leaves_offset, data_offset) = (u64_at(40), u64_at(56));
+
+    let mut directories = vec![section(0, u64_at(8), u64_at(16))?];
+    let mut leaves = HashSet::new();
+    let mut tiles = Vec::new();
+
+    while let Some(directory) = directories.pop() {
+        let directory = decompress(directory, internal_compression)?;
+
+        for entry in deserialize_directory(&directory)? {
+            if entry.run_length == 0 {
+                // Each leaf is read once, so that leaves pointing back do not loop
+                if !leaves.insert(entry.offset) {
+                    return Err(invalid_data("leaf directory referenced twice"));
+                }
+
+                directories.push(section(leaves_offset, entry.offset, entry.length)?);
+                continue;
+            }
+
+            let data = section(data_offset, entry.offset, entry.length)?;
+
+            let end = entry
+                .tile_id
+                .checked_add(entry.run_length)
+                .filter(|&end| end <= tile_count)
+                .ok_or_else(|| invalid_data("tiles beyond the deepest zoom level"))?;
+
+            for tile_id in entry.tile_id.max(first)..end {
+                let (z, x, y) = tile_position(tile_id)?;
+
+                if z == zoom {
+                    tiles.push((x as u32, y as u32, decompress(data, tile_compression)?));
+                }
+            }
+        }
+    }
+
+    Ok(Level {
+        bounds: from_extent([e7_at(102), e7_at(106), e7_at(110), e7_at(114)]),
+        zoom,
+        tiles,
+    })
+}
+
+fn gzip(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(bytes)?;
+    encoder.finish()
+}
+
+fn decompress(bytes: &[u8], compression: u8) -> Result<Vec<u8>> {
+    match compression {
+        COMPRESSION_NONE => Ok(bytes.to_vec()),
+        COMPRESSION_GZIP => {
+            let mut decompressed = Vec::new();
+            GzDecoder::new(bytes).read_to_end(&mut decompressed)?;
+            Ok(decompressed)
+        }
+        _ => Err(invalid_data("unsupported PMTiles compression")),
+    }
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    buffer.push(value as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<u64> {
+    let mut value = 0;
+
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes
+            .split_first()
+            .ok_or_else(|| invalid_data("truncated directory"))?;
+        *bytes = rest;
+
+        value |= u64::from(byte & 0x7f) << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    Err(invalid_data("varint longer than 64 bits"))
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
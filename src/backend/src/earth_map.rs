@@ -1,38 +1,124 @@
-use crate::{Bounds, Dataset, Tile};
+use crate::{Bounds, Dataset, Layer, Tile};
+use common::codec::Codec;
 use geo::Coord;
-use image::{DynamicImage, ImageBuffer, ImageReader, Rgba};
-use std::{fs::File, io::BufReader};
+use image::{ImageBuffer, Rgba};
+use std::path::Path;
 
+/// A color image, read from the file only a window at a time, so that
+/// large images are never held in memory as a whole.
 pub struct EarthmapDataset {
-    data: DynamicImage,
+    data: gdal::Dataset,
+    bounds: Bounds,
 }
 
 impl EarthmapDataset {
-    pub fn new<P>(path: P) -> Self
+    /// Opens an image covering the whole globe.
+    pub fn new<P>(path: P) -> gdal::errors::Result<Self>
     where
         P: AsRef<std::path::Path>,
     {
-        let file = File::open(path).unwrap();
-        let reader = BufReader::new(file);
+        let bounds = Bounds::new(
+            Coord { x: -180.0, y: 90.0 }, // Northwest
+            Coord { x: 180.0, y: -90.0 }, // Southeast
+        );
 
-        let mut reader = ImageReader::new(reader).with_guessed_format().unwrap();
+        Self::with_bounds(path, bounds)
+    }
 
-        reader.no_limits();
+    /// Opens an image positioned by its georeference, or by its name if it
+    /// is one of the Blue Marble panels, failing if it is neither.
+    pub fn georeferenced<P>(path: P) -> gdal::errors::Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let data = gdal::Dataset::open(path.as_ref())?;
+
+        let [x, pixel_width, _, y, _, pixel_height] = match data.geo_transform() {
+            Ok(transform) => transform,
+            Err(err) => {
+                let bounds = blue_marble_panel(path.as_ref()).ok_or(err)?;
+
+                return Ok(Self { data, bounds });
+            }
+        };
+
+        let (width, height) = data.raster_size();
+
+        let bounds = Bounds::new(
+            Coord {
+                x: x as f32,
+                y: y as f32,
+            },
+            Coord {
+                x: (x + width as f64 * pixel_width) as f32,
+                y: (y + height as f64 * pixel_height) as f32,
+            },
+        );
 
-        let decoder = reader.into_decoder().unwrap();
+        Ok(Self { data, bounds })
+    }
 
-        let image = DynamicImage::from_decoder(decoder).unwrap();
+    pub fn with_bounds<P>(path: P, bounds: Bounds) -> gdal::errors::Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        Ok(Self {
+            data: gdal::Dataset::open(path)?,
+            bounds,
+        })
+    }
 
-        EarthmapDataset { data: image }
+    /// Reads a window of the band at `index`, counting from 1.
+    fn band(&self, index: usize, x: usize, y: usize, width: usize, height: usize) -> Vec<u8> {
+        self.data
+            .rasterband(index)
+            .and_then(|band| {
+                band.read_as::<u8>(
+                    (x as isize, y as isize),
+                    (width, height),
+                    (width, height),
+                    None,
+                )
+            })
+            .unwrap()
+            .into_shape_and_vec()
+            .1
     }
 }
 
+/// The bounds of a Blue Marble panel, named like
+/// `world.topo.bathy.200412.3x21600x21600.A1.png`. The columns `A` to `D`
+/// run east from the antimeridian and the rows `1` and `2` south from the
+/// north pole, each panel spanning 90 degrees.
+pub fn blue_marble_panel(path: &Path) -> Option<Bounds> {
+    let panel = path.file_stem()?.to_str()?.rsplit('.').next()?;
+
+    let &[column @ b'A'..=b'D', row @ b'1'..=b'2'] = panel.as_bytes() else {
+        return None;
+    };
+
+    let west = -180.0 + 90.0 * (column - b'A') as f32;
+    let north = 90.0 - 90.0 * (row - b'1') as f32;
+
+    Some(Bounds::new(
+        Coord { x: west, y: north },
+        Coord {
+            x: west + 90.0,
+            y: north - 90.0,
+        },
+    ))
+}
+
 type Pixel = [u8; 4];
 
-impl Dataset for EarthmapDataset {
+impl Layer for EarthmapDataset {
     type Type = Pixel;
     type AggregateType = ();
 
+    const CHILDREN_PER_AXIS: usize = 2;
+}
+
+impl Dataset for EarthmapDataset {
     fn downsample(data: &Tile<Pixel>) -> Tile<Pixel> {
         let pixels: Vec<_> = data.iter().flatten().flatten().copied().collect();
 
@@ -54,22 +140,62 @@ impl Dataset for EarthmapDataset {
         [0; 4]
     }
 
-    fn data(&self) -> Tile<Pixel> {
-        self.data
-            .to_rgba8()
-            .rows()
-            .map(|row| row.map(|pixel| pixel.0).collect())
-            .collect()
+    fn size(&self) -> (usize, usize) {
+        self.data.raster_size()
+    }
+
+    /// Reads gray, gray and alpha, RGB or RGBA images by their number of
+    /// bands. Images of one band with a palette are looked up in it.
+    fn window(&self, x: usize, y: usize, width: usize, height: usize) -> Tile<Pixel> {
+        let band = |index| self.band(index, x, y, width, height);
+
+        let pixels: Vec<Pixel> = match self.data.raster_count() {
+            1 => {
+                let first = self.data.rasterband(1).unwrap();
+                let palette = first.color_table();
+
+                let colors: Vec<Pixel> = (0..=u8::MAX as usize)
+                    .map(|index| {
+                        let Some(color) = palette.as_ref().and_then(|p| p.entry_as_rgb(index))
+                        else {
+                            return [index as u8, index as u8, index as u8, u8::MAX];
+                        };
+
+                        [color.r, color.g, color.b, color.a].map(|c| c.clamp(0, 255) as u8)
+                    })
+                    .collect();
+
+                band(1)
+                    .into_iter()
+                    .map(|index| colors[index as usize])
+                    .collect()
+            }
+            2 => band(1)
+                .into_iter()
+                .zip(band(2))
+                .map(|(gray, alpha)| [gray, gray, gray, alpha])
+                .collect(),
+            3 => {
+                let (r, g, b) = (band(1), band(2), band(3));
+
+                (0..r.len()).map(|i| [r[i], g[i], b[i], u8::MAX]).collect()
+            }
+            _ => {
+                let (r, g, b, a) = (band(1), band(2), band(3), band(4));
+
+                (0..r.len()).map(|i| [r[i], g[i], b[i], a[i]]).collect()
+            }
+        };
+
+        pixels.chunks(width).map(<[Pixel]>::to_vec).collect()
     }
 
     fn bounds(&self) -> Bounds {
-        Bounds::new(
-            Coord { x: -180.0, y: 90.0 }, // Northwest
-            Coord { x: 180.0, y: -90.0 }, // Southeast
-        )
+        self.bounds
     }
 
     const TILE_SIZE: u32 = 256;
-    const CHILDREN_PER_AXIS: usize = 2;
     const MAX_LEVEL: u32 = 2;
+
+    const CODEC: Codec = Codec::ShuffleDeltaLz4;
 }
@@ -0,0 +1,94 @@
+use std::path::Path;
+
+use common::{codec::Codec, Bounds};
+use geo::Coord;
+
+use crate::{downsample_mean, Dataset, Layer, Tile};
+
+/// Height of the terrain above sea level in meters, such as GTOPO30 or
+/// SRTM. Pixels without data, like the oceans of GTOPO30, are at sea level.
+pub struct ElevationDataset {
+    data: gdal::Dataset,
+    bounds: Bounds,
+}
+
+impl ElevationDataset {
+    /// Opens a raster positioned by its georeference, refusing files
+    /// without one, as their place on the globe is unknown.
+    pub fn new<P>(path: P) -> gdal::errors::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let data = gdal::Dataset::open(path)?;
+
+        let [x, pixel_width, _, y, _, pixel_height] = data.geo_transform()?;
+        let (width, height) = data.raster_size();
+
+        let bounds = Bounds::new(
+            Coord {
+                x: x as f32,
+                y: y as f32,
+            },
+            Coord {
+                x: (x + width as f64 * pixel_width) as f32,
+                y: (y + height as f64 * pixel_height) as f32,
+            },
+        );
+
+        Ok(Self { data, bounds })
+    }
+}
+
+impl Layer for ElevationDataset {
+    type Type = f32;
+    type AggregateType = ();
+
+    const CHILDREN_PER_AXIS: usize = 2;
+}
+
+impl Dataset for ElevationDataset {
+    fn downsample(data: &Tile<Self::Type>) -> Tile<Self::Type> {
+        downsample_mean(data, Self::TILE_SIZE as usize)
+    }
+
+    fn default() -> Self::Type {
+        0.
+    }
+
+    fn size(&self) -> (usize, usize) {
+        self.data.raster_size()
+    }
+
+    fn window(&self, x: usize, y: usize, width: usize, height: usize) -> Tile<Self::Type> {
+        let band = self.data.rasterband(1).unwrap();
+        let no_data = band.no_data_value().map(|value| value as f32);
+
+        let ((cols, _), mut data) = band
+            .read_as::<Self::Type>(
+                (x as isize, y as isize),
+                (width, height),
+                (width, height),
+                None,
+            )
+            .unwrap()
+            .into_shape_and_vec();
+
+        for value in &mut data {
+            if Some(*value) == no_data || !value.is_finite() {
+                *value = Self::default();
+            }
+        }
+
+        data.chunks(cols).map(|chunk| chunk.to_vec()).collect()
+    }
+
+    fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    const TILE_SIZE: u32 = 256;
+
+    const MAX_LEVEL: u32 = 5;
+
+    const CODEC: Codec = Codec::ShuffleDeltaLz4;
+}
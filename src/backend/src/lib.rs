@@ -1,16 +1,36 @@
-use std::{fs::File, io::Result, path::Path};
-
+use std::{
+    fs::File,
+    io::{Result, Write},
+    path::Path,
+};
+
+use bytemuck::Pod;
+use common::codec::Codec;
 use geo::{Coord, Intersects, Rect};
-use serialize::{AlignedWriter, Serialize};
+use serialize::{serialize_tree, AlignedWriter};
 
-pub mod deserialize;
 pub mod serialize;
 
+pub use common::{deserialize, Layer};
+
+pub mod archive;
 pub mod earth_map;
+pub mod elevation;
 pub mod light_pollution;
+pub mod mosaic;
 pub mod population;
-
-fn slice<D>(data: &Tile<D::Type>, x: usize, y: usize, width: usize, height: usize) -> Tile<D::Type>
+pub mod region;
+pub mod temporal;
+pub mod update;
+pub mod vector;
+
+pub(crate) fn slice<D>(
+    data: &Tile<D::Type>,
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> Tile<D::Type>
 where
     D: Dataset,
     D::Type: Clone,
@@ -46,29 +66,84 @@ where
     result
 }
 
+/// Shrinks `data` to `size` by `size` pixels, each the mean of the pixels
+/// it covers.
+pub(crate) fn downsample_mean(data: &Tile<f32>, size: usize) -> Tile<f32> {
+    let input_height = data.len();
+    let input_width = data[0].len();
+
+    let scale_y = input_height as f32 / size as f32;
+    let scale_x = input_width as f32 / size as f32;
+
+    let mut output = vec![vec![0.0; size]; size];
+
+    #[allow(clippy::needless_range_loop)]
+    for out_y in 0..size {
+        for out_x in 0..size {
+            let y0 = (out_y as f32 * scale_y).floor() as usize;
+            let y1 = ((out_y + 1) as f32 * scale_y)
+                .ceil()
+                .min(input_height as f32) as usize;
+
+            let x0 = (out_x as f32 * scale_x).floor() as usize;
+            let x1 = ((out_x + 1) as f32 * scale_x)
+                .ceil()
+                .min(input_width as f32) as usize;
+
+            let mut sum = 0.0;
+            let mut count = 0.0;
+
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    sum += data[y][x];
+                    count += 1.0;
+                }
+            }
+
+            output[out_y][out_x] = if count > 0.0 { sum / count } else { 0.0 };
+        }
+    }
+
+    output
+}
+
 pub type Tile<T> = Vec<Vec<T>>;
 pub type Bounds = Rect<f32>;
 
-pub trait Dataset {
-    type Type;
-    type AggregateType;
+/// Region of a dataset covered by a node, as `(x, y, width, height)` in
+/// pixels.
+pub(crate) type Window = (usize, usize, usize, usize);
 
+pub trait Dataset: Layer {
     fn aggregate(_values: &[Self::Type]) -> Option<Self::AggregateType> {
         None
     }
-    fn aggregate2(_values: &[Self::AggregateType]) -> Option<Self::AggregateType> {
-        None
-    }
 
     fn downsample(data: &Tile<Self::Type>) -> Tile<Self::Type>;
     fn default() -> Self::Type;
 
-    fn data(&self) -> Tile<Self::Type>;
+    /// Reads the whole dataset at once.
+    fn data(&self) -> Tile<Self::Type> {
+        let (width, height) = self.size();
+
+        self.window(0, 0, width, height)
+    }
+
+    /// Width and height of the dataset in pixels.
+    fn size(&self) -> (usize, usize);
+
+    /// Reads `width` by `height` pixels starting at column `x` and row `y`,
+    /// without reading the rest of the dataset. The window always lies
+    /// within [`Dataset::size`].
+    fn window(&self, x: usize, y: usize, width: usize, height: usize) -> Tile<Self::Type>;
+
     fn bounds(&self) -> Bounds;
 
     const TILE_SIZE: u32;
-    const CHILDREN_PER_AXIS: usize;
     const MAX_LEVEL: u32;
+
+    /// How tiles are compressed when written to the database.
+    const CODEC: Codec = Codec::None;
 }
 
 pub fn flatten<T>(data: Vec<Vec<&Tile<T>>>) -> Tile<T>
@@ -175,7 +250,9 @@ where
             children: Vec::new(),
         };
 
-        Self::recursive_slice(&mut root, data.data());
+        let (width, height) = data.size();
+
+        Self::recursive_slice(&mut root, data, (0, 0, width, height));
         Self::propagate(&mut root);
 
         Self { root }
@@ -211,11 +288,16 @@ where
         parent.aggregate = D::aggregate2(&aggregates);
     }
 
-    fn recursive_slice(parent: &mut TileNode<D::Type, D::AggregateType>, data: Tile<D::Type>) {
-        let height = data.len();
-        let width = data[0].len();
-
+    /// Splits `window` of `dataset` until it fits into a tile, reading only
+    /// the windows of the leaves from the dataset.
+    fn recursive_slice(
+        parent: &mut TileNode<D::Type, D::AggregateType>,
+        dataset: &D,
+        (x, y, width, height): Window,
+    ) {
         if height as u32 <= D::TILE_SIZE && width as u32 <= D::TILE_SIZE {
+            let data = dataset.window(x, y, width, height);
+
             parent.aggregate = D::aggregate(&data.iter().flatten().copied().collect::<Vec<_>>());
             parent.data = Some(data);
 
@@ -244,8 +326,7 @@ where
                             child_height
                         };
 
-                        let child_data =
-                            slice::<D>(&data, x_start, y_start, actual_width, actual_height);
+                        let window = (x + x_start, y + y_start, actual_width, actual_height);
 
                         let bounds_delta_w = parent.bounds.width() / D::CHILDREN_PER_AXIS as f32;
                         let bounds_delta_h = parent.bounds.height() / D::CHILDREN_PER_AXIS as f32;
@@ -267,7 +348,7 @@ where
                             children: Vec::new(),
                         };
 
-                        Self::recursive_slice(&mut child, child_data);
+                        Self::recursive_slice(&mut child, dataset, window);
 
                         child
                     })
@@ -279,15 +360,23 @@ where
     pub fn write_to_file<P>(&self, path: P) -> Result<()>
     where
         P: AsRef<Path>,
-        TileNode<D::Type, D::AggregateType>: Serialize,
+        D::Type: Pod,
+        D::AggregateType: Pod,
     {
         let Ok(file) = File::create_new(path) else {
             return Ok(());
         };
 
-        let mut writer = AlignedWriter::new(&file);
-        self.root.serialize(&mut writer)?;
+        self.write(&file)
+    }
 
-        Ok(())
+    pub fn write<W>(&self, writer: W) -> Result<()>
+    where
+        W: Write,
+        D::Type: Pod,
+        D::AggregateType: Pod,
+    {
+        let mut writer = AlignedWriter::new(writer);
+        serialize_tree(&self.root, D::CODEC, &mut writer)
     }
 }
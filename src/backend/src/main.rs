@@ -1,25 +1,40 @@
 use axum::{
     body::Body,
     extract::{Path, Query, State},
-    http::{HeaderValue, Response},
+    http::{header, HeaderMap, HeaderValue, Response, StatusCode},
     response::IntoResponse,
     routing::{get, post},
     Json, Router,
 };
 use backend::{
-    deserialize::GeoTree, earth_map::EarthmapDataset, light_pollution::LightPollutionDataset,
-    population::PopulationDataset, Bounds, Dataset,
+    archive::{self, ArchiveDataset},
+    deserialize::{self, GeoTree},
+    earth_map::EarthmapDataset,
+    elevation::ElevationDataset,
+    light_pollution::LightPollutionDataset,
+    mosaic::{MosaicDataset, Overlap},
+    population::PopulationDataset,
+    region,
+    temporal::TemporalGeoTree,
+    vector::{self, VectorStore},
+    Bounds, Dataset,
 };
 use bytemuck::Pod;
+use common::{CircleQuery, COMPRESSED_TILE_MEDIA_TYPE};
 use geo::{Coord, Polygon};
 use serde::Deserialize;
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::{Error, ErrorKind},
+    net::SocketAddr,
+    sync::Arc,
+};
 use tower_http::{services::ServeDir, trace::TraceLayer};
 
 fn initialize_tree<P, F, D>(path: P, dataset: F) -> std::io::Result<GeoTree<D>>
 where
     P: AsRef<std::path::Path>,
-    F: Fn() -> D,
+    F: Fn() -> std::io::Result<D>,
     D: Dataset,
     D::Type: Copy + Pod,
     D::AggregateType: Copy + Pod,
@@ -29,52 +44,305 @@ where
     }
 
     {
-        let tree = backend::GeoTree::build(&dataset());
+        let tree = backend::GeoTree::build(&dataset()?);
         tree.write_to_file(&path)?;
     }
 
     GeoTree::new(path)
 }
 
+/// Opens the vector store at `path`, ingesting the files matching
+/// `pattern` into it first if it does not exist yet.
+fn initialize_vectors<P>(path: P, pattern: &str) -> std::io::Result<VectorStore>
+where
+    P: AsRef<std::path::Path>,
+{
+    if path.as_ref().try_exists()? {
+        return VectorStore::open(path);
+    }
+
+    let paths = glob::glob(pattern)
+        .map_err(|err| Error::new(ErrorKind::InvalidInput, format!("{pattern}: {err}")))?
+        .map(|path| path.map_err(|err| Error::new(err.error().kind(), err.to_string())))
+        .collect::<std::io::Result<Vec<_>>>()?;
+
+    let store = VectorStore::ingest(paths)?;
+    store.write_to_file(path)?;
+
+    Ok(store)
+}
+
+/// Rebuilds the parts of `path` intersecting `region` from `dataset`,
+/// then replaces the old database with the updated one.
+fn update_tree<P, D>(path: P, dataset: D, region: Bounds) -> std::io::Result<()>
+where
+    P: AsRef<std::path::Path>,
+    D: Dataset,
+    D::Type: Copy + Pod,
+    D::AggregateType: Copy + Pod,
+{
+    let path = path.as_ref();
+    let updated = path.with_extension("db.new");
+
+    // A file left behind by an update that failed is simply overwritten
+    let written = GeoTree::<D>::new(path).and_then(|tree| {
+        let file = std::fs::File::create(&updated)?;
+        backend::update::update(&tree, &dataset, region, &file)
+    });
+
+    if let Err(err) = written {
+        let _ = std::fs::remove_file(&updated);
+        return Err(err);
+    }
+
+    std::fs::rename(updated, path)
+}
+
+fn update(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str =
+        "usage: backend update <earth_map|light_pollution|elevation> <west> <south> <east> <north>";
+
+    let layer = args.next().ok_or(USAGE)?;
+
+    let coordinates = args
+        .map(|arg| arg.parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let [west, south, east, north] = coordinates[..] else {
+        return Err(USAGE.into());
+    };
+
+    let region = Bounds::new(Coord { x: west, y: south }, Coord { x: east, y: north });
+
+    match layer.as_str() {
+        "earth_map" => update_tree("earth_map.db", earth_map_dataset()?, region)?,
+        "light_pollution" => update_tree("light_pollution.db", light_pollution_dataset(), region)?,
+        "elevation" => update_tree("elevation.db", elevation_dataset()?, region)?,
+        _ => return Err(USAGE.into()),
+    }
+
+    Ok(())
+}
+
+fn export(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "usage: backend export <earth_map|light_pollution> <levels> <output>";
+
+    let layer = args.next().ok_or(USAGE)?;
+    let levels = args.next().ok_or(USAGE)?.parse::<usize>()?;
+    let output = args.next().ok_or(USAGE)?;
+
+    // Archives hold images, so only the satellite layer can be exported
+    if output.ends_with(".mbtiles") || output.ends_with(".pmtiles") {
+        if layer != "earth_map" {
+            return Err("only earth_map can be exported as an archive".into());
+        }
+
+        let tree = GeoTree::<MosaicDataset<EarthmapDataset>>::new("earth_map.db")?;
+
+        if output.ends_with(".mbtiles") {
+            archive::mbtiles::export(&tree, levels, &output)?;
+        } else {
+            archive::pmtiles::export(&tree, levels, &output)?;
+        }
+
+        return Ok(());
+    }
+
+    let output = std::fs::File::create_new(output)?;
+
+    match layer.as_str() {
+        "earth_map" => backend::update::truncate(
+            &GeoTree::<MosaicDataset<EarthmapDataset>>::new("earth_map.db")?,
+            levels,
+            &output,
+        )?,
+        "light_pollution" => backend::update::truncate(
+            &GeoTree::<LightPollutionDataset>::new("light_pollution.db")?,
+            levels,
+            &output,
+        )?,
+        _ => return Err(USAGE.into()),
+    }
+
+    Ok(())
+}
+
+fn import(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "usage: backend import <archive.mbtiles|archive.pmtiles> <output>";
+
+    let dataset = ArchiveDataset::open(args.next().ok_or(USAGE)?)?;
+    let output = std::fs::File::create_new(args.next().ok_or(USAGE)?)?;
+
+    backend::GeoTree::build(&dataset).write(&output)?;
+
+    Ok(())
+}
+
+fn info(mut args: impl Iterator<Item = String>) -> Result<(), Box<dyn std::error::Error>> {
+    const USAGE: &str = "usage: backend info <earth_map|light_pollution|elevation>";
+
+    let info = match args.next().as_deref() {
+        Some("earth_map") => {
+            GeoTree::<MosaicDataset<EarthmapDataset>>::new("earth_map.db")?.info()?
+        }
+        Some("light_pollution") => {
+            GeoTree::<LightPollutionDataset>::new("light_pollution.db")?.info()?
+        }
+        Some("elevation") => {
+            GeoTree::<MosaicDataset<ElevationDataset>>::new("elevation.db")?.info()?
+        }
+        _ => return Err(USAGE.into()),
+    };
+
+    println!("{info}");
+
+    Ok(())
+}
+
+fn earth_map_dataset() -> std::io::Result<MosaicDataset<EarthmapDataset>> {
+    let key = "EARTH_MAP_DATASET";
+
+    let overlap = match std::env::var("EARTH_MAP_OVERLAP").as_deref() {
+        Ok("blend") => Overlap::Blend,
+        _ => Overlap::FirstWins,
+    };
+
+    let pattern = std::env::var(key).unwrap_or_else(|_| panic!("{key} environment variable"));
+
+    // A single image covers the whole globe, like Blue Marble's world map
+    if std::path::Path::new(&pattern).is_file() {
+        let dataset = EarthmapDataset::new(&pattern)
+            .map_err(|err| Error::other(format!("{pattern}: {err}")))?;
+
+        return Ok(MosaicDataset::new(vec![dataset], overlap));
+    }
+
+    MosaicDataset::from_glob(&pattern, overlap, EarthmapDataset::georeferenced)
+}
+
+fn light_pollution_dataset() -> LightPollutionDataset {
+    let key = "LIGHT_POLLUTION_DATASET";
+
+    LightPollutionDataset::new(
+        std::env::var(key).unwrap_or_else(|_| panic!("{key} environment variable")),
+    )
+}
+
+fn elevation_dataset() -> std::io::Result<MosaicDataset<ElevationDataset>> {
+    let key = "ELEVATION_DATASET";
+
+    // Elevation usually comes in tiles, such as those of GTOPO30 or SRTM
+    MosaicDataset::from_glob(
+        &std::env::var(key).unwrap_or_else(|_| panic!("{key} environment variable")),
+        Overlap::FirstWins,
+        ElevationDataset::new,
+    )
+}
+
 #[tokio::main(flavor = "multi_thread", worker_threads = 16)]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let earth_map_tree = {
-        let key = "EARTH_MAP_DATASET";
-        let dataset = || {
-            EarthmapDataset::new(
-                std::env::var(key).unwrap_or_else(|_| panic!("{key} environment variable")),
-            )
-        };
+    let mut args = std::env::args().skip(1);
+
+    match args.next().as_deref() {
+        Some("update") => return update(args),
+        Some("info") => return info(args),
+        Some("export") => return export(args),
+        Some("import") => return import(args),
+        _ => {}
+    }
 
-        Arc::new(initialize_tree("earth_map.db", dataset)?)
-    };
+    let earth_map_tree = Arc::new(initialize_tree("earth_map.db", earth_map_dataset)?);
 
     // let population_tree = {
     //     let key = "POPULATION_DATASET";
     //     let dataset = || {
-    //         PopulationDataset::new(
+    //         Ok(PopulationDataset::new(
     //             std::env::var(key).unwrap_or_else(|_| panic!("{key} environment variable")),
-    //         )
+    //         ))
     //     };
 
     //     Arc::new(initialize_tree("population.db", dataset)?)
     // };
 
-    let light_pollution_tree = {
-        let key = "LIGHT_POLLUTION_DATASET";
-        let dataset = || {
-            LightPollutionDataset::new(
-                std::env::var(key).unwrap_or_else(|_| panic!("{key} environment variable")),
-            )
-        };
+    let light_pollution_tree = Arc::new(initialize_tree("light_pollution.db", || {
+        Ok(light_pollution_dataset())
+    })?);
+
+    // Elevation is optional, shown as terrain when available
+    let elevation_tree = match std::env::var("ELEVATION_DATASET") {
+        Ok(_) => Some(Arc::new(initialize_tree(
+            "elevation.db",
+            elevation_dataset,
+        )?)),
+        Err(_) => None,
+    };
 
-        Arc::new(initialize_tree("light_pollution.db", dataset)?)
+    // Timesteps are optional, each matching file is one timestep
+    // labelled by its file stem, e.g. `2024-01.tif`.
+    let light_pollution_series = {
+        let mut timesteps = BTreeMap::new();
+
+        if let Ok(pattern) = std::env::var("LIGHT_POLLUTION_SERIES") {
+            let paths = glob::glob(&pattern)
+                .map_err(|err| Error::new(ErrorKind::InvalidInput, format!("{pattern}: {err}")))?;
+
+            for path in paths {
+                let path = path.map_err(|err| Error::new(err.error().kind(), err.to_string()))?;
+
+                let time = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::InvalidInput,
+                            format!("{}: file name is not a UTF-8 timestep", path.display()),
+                        )
+                    })?
+                    .to_owned();
+
+                let tree = initialize_tree(format!("light_pollution_{time}.db"), || {
+                    Ok(LightPollutionDataset::new(&path))
+                })?;
+
+                timesteps.insert(time, tree);
+            }
+        }
+
+        TemporalGeoTree::new(timesteps)
+    };
+
+    let layers = Arc::new(HashMap::from([(
+        "light_pollution".to_owned(),
+        light_pollution_series,
+    )]));
+
+    // Vector layers are optional, each set from the vector files matching
+    // its variable, e.g. `BORDERS_DATASET=borders/*.shp`.
+    let vectors = {
+        let mut vectors = HashMap::new();
+
+        for (name, key) in [
+            ("borders", "BORDERS_DATASET"),
+            ("coastlines", "COASTLINES_DATASET"),
+        ] {
+            if let Ok(pattern) = std::env::var(key) {
+                vectors.insert(
+                    name.to_owned(),
+                    initialize_vectors(format!("{name}.vec"), &pattern)?,
+                );
+            }
+        }
+
+        Arc::new(vectors)
     };
 
     let state = BackendState {
         earth_map_tree,
         // population_tree,
         light_pollution_tree,
+        elevation_tree,
+        layers,
+        vectors,
     };
 
     let addr = SocketAddr::from(([127, 0, 0, 1], 8000));
@@ -86,8 +354,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .route("/tiles", get(get_tiles))
         .route("/sat_tile/{z}/{y}/{x}", get(get_tile))
         .route("/light_p_tile/{z}/{y}/{x}", get(get_lp_tile))
+        .route("/elevation_tile/{z}/{y}/{x}", get(get_elevation_tile))
         // .route("/pop_tile/{z}/{y}/{x}", get(get_pop_tile))
         .route("/aggregate/lp", post(post_lp_aggregate))
+        .route("/aggregate/lp/circle", post(post_lp_circle_aggregate))
+        .route("/layers/{name}/times", get(get_layer_times))
+        .route(
+            "/layers/{name}/{time}/tile/{z}/{y}/{x}",
+            get(get_layer_tile),
+        )
+        .route("/layers/{name}/aggregate", post(post_layer_aggregate))
+        .route("/vector/{name}/{z}/{y}/{x}", get(get_vector_tile))
         // .route("/aggregate/pop", post(post_pop_aggregate))
         .with_state(state);
 
@@ -100,9 +377,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
 #[derive(Clone)]
 struct BackendState {
-    earth_map_tree: Arc<GeoTree<EarthmapDataset>>,
+    earth_map_tree: Arc<GeoTree<MosaicDataset<EarthmapDataset>>>,
     // population_tree: Arc<GeoTree<PopulationDataset>>,
     light_pollution_tree: Arc<GeoTree<LightPollutionDataset>>,
+    elevation_tree: Option<Arc<GeoTree<MosaicDataset<ElevationDataset>>>>,
+    layers: Arc<HashMap<String, TemporalGeoTree<LightPollutionDataset>>>,
+    vectors: Arc<HashMap<String, VectorStore>>,
 }
 
 #[derive(Deserialize)]
@@ -116,7 +396,19 @@ async fn get_tiles(
 ) -> impl IntoResponse {
     let query = Bounds::new(Coord { x: -180., y: 90. }, Coord { x: 180., y: -90. });
 
-    Json(state.earth_map_tree.get_tiles(query, tile_query.level)).into_response()
+    json_response(state.earth_map_tree.get_tiles(query, tile_query.level))
+}
+
+/// Answers with `result` as JSON, or with an error if the database it was
+/// read from is damaged.
+fn json_response<T>(result: Result<T, deserialize::Error>) -> Response<Body>
+where
+    T: serde::Serialize,
+{
+    match result {
+        Ok(value) => Json(value).into_response(),
+        Err(err) => (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+    }
 }
 
 #[derive(Deserialize)]
@@ -142,36 +434,109 @@ struct TileQuery {
 //         .unwrap()
 // }
 
-async fn get_lp_tile(
-    Path(TileQuery { x, y, z }): Path<TileQuery>,
-    State(state): State<BackendState>,
-) -> impl IntoResponse {
-    let data: Vec<u8> =
-        bincode::serialize(&state.light_pollution_tree.get_tile(x, y, z).unwrap()).unwrap();
+/// Serves a tile of `tree`. Clients accepting [`COMPRESSED_TILE_MEDIA_TYPE`]
+/// get compressed tiles as they are stored, usually straight from the
+/// database without copying them. Damaged tiles are answered with an error
+/// instead.
+fn tile_response<D>(
+    tree: &GeoTree<D>,
+    TileQuery { x, y, z }: TileQuery,
+    headers: &HeaderMap,
+) -> Response<Body>
+where
+    D: Dataset,
+    D::Type: Pod + serde::Serialize,
+    D::AggregateType: Pod,
+{
+    let accepts_compressed = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains(COMPRESSED_TILE_MEDIA_TYPE));
+
+    let (body, content_type) = match read_tile(tree, x, y, z, accepts_compressed) {
+        Ok(Some(tile)) => tile,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("tile {z}/{y}/{x} is damaged: {err}"),
+            )
+                .into_response()
+        }
+    };
 
     Response::builder()
         .header(
             "Cache-Control",
             HeaderValue::from_static("public, max-age=31536000, immutable"),
         )
-        .body(Body::from(data))
+        .header(header::VARY, HeaderValue::from_static("Accept"))
+        .header(header::CONTENT_TYPE, HeaderValue::from_static(content_type))
+        .body(body)
         .unwrap()
 }
 
-async fn get_tile(
-    Path(TileQuery { x, y, z }): Path<TileQuery>,
+/// Reads a tile of `tree` as a body and its content type, compressed if
+/// the client accepts it and the tile is stored that way.
+fn read_tile<D>(
+    tree: &GeoTree<D>,
+    x: usize,
+    y: usize,
+    z: usize,
+    compressed: bool,
+) -> Result<Option<(Body, &'static str)>, deserialize::Error>
+where
+    D: Dataset,
+    D::Type: Pod + serde::Serialize,
+    D::AggregateType: Pod,
+{
+    if compressed {
+        if let Some(bytes) = tree.get_tile_bytes(x, y, z)? {
+            return Ok(Some((Body::from(bytes), COMPRESSED_TILE_MEDIA_TYPE)));
+        }
+
+        if let Some(tile) = tree.get_compressed_tile(x, y, z)? {
+            let body = Body::from(bincode::serialize(&tile)?);
+
+            return Ok(Some((body, COMPRESSED_TILE_MEDIA_TYPE)));
+        }
+    }
+
+    let Some(tile) = tree.get_tile(x, y, z)? else {
+        return Ok(None);
+    };
+
+    let body = Body::from(bincode::serialize(&tile)?);
+
+    Ok(Some((body, "application/octet-stream")))
+}
+
+async fn get_lp_tile(
+    Path(query): Path<TileQuery>,
     State(state): State<BackendState>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    let data: Vec<u8> =
-        bincode::serialize(&state.earth_map_tree.get_tile(x, y, z).unwrap()).unwrap();
+    tile_response(&state.light_pollution_tree, query, &headers)
+}
 
-    Response::builder()
-        .header(
-            "Cache-Control",
-            HeaderValue::from_static("public, max-age=31536000, immutable"),
-        )
-        .body(Body::from(data))
-        .unwrap()
+async fn get_elevation_tile(
+    Path(query): Path<TileQuery>,
+    State(state): State<BackendState>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let Some(tree) = &state.elevation_tree else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    tile_response(tree, query, &headers)
+}
+
+async fn get_tile(
+    Path(query): Path<TileQuery>,
+    State(state): State<BackendState>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    tile_response(&state.earth_map_tree, query, &headers)
 }
 
 // async fn post_pop_aggregate(
@@ -183,13 +548,132 @@ async fn get_tile(
 //     Json(aggregate)
 // }
 
+async fn get_layer_times(
+    Path(name): Path<String>,
+    State(state): State<BackendState>,
+) -> impl IntoResponse {
+    let Some(layer) = state.layers.get(&name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    Json(layer.timesteps()).into_response()
+}
+
+#[derive(Deserialize)]
+struct LayerTileQuery {
+    name: String,
+    time: String,
+    x: usize,
+    y: usize,
+    z: usize,
+}
+
+async fn get_layer_tile(
+    Path(LayerTileQuery {
+        name,
+        time,
+        x,
+        y,
+        z,
+    }): Path<LayerTileQuery>,
+    State(state): State<BackendState>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let Some(tree) = state
+        .layers
+        .get(&name)
+        .and_then(|layer| layer.timestep(&time))
+    else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    tile_response(tree, TileQuery { x, y, z }, &headers)
+}
+
+#[derive(Deserialize)]
+struct VectorTileQuery {
+    name: String,
+    x: usize,
+    y: usize,
+    z: usize,
+}
+
+async fn get_vector_tile(
+    Path(VectorTileQuery { name, x, y, z }): Path<VectorTileQuery>,
+    State(state): State<BackendState>,
+) -> impl IntoResponse {
+    let Some(store) = state.vectors.get(&name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let Some(tile) = store.tile(x, y, z) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("vector tiles only go down to level {}", vector::MAX_LEVEL),
+        )
+            .into_response();
+    };
+
+    let body = match bincode::serialize(&tile) {
+        Ok(body) => body,
+        Err(err) => return (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()).into_response(),
+    };
+
+    (
+        [
+            (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+            (header::CONTENT_TYPE, "application/octet-stream"),
+        ],
+        body,
+    )
+        .into_response()
+}
+
+#[derive(Deserialize)]
+struct TimeRangeQuery {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+const INVALID_REGION: &str = "region coordinates must be finite numbers";
+
+async fn post_layer_aggregate(
+    Path(name): Path<String>,
+    Query(TimeRangeQuery { from, to }): Query<TimeRangeQuery>,
+    State(state): State<BackendState>,
+    Json(query): Json<Polygon<f32>>,
+) -> impl IntoResponse {
+    let Some(layer) = state.layers.get(&name) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let Some(query) = region::normalize(&query) else {
+        return (StatusCode::BAD_REQUEST, INVALID_REGION).into_response();
+    };
+
+    json_response(layer.get_aggregate_series(from.as_deref(), to.as_deref(), query))
+}
+
 async fn post_lp_aggregate(
     State(state): State<BackendState>,
     Json(query): Json<Polygon<f32>>,
 ) -> impl IntoResponse {
-    let aggregate = state.light_pollution_tree.get_aggregate(query);
+    let Some(query) = region::normalize(&query) else {
+        return (StatusCode::BAD_REQUEST, INVALID_REGION).into_response();
+    };
+
+    json_response(state.light_pollution_tree.get_aggregate(query))
+}
+
+async fn post_lp_circle_aggregate(
+    State(state): State<BackendState>,
+    Json(query): Json<CircleQuery>,
+) -> impl IntoResponse {
+    let Some(query) = region::circle_query(&query) else {
+        return (StatusCode::BAD_REQUEST, INVALID_REGION).into_response();
+    };
 
-    Json(aggregate)
+    json_response(state.light_pollution_tree.get_aggregate(query))
 }
 
 fn _write_to_image() {
@@ -198,9 +682,9 @@ fn _write_to_image() {
     let population_tree = {
         let key = "POPULATION_DATASET";
         let dataset = || {
-            PopulationDataset::new(
+            Ok(PopulationDataset::new(
                 std::env::var(key).unwrap_or_else(|_| panic!("{key} environment variable")),
-            )
+            ))
         };
 
         initialize_tree("population.db", dataset).unwrap()
@@ -215,6 +699,7 @@ fn _write_to_image() {
                 population_tree
                     .get_tile(x, y, level)
                     .unwrap()
+                    .unwrap()
                     .data
                     .into_iter()
                     .map(|x| x.to_vec())
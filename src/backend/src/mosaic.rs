@@ -0,0 +1,264 @@
+use std::{
+    fmt::Display,
+    io::{Error, ErrorKind, Result},
+    ops::Range,
+    path::PathBuf,
+};
+
+use common::codec::Codec;
+use geo::Coord;
+
+use crate::{Bounds, Dataset, Layer, Tile};
+
+/// How pixels covered by more than one source are resolved.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Overlap {
+    /// The first source in the list that covers a pixel is used.
+    #[default]
+    FirstWins,
+    /// Every source covering a pixel contributes equally.
+    Blend,
+}
+
+pub trait Blend: Copy {
+    /// Interpolates between `self` and `other`, where a `weight` of 1
+    /// yields `other`.
+    fn blend(self, other: Self, weight: f32) -> Self;
+}
+
+impl Blend for [u8; 4] {
+    fn blend(self, other: Self, weight: f32) -> Self {
+        let mut out = self;
+
+        for (channel, other) in out.iter_mut().zip(other) {
+            *channel = (*channel as f32 + (other as f32 - *channel as f32) * weight).round() as u8;
+        }
+
+        out
+    }
+}
+
+impl Blend for f32 {
+    fn blend(self, other: Self, weight: f32) -> Self {
+        self + (other - self) * weight
+    }
+}
+
+/// Combines several datasets into one layer, positioning each source by its
+/// bounds. The mosaic is sampled at the resolution of the first source, and
+/// pixels not covered by any source are filled with [`Dataset::default`].
+/// The sources are never merged as a whole, every window of the mosaic is
+/// pasted together from the windows of the sources it overlaps.
+pub struct MosaicDataset<D> {
+    sources: Vec<D>,
+    overlap: Overlap,
+    bounds: Bounds,
+    size: (usize, usize),
+    /// Where each source lies in the mosaic.
+    placements: Vec<Placement>,
+}
+
+/// The columns and rows of the mosaic a source covers, and its size.
+struct Placement {
+    columns: Range<usize>,
+    rows: Range<usize>,
+    size: (usize, usize),
+}
+
+impl<D> MosaicDataset<D>
+where
+    D: Dataset,
+{
+    pub fn new(sources: Vec<D>, overlap: Overlap) -> Self {
+        assert!(!sources.is_empty(), "Mosaic needs at least one source");
+
+        let bounds = sources
+            .iter()
+            .map(Dataset::bounds)
+            .reduce(|acc, bounds| {
+                Bounds::new(
+                    Coord {
+                        x: acc.min().x.min(bounds.min().x),
+                        y: acc.min().y.min(bounds.min().y),
+                    },
+                    Coord {
+                        x: acc.max().x.max(bounds.max().x),
+                        y: acc.max().y.max(bounds.max().y),
+                    },
+                )
+            })
+            .unwrap();
+
+        // A pixel of the mosaic is the size of one of the first source
+        let (width, height) = sources[0].size();
+        let resolution = Coord {
+            x: sources[0].bounds().width() / width as f32,
+            y: sources[0].bounds().height() / height as f32,
+        };
+
+        let (width, height) = (
+            (bounds.width() / resolution.x).round() as usize,
+            (bounds.height() / resolution.y).round() as usize,
+        );
+
+        let to_column =
+            |x: f32| (((x - bounds.min().x) / resolution.x).round().max(0.) as usize).min(width);
+        let to_row =
+            |y: f32| (((bounds.max().y - y) / resolution.y).round().max(0.) as usize).min(height);
+
+        let placements = sources
+            .iter()
+            .map(|source| {
+                let covered = source.bounds();
+
+                Placement {
+                    columns: to_column(covered.min().x)..to_column(covered.max().x),
+                    rows: to_row(covered.max().y)..to_row(covered.min().y),
+                    size: source.size(),
+                }
+            })
+            .collect();
+
+        Self {
+            sources,
+            overlap,
+            bounds,
+            size: (width, height),
+            placements,
+        }
+    }
+
+    /// Opens every file matching `pattern`, in alphabetical order, failing
+    /// if the pattern is invalid, matches nothing or a file cannot be opened.
+    pub fn from_glob<F, E>(pattern: &str, overlap: Overlap, open: F) -> Result<Self>
+    where
+        F: Fn(PathBuf) -> std::result::Result<D, E>,
+        E: Display,
+    {
+        let paths = glob::glob(pattern)
+            .map_err(|err| Error::new(ErrorKind::InvalidInput, format!("{pattern}: {err}")))?;
+
+        let mut sources = Vec::new();
+
+        for path in paths {
+            let path = path.map_err(|err| Error::new(err.error().kind(), err.to_string()))?;
+            let display = path.display().to_string();
+
+            sources.push(open(path).map_err(|err| Error::other(format!("{display}: {err}")))?);
+        }
+
+        if sources.is_empty() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("no files match {pattern}"),
+            ));
+        }
+
+        Ok(Self::new(sources, overlap))
+    }
+}
+
+impl<D> Layer for MosaicDataset<D>
+where
+    D: Layer,
+{
+    type Type = D::Type;
+    type AggregateType = D::AggregateType;
+
+    fn aggregate2(values: &[Self::AggregateType]) -> Option<Self::AggregateType> {
+        D::aggregate2(values)
+    }
+
+    const CHILDREN_PER_AXIS: usize = D::CHILDREN_PER_AXIS;
+}
+
+impl<D> Dataset for MosaicDataset<D>
+where
+    D: Dataset,
+    D::Type: Blend,
+{
+    fn aggregate(values: &[Self::Type]) -> Option<Self::AggregateType> {
+        D::aggregate(values)
+    }
+
+    fn downsample(data: &Tile<Self::Type>) -> Tile<Self::Type> {
+        D::downsample(data)
+    }
+
+    fn default() -> Self::Type {
+        D::default()
+    }
+
+    fn size(&self) -> (usize, usize) {
+        self.size
+    }
+
+    /// Pastes the part of every source overlapping the window, reading
+    /// only that part of each source. Sources are stretched to the pixels
+    /// of the mosaic they cover, sampling the nearest pixel.
+    fn window(&self, x: usize, y: usize, width: usize, height: usize) -> Tile<Self::Type> {
+        let mut data = vec![vec![D::default(); width]; height];
+        let mut coverage = vec![vec![0_u8; width]; height];
+
+        for (source, placement) in self.sources.iter().zip(&self.placements) {
+            let Placement {
+                columns,
+                rows,
+                size: (source_width, source_height),
+            } = placement;
+
+            let (x0, x1) = (columns.start.max(x), columns.end.min(x + width));
+            let (y0, y1) = (rows.start.max(y), rows.end.min(y + height));
+
+            if x0 >= x1 || y0 >= y1 {
+                continue;
+            }
+
+            let source_x = |column: usize| {
+                ((column - columns.start) * source_width / columns.len()).min(source_width - 1)
+            };
+            let source_y = |row: usize| {
+                ((row - rows.start) * source_height / rows.len()).min(source_height - 1)
+            };
+
+            let (left, top) = (source_x(x0), source_y(y0));
+            let part = source.window(
+                left,
+                top,
+                source_x(x1 - 1) + 1 - left,
+                source_y(y1 - 1) + 1 - top,
+            );
+
+            for row in y0..y1 {
+                let values = &part[source_y(row) - top];
+
+                for column in x0..x1 {
+                    let value = values[source_x(column) - left];
+
+                    let count = &mut coverage[row - y][column - x];
+                    let pixel = &mut data[row - y][column - x];
+
+                    match (self.overlap, *count) {
+                        (_, 0) => *pixel = value,
+                        (Overlap::FirstWins, _) => continue,
+                        (Overlap::Blend, count) => {
+                            *pixel = pixel.blend(value, 1. / (count + 1) as f32)
+                        }
+                    }
+
+                    *count = count.saturating_add(1);
+                }
+            }
+        }
+
+        data
+    }
+
+    fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    const TILE_SIZE: u32 = D::TILE_SIZE;
+    const MAX_LEVEL: u32 = D::MAX_LEVEL;
+    const CODEC: Codec = D::CODEC;
+}
@@ -1,4 +1,4 @@
-use crate::{Bounds, Dataset, Tile};
+use crate::{Bounds, Dataset, Layer, Tile};
 use geo::Coord;
 
 pub struct PopulationDataset {
@@ -16,25 +16,29 @@ impl PopulationDataset {
     }
 }
 
-impl Dataset for PopulationDataset {
+impl Layer for PopulationDataset {
     type Type = f32;
     type AggregateType = f64;
 
-    fn aggregate(values: &[Self::Type]) -> Option<Self::AggregateType> {
+    fn aggregate2(values: &[Self::AggregateType]) -> Option<Self::AggregateType> {
         Some(
             values
                 .iter()
-                .filter(|&&c| c >= Self::default())
-                .map(|&x| x as f64)
+                .filter(|&&c| c >= (Self::default() as f64))
                 .sum(),
         )
     }
 
-    fn aggregate2(values: &[Self::AggregateType]) -> Option<Self::AggregateType> {
+    const CHILDREN_PER_AXIS: usize = 2;
+}
+
+impl Dataset for PopulationDataset {
+    fn aggregate(values: &[Self::Type]) -> Option<Self::AggregateType> {
         Some(
             values
                 .iter()
-                .filter(|&&c| c >= (Self::default() as f64))
+                .filter(|&&c| c >= Self::default())
+                .map(|&x| x as f64)
                 .sum(),
         )
     }
@@ -83,19 +87,25 @@ impl Dataset for PopulationDataset {
         -3.402_823e38
     }
 
-    fn data(&self) -> Tile<Self::Type> {
-        let band = self
+    fn size(&self) -> (usize, usize) {
+        self.data.raster_size()
+    }
+
+    fn window(&self, x: usize, y: usize, width: usize, height: usize) -> Tile<Self::Type> {
+        let (_, data) = self
             .data
-            .rasterbands()
-            .next()
+            .rasterband(1)
             .unwrap()
+            .read_as::<Self::Type>(
+                (x as isize, y as isize),
+                (width, height),
+                (width, height),
+                None,
+            )
             .unwrap()
-            .read_band_as::<Self::Type>()
-            .unwrap();
-
-        let ((cols, _rows), data) = band.into_shape_and_vec();
+            .into_shape_and_vec();
 
-        data.chunks(cols).map(|chunk| chunk.to_vec()).collect()
+        data.chunks(width).map(|chunk| chunk.to_vec()).collect()
     }
 
     fn bounds(&self) -> Bounds {
@@ -110,7 +120,5 @@ impl Dataset for PopulationDataset {
 
     const TILE_SIZE: u32 = 256;
 
-    const CHILDREN_PER_AXIS: usize = 2;
-
     const MAX_LEVEL: u32 = 11;
 }
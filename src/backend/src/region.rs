@@ -0,0 +1,248 @@
+//! Query regions in plain longitude and latitude, normalized so that they
+//! can be aggregated over the trees, whose bounds never wrap around.
+//!
+//! A ring crossing the antimeridian jumps from one side of the map to the
+//! other, and a ring around a pole never comes back to where it started in
+//! longitude. Both are unwrapped into a continuous ring, closed over the
+//! pole where needed, and cut into pieces within -180° to 180°.
+
+use common::{CircleQuery, EARTH_RADIUS_KM};
+use geo::{
+    BooleanOps, Coord, Destination, Haversine, LineString, MultiPolygon, Point, Polygon, Rect,
+    Translate,
+};
+
+/// Number of vertices of the ring a circle is approximated with.
+const CIRCLE_VERTICES: usize = 128;
+
+fn world() -> Polygon<f32> {
+    Rect::new(Coord { x: -180., y: -90. }, Coord { x: 180., y: 90. }).to_polygon()
+}
+
+/// Splits `polygon`, given in degrees with `x` as longitude, at the
+/// antimeridian and closes rings around a pole over that pole.
+///
+/// Consecutive vertices are taken to be joined the short way around, so an
+/// edge is never more than 180° of longitude long. Which pole a ring around
+/// a pole encloses can not be told from the ring alone; the one on the side
+/// of the equator most of the ring is on is taken.
+///
+/// Returns `None` if a coordinate is not a finite number. Latitudes past a
+/// pole are clamped to it.
+pub fn normalize(polygon: &Polygon<f32>) -> Option<MultiPolygon<f32>> {
+    let ring = |ring: &LineString<f32>| {
+        ring.coords()
+            .map(|&coord| sanitize(coord))
+            .collect::<Option<LineString<f32>>>()
+    };
+
+    let polygon = Polygon::new(
+        ring(polygon.exterior())?,
+        polygon
+            .interiors()
+            .iter()
+            .map(ring)
+            .collect::<Option<_>>()?,
+    );
+
+    Some(normalize_with_pole(&polygon, None))
+}
+
+/// `coord` with its latitude clamped to the poles and a longitude past the
+/// antimeridian wrapped around, or `None` if either is not a finite number.
+fn sanitize(Coord { x, y }: Coord<f32>) -> Option<Coord<f32>> {
+    if !x.is_finite() || !y.is_finite() {
+        return None;
+    }
+
+    Some(Coord {
+        x: if (-180.0..=180.).contains(&x) {
+            x
+        } else {
+            (x + 180.).rem_euclid(360.) - 180.
+        },
+        y: y.clamp(-90., 90.),
+    })
+}
+
+/// Like [`normalize`], closing rings around a pole over `pole`, the latitude
+/// of the pole, when given.
+fn normalize_with_pole(polygon: &Polygon<f32>, pole: Option<f32>) -> MultiPolygon<f32> {
+    let (mut exterior, winding) = unwrap(polygon.exterior());
+
+    if exterior.len() < 3 {
+        return MultiPolygon::new(Vec::new());
+    }
+
+    if winding != 0 {
+        let pole = pole.unwrap_or_else(|| {
+            let mean = exterior.iter().map(|coord| coord.y).sum::<f32>() / exterior.len() as f32;
+
+            if mean < 0. {
+                -90.
+            } else {
+                90.
+            }
+        });
+
+        let (first, last) = (exterior[0], exterior[exterior.len() - 1]);
+
+        exterior.push(Coord { x: last.x, y: pole });
+        exterior.push(Coord {
+            x: first.x,
+            y: pole,
+        });
+    }
+
+    let (min, max) = longitude_range(&exterior);
+    let middle = (min + max) / 2.;
+
+    // Holes are moved next to the exterior they were unwrapped apart from
+    let interiors = polygon
+        .interiors()
+        .iter()
+        .map(|ring| unwrap(ring).0)
+        .filter(|ring| ring.len() >= 3)
+        .map(|ring| {
+            let (min, max) = longitude_range(&ring);
+            let shift = ((middle - (min + max) / 2.) / 360.).round() * 360.;
+
+            LineString::from(ring).translate(shift, 0.)
+        })
+        .collect();
+
+    let unwrapped = Polygon::new(LineString::from(exterior), interiors);
+
+    // One piece for every copy of the world the unwrapped ring reaches into
+    let first = ((min + 180.) / 360.).floor() as i32;
+    let last = ((max - 180.) / 360.).ceil() as i32;
+
+    (first..=last)
+        .map(|copy| {
+            let shift = copy as f32 * 360.;
+
+            world()
+                .translate(shift, 0.)
+                .intersection(&unwrapped)
+                .translate(-shift, 0.)
+        })
+        .fold(MultiPolygon::new(Vec::new()), |pieces, piece| {
+            pieces.union(&piece)
+        })
+}
+
+/// The region of a [`CircleQuery`], see [`circle`].
+pub fn circle_query(query: &CircleQuery) -> Option<MultiPolygon<f32>> {
+    circle(
+        Coord {
+            x: query.center.lon,
+            y: query.center.lat,
+        },
+        query.radius_km,
+    )
+}
+
+/// The region within `radius_km` of `center`, in degrees with `x` as
+/// longitude, measured along great circles. Returns `None` if the center or
+/// radius is not a finite number.
+pub fn circle(center: Coord<f32>, radius_km: f32) -> Option<MultiPolygon<f32>> {
+    let center = sanitize(center)?;
+
+    if !radius_km.is_finite() {
+        return None;
+    }
+
+    let radius = radius_km / EARTH_RADIUS_KM;
+
+    if radius <= 0. {
+        return Some(MultiPolygon::new(Vec::new()));
+    }
+
+    if radius >= std::f32::consts::PI {
+        return Some(MultiPolygon::new(vec![world()]));
+    }
+
+    let contains_north = (90. - center.y).to_radians() < radius;
+    let contains_south = (center.y + 90.).to_radians() < radius;
+
+    // Larger than the rest of the sphere, so the ring would wind around
+    // neither pole; cut the smaller circle around the antipode out instead
+    if contains_north && contains_south {
+        let antipode = Coord {
+            x: if center.x > 0. {
+                center.x - 180.
+            } else {
+                center.x + 180.
+            },
+            y: -center.y,
+        };
+
+        let rest = circle(antipode, (std::f32::consts::PI - radius) * EARTH_RADIUS_KM)?;
+
+        return Some(MultiPolygon::new(vec![world()]).difference(&rest));
+    }
+
+    let ring = (0..CIRCLE_VERTICES)
+        .map(|i| {
+            let bearing = i as f32 / CIRCLE_VERTICES as f32 * 360.;
+
+            Haversine
+                .destination(Point::from(center), bearing, radius_km * 1000.)
+                .0
+        })
+        .collect::<Vec<_>>();
+
+    let pole = match (contains_north, contains_south) {
+        (true, _) => Some(90.),
+        (_, true) => Some(-90.),
+        _ => None,
+    };
+
+    Some(normalize_with_pole(
+        &Polygon::new(LineString::from(ring), Vec::new()),
+        pole,
+    ))
+}
+
+/// The vertices of `ring` with longitudes shifted by whole turns so that no
+/// edge is longer than 180°, and the number of times it winds around a pole.
+/// Rings winding around a pole keep their last vertex, a whole turn away
+/// from the first; others are left open.
+fn unwrap(ring: &LineString<f32>) -> (Vec<Coord<f32>>, i32) {
+    let mut coords = ring.coords().copied();
+
+    let Some(first) = coords.next() else {
+        return (Vec::new(), 0);
+    };
+
+    let mut unwrapped = vec![first];
+
+    for coord in coords {
+        let previous = unwrapped[unwrapped.len() - 1];
+        let turns = ((previous.x - coord.x) / 360.).round();
+
+        unwrapped.push(Coord {
+            x: coord.x + turns * 360.,
+            y: coord.y,
+        });
+    }
+
+    // Rings are closed, so the last vertex is the first one again, a whole
+    // number of turns away
+    let last = unwrapped[unwrapped.len() - 1];
+    let winding = ((last.x - first.x) / 360.).round() as i32;
+
+    if winding == 0 {
+        unwrapped.pop();
+    }
+
+    (unwrapped, winding)
+}
+
+fn longitude_range(coords: &[Coord<f32>]) -> (f32, f32) {
+    coords
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), coord| {
+            (min.min(coord.x), max.max(coord.x))
+        })
+}
@@ -1,12 +1,19 @@
 use std::{
-    collections::{HashMap, VecDeque},
+    borrow::Cow,
+    collections::{hash_map::DefaultHasher, HashMap, VecDeque},
+    hash::Hasher,
     io::{Result, Write},
+    ops::Range,
 };
 
-use bytemuck::Pod;
-use common::Bounds;
+use bytemuck::{Pod, Zeroable};
+use common::{codec::Codec, Bounds, CompressedTileResponse};
 use geo::{Coord, CoordNum};
 
+pub use common::format::{IndexEntry, IndexFooter, IndexLevel, Le64, TileEncoding};
+
+use common::deserialize::tree::StoredTile;
+
 use crate::TileNode;
 
 pub trait Serialize {
@@ -87,76 +94,454 @@ where
     }
 }
 
-impl<T, U> Serialize for TileNode<T, U>
+/// The rows of a tile, borrowed where they need not be decoded.
+pub(crate) type Rows<'a, T> = Vec<Cow<'a, [T]>>;
+
+/// A node that can be written by [`serialize_tree`]. Each node is written
+/// as its bounds, pointers to its children, its aggregate and its tile.
+pub(crate) trait TreeNode: Sized {
+    type Type: Pod;
+    type AggregateType: Pod;
+
+    fn bounds(&self) -> &Bounds;
+    fn children(&self) -> &[Vec<Self>];
+
+    fn aggregate(&self) -> Option<&Self::AggregateType>;
+    fn tile(&self) -> Result<Option<Rows<'_, Self::Type>>>;
+
+    /// The tile as it is stored in an existing database, if the node was
+    /// read from one, so that it can be copied without encoding it again.
+    fn stored(&self) -> Option<StoredTile<'_, Self::Type>> {
+        None
+    }
+}
+
+/// Where each tile is stored, by its hash.
+type Written<T> = HashMap<u64, Vec<(Range<usize>, T)>>;
+
+/// Keeps track of the tiles written so far, so that identical tiles are
+/// only stored once.
+struct Deduplicator<'n, N> {
+    codec: Codec,
+    written: Written<&'n N>,
+    /// Compressed tiles copied from an existing database, by their wire.
+    copied: Written<&'n [u8]>,
+    /// The wire of every tile compressed in the first pass, by its node,
+    /// until the second pass writes it.
+    encoded: HashMap<usize, Option<Vec<u8>>>,
+}
+
+impl<'n, N> Deduplicator<'n, N>
 where
-    T: Pod,
-    U: Pod,
+    N: TreeNode,
 {
-    fn serialize<W>(&self, writer: &mut AlignedWriter<W>) -> Result<()>
+    fn new(codec: Codec) -> Self {
+        Self {
+            codec,
+            written: HashMap::new(),
+            copied: HashMap::new(),
+            encoded: HashMap::new(),
+        }
+    }
+
+    /// Forgets the tiles written, to write them again, but keeps the wire
+    /// of the compressed ones.
+    fn restart(self) -> Self {
+        Self {
+            encoded: self.encoded,
+            ..Self::new(self.codec)
+        }
+    }
+
+    /// Writes the tile of `node`, returning where its tag and data are
+    /// stored. For duplicates, that is where the original is stored.
+    fn write<W>(&mut self, node: &'n N, writer: &mut AlignedWriter<W>) -> Result<Range<usize>>
     where
         W: Write,
     {
-        let mut pointers = HashMap::<usize, usize>::new();
+        let start = writer.position;
 
-        // First pass, calculate pointers
-        {
-            let mut queue = VecDeque::new();
-            queue.push_back(self);
+        let (tile, encode) = match node.stored() {
+            Some(StoredTile::Compressed(wire)) => return self.copy(node, wire, writer),
+            // Stored as rows, as compressing them did not make them smaller
+            Some(StoredTile::Rows(rows)) => {
+                (Some(rows.into_iter().map(Cow::Borrowed).collect()), false)
+            }
+            None => (node.tile()?, true),
+        };
 
-            let mut sink = writer.with(std::io::sink());
+        let Some(tile) = tile else {
+            writer.write(&(TileEncoding::Empty as u8))?;
 
-            while let Some(node) = queue.pop_front() {
-                sink.align_to(8)?;
+            return Ok(start..writer.position);
+        };
 
-                pointers.insert(node as *const _ as usize, sink.position);
+        let mut hasher = DefaultHasher::new();
+        for row in &tile {
+            hasher.write_usize(row.len());
+            hasher.write(bytemuck::cast_slice(row));
+        }
+        let hash = hasher.finish();
+
+        let duplicate = self
+            .written
+            .get(&hash)
+            .into_iter()
+            .flatten()
+            .find(|(_, other)| {
+                // Read successfully when it was written
+                let Ok(Some(other)) = other.tile() else {
+                    return false;
+                };
+
+                tile.len() == other.len()
+                    && tile.iter().zip(&other).all(|(a, b)| {
+                        bytemuck::cast_slice::<_, u8>(a) == bytemuck::cast_slice::<_, u8>(b)
+                    })
+            });
+
+        if let Some((original, _)) = duplicate {
+            let original = original.clone();
+
+            writer.write(&(TileEncoding::Reference as u8))?;
+            writer.write(&Le64::new(original.start))?;
+
+            return Ok(original);
+        }
 
-                node.bounds.serialize(&mut sink)?;
-                (&node
-                    .children
-                    .iter()
-                    .map(|row| row.iter().map(|_| 0_usize).collect::<Vec<_>>())
-                    .collect::<Vec<_>>())
-                    .serialize(&mut sink)?;
-                node.aggregate.as_ref().as_ref().serialize(&mut sink)?;
-                node.data.as_ref().serialize(&mut sink)?;
+        let first = tile.first().and_then(|row| row.first());
+        let constant = first.is_some_and(|first| {
+            let first = bytemuck::bytes_of(first);
 
-                for child in node.children.iter().flatten() {
-                    queue.push_back(child);
-                }
+            tile.iter().all(|row| {
+                row.len() == tile[0].len()
+                    && row.iter().all(|value| bytemuck::bytes_of(value) == first)
+            })
+        });
+
+        let width = tile.first().map_or(0, |row| row.len());
+        let rectangular = tile.iter().all(|row| row.len() == width);
+
+        // Constant tiles are smaller still than compressed ones
+        let wire = if encode && rectangular && !constant {
+            self.encode(node, &tile)?
+        } else {
+            None
+        };
+
+        if constant {
+            writer.write(&(TileEncoding::Constant as u8))?;
+            writer.write(&Le64::new(tile.len()))?;
+            (&tile[0][..]).serialize(writer)?;
+        } else if let Some(wire) = wire {
+            writer.write(&(TileEncoding::Compressed as u8))?;
+            (&wire[..]).serialize(writer)?;
+        } else {
+            writer.write(&(TileEncoding::Inline as u8))?;
+            writer.write(&Le64::new(tile.len()))?;
+
+            for row in &tile {
+                (&row[..]).serialize(writer)?;
             }
         }
 
-        // Second pass, use pointers from first pass
+        self.written
+            .entry(hash)
+            .or_default()
+            .push((start..writer.position, node));
+
+        Ok(start..writer.position)
+    }
+
+    /// Writes a tile compressed in an existing database by copying its wire.
+    /// Only a wire carrying the bounds of the tile it was deduplicated with
+    /// is serialized again, with the bounds of `node`.
+    fn copy<W>(
+        &mut self,
+        node: &N,
+        wire: &'n [u8],
+        writer: &mut AlignedWriter<W>,
+    ) -> Result<Range<usize>>
+    where
+        W: Write,
+    {
+        let start = writer.position;
+
+        let mut hasher = DefaultHasher::new();
+        hasher.write(wire);
+        let hash = hasher.finish();
+
+        let duplicate = self
+            .copied
+            .get(&hash)
+            .into_iter()
+            .flatten()
+            .find(|(_, other)| *other == wire);
+
+        if let Some((original, _)) = duplicate {
+            let original = original.clone();
+
+            writer.write(&(TileEncoding::Reference as u8))?;
+            writer.write(&Le64::new(original.start))?;
+
+            return Ok(original);
+        }
+
+        let response: CompressedTileResponse =
+            bincode::deserialize(wire).map_err(std::io::Error::other)?;
+
+        writer.write(&(TileEncoding::Compressed as u8))?;
+
+        if response.bounds == *node.bounds() {
+            wire.serialize(writer)?;
+        } else {
+            let response = CompressedTileResponse {
+                bounds: *node.bounds(),
+                ..response
+            };
+
+            let wire = bincode::serialize(&response).map_err(std::io::Error::other)?;
+            (&wire[..]).serialize(writer)?;
+        }
 
+        self.copied
+            .entry(hash)
+            .or_default()
+            .push((start..writer.position, wire));
+
+        Ok(start..writer.position)
+    }
+
+    /// The wire of `tile`, the tile of `node`, compressed with the codec,
+    /// unless that would not make it smaller. Every tile is only compressed
+    /// in the first pass, the second takes its wire from there.
+    fn encode(&mut self, node: &N, tile: &[Cow<'_, [N::Type]>]) -> Result<Option<Vec<u8>>> {
+        let key = node as *const _ as usize;
+
+        if let Some(wire) = self.encoded.remove(&key) {
+            return Ok(wire);
+        }
+
+        let wire = self
+            .compress(tile)
+            .map(|compressed| {
+                let response = CompressedTileResponse {
+                    bounds: *node.bounds(),
+                    codec: self.codec,
+                    width: tile[0].len() as u32,
+                    height: tile.len() as u32,
+                    data: Cow::Borrowed(&compressed),
+                };
+
+                bincode::serialize(&response).map_err(std::io::Error::other)
+            })
+            .transpose()?;
+
+        self.encoded.insert(key, wire.clone());
+
+        Ok(wire)
+    }
+
+    /// Encodes `tile` with the codec, unless that would not make it smaller.
+    fn compress(&self, tile: &[Cow<'_, [N::Type]>]) -> Option<Vec<u8>> {
+        if self.codec == Codec::None {
+            return None;
+        }
+
+        let values = tile.concat();
+        let compressed = self.codec.encode(&values);
+
+        (compressed.len() < std::mem::size_of_val(&values[..])).then_some(compressed)
+    }
+}
+
+pub(crate) fn serialize_tree<N, W>(
+    root: &N,
+    codec: Codec,
+    writer: &mut AlignedWriter<W>,
+) -> Result<()>
+where
+    N: TreeNode,
+    W: Write,
+{
+    let mut pointers = HashMap::<usize, usize>::new();
+    let mut deduplicator = Deduplicator::new(codec);
+
+    // First pass, calculate pointers
+    {
         let mut queue = VecDeque::new();
-        queue.push_back(self);
+        queue.push_back(root);
+
+        let mut sink = writer.with(std::io::sink());
 
         while let Some(node) = queue.pop_front() {
-            writer.align_to(8)?;
+            sink.align_to(8)?;
 
-            node.bounds.serialize(writer)?;
+            pointers.insert(node as *const _ as usize, sink.position);
 
-            let children = node
-                .children
+            node.bounds().serialize(&mut sink)?;
+            (&node
+                .children()
                 .iter()
-                .map(|row| {
-                    row.iter()
-                        .map(|node| pointers[&(node as *const _ as usize)])
-                        .collect::<Vec<_>>()
-                })
-                .collect::<Vec<_>>();
+                .map(|row| row.iter().map(|_| Le64::default()).collect::<Vec<_>>())
+                .collect::<Vec<_>>())
+                .serialize(&mut sink)?;
+            node.aggregate().as_ref().serialize(&mut sink)?;
+            deduplicator.write(node, &mut sink)?;
 
-            (&children).serialize(writer)?;
+            for child in node.children().iter().flatten() {
+                queue.push_back(child);
+            }
+        }
+    }
 
-            node.aggregate.as_ref().as_ref().serialize(writer)?;
-            node.data.as_ref().serialize(writer)?;
+    // Second pass, use pointers from first pass.
+    // Tiles are deduplicated the same way as in the first pass,
+    // so every node ends up at the position calculated there.
 
-            for child in node.children.iter().flatten() {
-                queue.push_back(child);
+    let mut queue = VecDeque::new();
+    queue.push_back((root, 0, 0, 0));
+
+    let mut deduplicator = deduplicator.restart();
+    let mut levels = Vec::<Vec<(usize, usize, IndexEntry)>>::new();
+
+    while let Some((node, x, y, z)) = queue.pop_front() {
+        writer.align_to(8)?;
+
+        node.bounds().serialize(writer)?;
+
+        let children = node
+            .children()
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|node| Le64::new(pointers[&(node as *const _ as usize)]))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        (&children).serialize(writer)?;
+
+        let bounds = node.bounds();
+
+        if levels.len() == z {
+            levels.push(Vec::new());
+        }
+
+        let data = writer.position;
+
+        node.aggregate().as_ref().serialize(writer)?;
+        let tile = deduplicator.write(node, writer)?;
+
+        levels[z].push((
+            x,
+            y,
+            IndexEntry {
+                extent: [
+                    bounds.min().x,
+                    bounds.min().y,
+                    bounds.max().x,
+                    bounds.max().y,
+                ],
+                data: Le64::new(data),
+                tile: Le64::new(tile.start),
+                tile_size: Le64::new(tile.len()),
+            },
+        ));
+
+        for (i, row) in node.children().iter().enumerate() {
+            for (j, child) in row.iter().enumerate() {
+                queue.push_back((
+                    child,
+                    x * row.len() + j,
+                    y * node.children().len() + i,
+                    z + 1,
+                ));
             }
         }
+    }
 
-        Ok(())
+    write_index(&levels, writer)
+}
+
+/// Writes every level as a dense grid of [`IndexEntry`]s, followed by the
+/// table of levels and the footer.
+fn write_index<W>(
+    levels: &[Vec<(usize, usize, IndexEntry)>],
+    writer: &mut AlignedWriter<W>,
+) -> Result<()>
+where
+    W: Write,
+{
+    let mut table = Vec::with_capacity(levels.len());
+
+    for level in levels {
+        let width = level.iter().map(|(x, ..)| x + 1).max().unwrap_or(0);
+        let height = level.iter().map(|(_, y, _)| y + 1).max().unwrap_or(0);
+
+        let mut entries = vec![IndexEntry::zeroed(); width * height];
+        for &(x, y, entry) in level {
+            entries[y * width + x] = entry;
+        }
+
+        writer.align_to(8)?;
+        table.push(IndexLevel {
+            offset: Le64::new(writer.position),
+            width: Le64::new(width),
+            height: Le64::new(height),
+        });
+        writer.write_slice(&entries)?;
+    }
+
+    writer.align_to(8)?;
+    let offset = writer.position;
+    writer.write_slice(&table)?;
+
+    writer.write(&IndexFooter {
+        offset: Le64::new(offset),
+        levels: Le64::new(table.len()),
+        magic: IndexFooter::MAGIC,
+    })
+}
+
+impl<T, U> TreeNode for TileNode<T, U>
+where
+    T: Pod,
+    U: Pod,
+{
+    type Type = T;
+    type AggregateType = U;
+
+    fn bounds(&self) -> &Bounds {
+        &self.bounds
+    }
+
+    fn children(&self) -> &[Vec<Self>] {
+        &self.children
+    }
+
+    fn aggregate(&self) -> Option<&U> {
+        self.aggregate.as_ref()
+    }
+
+    fn tile(&self) -> Result<Option<Rows<'_, T>>> {
+        Ok(self
+            .data
+            .as_ref()
+            .map(|tile| tile.iter().map(|row| Cow::Borrowed(&row[..])).collect()))
+    }
+}
+
+impl<T, U> Serialize for TileNode<T, U>
+where
+    T: Pod,
+    U: Pod,
+{
+    fn serialize<W>(&self, writer: &mut AlignedWriter<W>) -> Result<()>
+    where
+        W: Write,
+    {
+        serialize_tree(self, Codec::None, writer)
     }
 }
 
@@ -223,7 +608,7 @@ where
     where
         W: Write,
     {
-        writer.write(&self.len())?;
+        writer.write(&Le64::new(self.len()))?;
 
         for v in *self {
             Serialize::serialize(v, writer)?;
@@ -241,7 +626,7 @@ where
     where
         W: Write,
     {
-        writer.write(&self.len())?;
+        writer.write(&Le64::new(self.len()))?;
         writer.write_slice(self)?;
 
         Ok(())
@@ -0,0 +1,93 @@
+use std::{collections::BTreeMap, ops::Bound};
+
+use bytemuck::Pod;
+use common::{Bounds, TileRefResponse};
+use geo::{Contains, Intersects};
+use serde::Serialize;
+
+use crate::{
+    deserialize::{self, GeoTree},
+    Dataset,
+};
+
+/// A layer holding one tree per timestep. Timesteps are ordered by their
+/// label, so labels should sort chronologically, e.g. `2024-01`.
+pub struct TemporalGeoTree<D>
+where
+    D: Dataset,
+{
+    timesteps: BTreeMap<String, GeoTree<D>>,
+}
+
+#[derive(Serialize)]
+pub struct TimestepAggregate<'a, U> {
+    pub time: &'a str,
+    pub aggregate: Option<U>,
+}
+
+impl<D> TemporalGeoTree<D>
+where
+    D: Dataset,
+{
+    pub fn new(timesteps: BTreeMap<String, GeoTree<D>>) -> Self {
+        Self { timesteps }
+    }
+
+    pub fn timesteps(&self) -> Vec<&str> {
+        self.timesteps.keys().map(String::as_str).collect()
+    }
+
+    pub fn timestep(&self, time: &str) -> Option<&GeoTree<D>> {
+        self.timesteps.get(time)
+    }
+
+    pub fn get_tile(
+        &self,
+        time: &str,
+        x: usize,
+        y: usize,
+        z: usize,
+    ) -> Result<Option<TileRefResponse<'_, D::Type>>, deserialize::Error>
+    where
+        D::Type: Pod,
+        D::AggregateType: Pod,
+    {
+        match self.timesteps.get(time) {
+            Some(tree) => tree.get_tile(x, y, z),
+            None => Ok(None),
+        }
+    }
+
+    /// Aggregates `query` for every timestep between `from` and `to`,
+    /// both inclusive. A missing bound leaves that end of the range open.
+    pub fn get_aggregate_series<Query>(
+        &self,
+        from: Option<&str>,
+        to: Option<&str>,
+        query: Query,
+    ) -> Result<Vec<TimestepAggregate<'_, D::AggregateType>>, deserialize::Error>
+    where
+        D::Type: Pod,
+        D::AggregateType: Pod,
+        Query: Contains<Bounds> + Intersects<Bounds> + Clone,
+    {
+        if let (Some(from), Some(to)) = (from, to) {
+            if from > to {
+                return Ok(Vec::new());
+            }
+        }
+
+        let from = from.map_or(Bound::Unbounded, Bound::Included);
+        let to = to.map_or(Bound::Unbounded, Bound::Included);
+
+        self.timesteps
+            .range::<str, _>((from, to))
+            .map(|(time, tree)| {
+                Ok(TimestepAggregate {
+                    time,
+                    aggregate: tree.get_aggregate(query.clone())?,
+                })
+            })
+            .collect()
+    }
+}
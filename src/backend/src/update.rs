@@ -0,0 +1,268 @@
+use std::{
+    borrow::Cow,
+    io::{Result, Write},
+};
+
+use bytemuck::Pod;
+use geo::Intersects;
+
+use crate::{
+    deserialize::{
+        self,
+        reader::Reader,
+        tree::{Pointer, StoredTile, TileData},
+    },
+    flatten,
+    serialize::{serialize_tree, AlignedWriter, Rows, TreeNode},
+    Bounds, Dataset, Tile, Window,
+};
+
+enum Payload<'a, T, U> {
+    /// Payload of a node outside the updated region, borrowed from the old
+    /// file as it is stored.
+    Reused(TileData<'a, T, U>),
+    Updated {
+        aggregate: Option<U>,
+        data: Option<Tile<T>>,
+    },
+}
+
+struct UpdateNode<'a, T, U> {
+    bounds: Bounds,
+    payload: Payload<'a, T, U>,
+    children: Vec<Vec<UpdateNode<'a, T, U>>>,
+}
+
+/// Writes a copy of `old` to `writer`, where only the leaves intersecting
+/// `region` are read again from `dataset`, and only their ancestors are
+/// downsampled again. All other payloads are copied from `old` as they are
+/// stored, without decoding them.
+///
+/// The dataset must have the same bounds and resolution as the one `old`
+/// was built from, so that the tree keeps its shape.
+pub fn update<D, W>(
+    old: &deserialize::GeoTree<D>,
+    dataset: &D,
+    region: Bounds,
+    writer: W,
+) -> Result<()>
+where
+    D: Dataset,
+    D::Type: Pod,
+    D::AggregateType: Pod,
+    W: Write,
+{
+    let root = {
+        let (width, height) = dataset.size();
+
+        rebuild(
+            &mut old.reader(),
+            &Pointer::default(),
+            (0, 0, width, height),
+            dataset,
+            region,
+        )?
+    };
+
+    let mut writer = AlignedWriter::new(writer);
+    serialize_tree(&root, D::CODEC, &mut writer)
+}
+
+/// Writes a copy of the top `levels` levels of `old` to `writer`, such as
+/// a small database bundled with the frontend for offline use. The root is
+/// always kept.
+pub fn truncate<D, W>(old: &deserialize::GeoTree<D>, levels: usize, writer: W) -> Result<()>
+where
+    D: Dataset,
+    D::Type: Pod,
+    D::AggregateType: Pod,
+    W: Write,
+{
+    let root = reuse::<D::Type, D::AggregateType>(&mut old.reader(), &Pointer::default(), levels)?;
+
+    let mut writer = AlignedWriter::new(writer);
+    serialize_tree(&root, D::CODEC, &mut writer)
+}
+
+fn reuse<'a, T, U>(
+    reader: &mut Reader<'a>,
+    pointer: &Pointer<T>,
+    levels: usize,
+) -> Result<UpdateNode<'a, T, U>>
+where
+    T: Pod,
+    U: Pod,
+{
+    let node = reader.load(pointer)?;
+    let data = reader.read()?;
+
+    let children = if levels > 1 {
+        node.children
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|child| reuse(reader, child, levels - 1))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<_>>()?
+    } else {
+        Vec::new()
+    };
+
+    Ok(UpdateNode {
+        bounds: node.bounds,
+        payload: Payload::Reused(data),
+        children,
+    })
+}
+
+fn rebuild<'a, D>(
+    reader: &mut Reader<'a>,
+    pointer: &Pointer<D::Type>,
+    (x, y, width, height): Window,
+    dataset: &D,
+    region: Bounds,
+) -> Result<UpdateNode<'a, D::Type, D::AggregateType>>
+where
+    D: Dataset,
+    D::Type: Pod,
+    D::AggregateType: Pod,
+{
+    let node = reader.load(pointer)?;
+    let data = reader.read()?;
+
+    // Same split as `GeoTree::recursive_slice`
+    let child_width = width / D::CHILDREN_PER_AXIS;
+    let child_height = height / D::CHILDREN_PER_AXIS;
+
+    let children = node
+        .children
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            row.iter()
+                .enumerate()
+                .map(|(j, child)| {
+                    let x_start = j * child_width;
+                    let y_start = i * child_height;
+
+                    let actual_width = if j == D::CHILDREN_PER_AXIS - 1 {
+                        width - x_start
+                    } else {
+                        child_width
+                    };
+
+                    let actual_height = if i == D::CHILDREN_PER_AXIS - 1 {
+                        height - y_start
+                    } else {
+                        child_height
+                    };
+
+                    let window = (x + x_start, y + y_start, actual_width, actual_height);
+
+                    rebuild(reader, child, window, dataset, region)
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if !node.bounds.intersects(&region) {
+        return Ok(UpdateNode {
+            bounds: node.bounds,
+            payload: Payload::Reused(data),
+            children,
+        });
+    }
+
+    let payload = if children.is_empty() {
+        let data = dataset.window(x, y, width, height);
+
+        Payload::Updated {
+            aggregate: D::aggregate(&data.iter().flatten().copied().collect::<Vec<_>>()),
+            data: Some(data),
+        }
+    } else {
+        let tiles = children
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .filter_map(|child| child.tile().transpose())
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let tiles = tiles
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|tile| tile.into_iter().map(Cow::into_owned).collect())
+                    .collect::<Vec<Tile<_>>>()
+            })
+            .collect::<Vec<_>>();
+
+        let data = flatten(
+            tiles
+                .iter()
+                .map(|row| row.iter().collect())
+                .collect::<Vec<_>>(),
+        );
+
+        let aggregates = children
+            .iter()
+            .flatten()
+            .flat_map(|child| child.aggregate())
+            .copied()
+            .collect::<Vec<_>>();
+
+        Payload::Updated {
+            aggregate: D::aggregate2(&aggregates),
+            data: Some(D::downsample(&data)),
+        }
+    };
+
+    Ok(UpdateNode {
+        bounds: node.bounds,
+        payload,
+        children,
+    })
+}
+
+impl<T, U> TreeNode for UpdateNode<'_, T, U>
+where
+    T: Pod,
+    U: Pod,
+{
+    type Type = T;
+    type AggregateType = U;
+
+    fn bounds(&self) -> &Bounds {
+        &self.bounds
+    }
+
+    fn children(&self) -> &[Vec<Self>] {
+        &self.children
+    }
+
+    fn aggregate(&self) -> Option<&U> {
+        match &self.payload {
+            Payload::Reused(data) => data.aggregate,
+            Payload::Updated { aggregate, .. } => aggregate.as_ref(),
+        }
+    }
+
+    fn stored(&self) -> Option<StoredTile<'_, T>> {
+        match &self.payload {
+            Payload::Reused(data) => data.tile.clone(),
+            Payload::Updated { .. } => None,
+        }
+    }
+
+    fn tile(&self) -> Result<Option<Rows<'_, T>>> {
+        match &self.payload {
+            Payload::Reused(data) => Ok(data.tile.clone().map(StoredTile::decode).transpose()?),
+            Payload::Updated { data, .. } => Ok(data
+                .as_ref()
+                .map(|tile| tile.iter().map(|row| Cow::Borrowed(&row[..])).collect())),
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Error, Result},
+    path::Path,
+};
+
+use common::VectorTile;
+use gdal::vector::LayerAccess;
+use geo::{Coord, Geometry, LineString, Simplify};
+use rstar::{primitives::GeomWithData, RTree, AABB};
+
+use crate::Bounds;
+
+/// Lines are split into pieces of at most this many points before they are
+/// indexed, so that a long coastline is not returned for every tile it
+/// passes close to.
+const PIECE_LENGTH: usize = 64;
+
+/// The deepest level vector tiles are served for.
+pub const MAX_LEVEL: usize = 20;
+
+type Envelope = GeomWithData<rstar::primitives::Rectangle<[f32; 2]>, usize>;
+
+/// Lines and polygon outlines in degrees, indexed by their bounding boxes.
+pub struct VectorStore {
+    lines: Vec<LineString<f32>>,
+    index: RTree<Envelope>,
+}
+
+impl VectorStore {
+    pub fn new(lines: impl IntoIterator<Item = LineString<f32>>) -> Self {
+        let lines = lines
+            .into_iter()
+            .flat_map(|line| split(line.0))
+            .collect::<Vec<_>>();
+
+        let index = RTree::bulk_load(
+            lines
+                .iter()
+                .enumerate()
+                .filter_map(|(i, line)| {
+                    let bounds = geo::BoundingRect::bounding_rect(line)?;
+
+                    Some(Envelope::new(
+                        rstar::primitives::Rectangle::from_corners(
+                            bounds.min().into(),
+                            bounds.max().into(),
+                        ),
+                        i,
+                    ))
+                })
+                .collect(),
+        );
+
+        Self { lines, index }
+    }
+
+    /// Reads the lines and polygons of every layer of the vector files at
+    /// `paths`, such as GeoJSON or Shapefiles. Polygons become their
+    /// outlines and points are skipped.
+    pub fn ingest<P>(paths: impl IntoIterator<Item = P>) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let mut lines = Vec::new();
+
+        for path in paths {
+            let dataset = gdal::Dataset::open(path.as_ref()).map_err(Error::other)?;
+
+            for mut layer in dataset.layers() {
+                for feature in layer.features() {
+                    let Some(geometry) = feature.geometry() else {
+                        continue;
+                    };
+
+                    outlines(geometry.to_geo().map_err(Error::other)?, &mut lines);
+                }
+            }
+        }
+
+        Ok(Self::new(lines))
+    }
+
+    pub fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let lines: Vec<LineString<f32>> =
+            bincode::deserialize_from(BufReader::new(File::open(path)?)).map_err(Error::other)?;
+
+        Ok(Self::new(lines))
+    }
+
+    pub fn write_to_file<P>(&self, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        bincode::serialize_into(BufWriter::new(File::create(path)?), &self.lines)
+            .map_err(Error::other)
+    }
+
+    /// The lines within `bounds`, clipped to them, simplified so that no
+    /// point moves by more than `tolerance` degrees.
+    pub fn lines(&self, bounds: Bounds, tolerance: f32) -> VectorTile {
+        let envelope = AABB::from_corners(bounds.min().into(), bounds.max().into());
+
+        self.index
+            .locate_in_envelope_intersecting(&envelope)
+            .flat_map(|envelope| clip(&self.lines[envelope.data].simplify(&tolerance), bounds))
+            .collect()
+    }
+
+    /// The lines of tile `x`, `y` on level `z` of a quadtree covering the
+    /// globe, laid out like the raster layers, simplified to about a
+    /// pixel of a 256 pixel wide tile. Returns `None` for levels deeper
+    /// than [`MAX_LEVEL`].
+    pub fn tile(&self, x: usize, y: usize, z: usize) -> Option<VectorTile> {
+        let bounds = tile_bounds(x, y, z)?;
+
+        Some(self.lines(bounds, bounds.width() / 256.))
+    }
+}
+
+/// The bounds of tile `x`, `y` on level `z` of a quadtree covering the
+/// globe, with `y` counting from the north, or `None` for levels deeper
+/// than [`MAX_LEVEL`].
+pub fn tile_bounds(x: usize, y: usize, z: usize) -> Option<Bounds> {
+    if z > MAX_LEVEL {
+        return None;
+    }
+
+    let size = (1 << z) as f32;
+    let (width, height) = (360. / size, 180. / size);
+
+    let west = -180. + x as f32 * width;
+    let north = 90. - y as f32 * height;
+
+    Some(Bounds::new(
+        Coord { x: west, y: north },
+        Coord {
+            x: west + width,
+            y: north - height,
+        },
+    ))
+}
+
+fn outlines(geometry: Geometry<f64>, lines: &mut Vec<LineString<f32>>) {
+    let to_f32 = |line: LineString<f64>| {
+        line.into_iter()
+            .map(|Coord { x, y }| Coord {
+                x: x as f32,
+                y: y as f32,
+            })
+            .collect()
+    };
+
+    match geometry {
+        Geometry::Line(line) => lines.push(to_f32(line.into())),
+        Geometry::LineString(line) => lines.push(to_f32(line)),
+        Geometry::MultiLineString(multi) => lines.extend(multi.into_iter().map(to_f32)),
+        Geometry::Polygon(polygon) => {
+            let (exterior, interiors) = polygon.into_inner();
+            lines.push(to_f32(exterior));
+            lines.extend(interiors.into_iter().map(to_f32));
+        }
+        Geometry::MultiPolygon(multi) => {
+            for polygon in multi {
+                outlines(Geometry::Polygon(polygon), lines);
+            }
+        }
+        Geometry::Rect(rect) => outlines(Geometry::Polygon(rect.to_polygon()), lines),
+        Geometry::Triangle(triangle) => {
+            outlines(Geometry::Polygon(triangle.to_polygon()), lines);
+        }
+        Geometry::GeometryCollection(collection) => {
+            for geometry in collection {
+                outlines(geometry, lines);
+            }
+        }
+        Geometry::Point(_) | Geometry::MultiPoint(_) => {}
+    }
+}
+
+/// Splits `points` into lines of at most [`PIECE_LENGTH`] points, each
+/// starting where the previous one ends.
+fn split(points: Vec<Coord<f32>>) -> Vec<LineString<f32>> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+
+    while start + 1 < points.len() {
+        let end = (start + PIECE_LENGTH).min(points.len());
+        pieces.push(LineString::from(points[start..end].to_vec()));
+        start = end - 1;
+    }
+
+    pieces
+}
+
+/// The parts of `line` within `bounds`.
+fn clip(line: &LineString<f32>, bounds: Bounds) -> Vec<LineString<f32>> {
+    let mut parts = Vec::new();
+    let mut current = Vec::new();
+
+    for segment in line.lines() {
+        let Some((start, end, start_clipped, end_clipped)) =
+            clip_segment(segment.start, segment.end, bounds)
+        else {
+            continue;
+        };
+
+        if start_clipped || current.is_empty() {
+            if current.len() > 1 {
+                parts.push(LineString::from(std::mem::take(&mut current)));
+            }
+
+            current = vec![start];
+        }
+
+        current.push(end);
+
+        if end_clipped {
+            parts.push(LineString::from(std::mem::take(&mut current)));
+        }
+    }
+
+    if current.len() > 1 {
+        parts.push(LineString::from(current));
+    }
+
+    parts
+}
+
+/// Clips the segment from `start` to `end` to `bounds` with the
+/// Liang-Barsky algorithm, returning the clipped ends and whether each of
+/// them was moved.
+fn clip_segment(
+    start: Coord<f32>,
+    end: Coord<f32>,
+    bounds: Bounds,
+) -> Option<(Coord<f32>, Coord<f32>, bool, bool)> {
+    let delta = end - start;
+    let (min, max) = (bounds.min(), bounds.max());
+
+    let (mut t0, mut t1) = (0_f32, 1_f32);
+
+    for (p, q) in [
+        (-delta.x, start.x - min.x),
+        (delta.x, max.x - start.x),
+        (-delta.y, start.y - min.y),
+        (delta.y, max.y - start.y),
+    ] {
+        if p == 0. {
+            if q < 0. {
+                return None;
+            }
+
+            continue;
+        }
+
+        let t = q / p;
+
+        if p < 0. {
+            t0 = t0.max(t);
+        } else {
+            t1 = t1.min(t);
+        }
+
+        if t0 > t1 {
+            return None;
+        }
+    }
+
+    Some((start + delta * t0, start + delta * t1, t0 > 0., t1 < 1.))
+}
@@ -0,0 +1,156 @@
+mod support;
+
+use std::path::Path;
+
+use backend::{
+    archive::{mbtiles, pmtiles, ArchiveDataset},
+    deserialize, Bounds, Dataset, GeoTree,
+};
+use geo::Coord;
+use support::{temp_path, Grid};
+
+type Pixel = [u8; 4];
+
+/// An image of 4x4 tiles, of which only the bottom row repeats, so that
+/// archives hold duplicate tiles.
+type Gradient = Grid<2, Pixel>;
+
+fn gradient() -> Gradient {
+    Grid::new(64, 64, |x, y| match y {
+        48.. => [0, 0, 255, 255],
+        _ => [x as u8 * 4, y as u8 * 4, 0, 255],
+    })
+    .with_bounds(Bounds::new(
+        Coord { x: -90., y: -45. },
+        Coord { x: 90., y: 45. },
+    ))
+}
+
+fn database() -> deserialize::GeoTree<Gradient> {
+    let mut bytes = Vec::new();
+    GeoTree::build(&gradient()).write(&mut bytes).unwrap();
+
+    deserialize::GeoTree::from_source(bytes)
+}
+
+fn assert_imported(path: &Path) {
+    let imported = ArchiveDataset::open(path).unwrap();
+
+    assert_eq!(imported.bounds(), gradient().bounds());
+    assert_eq!(imported.data(), gradient().data());
+}
+
+#[test]
+fn mbtiles_round_trip() {
+    let path = temp_path("archive").with_extension("mbtiles");
+    mbtiles::export(&database(), 3, &path).unwrap();
+
+    // Rows count from the south, so the bottom row of the image is row 0
+    let connection = rusqlite::Connection::open(&path).unwrap();
+    let bottom: Vec<u8> = connection
+        .query_row(
+            "SELECT tile_data FROM tiles WHERE zoom_level = 2 AND tile_column = 1 AND tile_row = 0",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+
+    let pixel = image::load_from_memory(&bottom).unwrap().to_rgba8()[(0, 0)].0;
+    assert_eq!(pixel, [0, 0, 255, 255]);
+
+    assert_imported(&path);
+}
+
+#[test]
+fn pmtiles_round_trip() {
+    let path = temp_path("archive").with_extension("pmtiles");
+    pmtiles::export(&database(), 3, &path).unwrap();
+
+    assert_imported(&path);
+}
+
+#[test]
+fn exports_existing_levels_only() {
+    let path = temp_path("levels").with_extension("pmtiles");
+    pmtiles::export(&database(), 10, &path).unwrap();
+
+    let levels = database().info().unwrap().levels;
+    assert!(levels < 10);
+
+    // The deepest zoom level, stored in the header
+    let max_zoom = std::fs::read(&path).unwrap()[101];
+    assert_eq!(usize::from(max_zoom) + 1, levels);
+
+    assert_imported(&path);
+}
+
+#[test]
+fn exports_the_projection() {
+    let path = temp_path("projection").with_extension("mbtiles");
+    mbtiles::export(&database(), 3, &path).unwrap();
+
+    let connection = rusqlite::Connection::open(&path).unwrap();
+    let crs: String = connection
+        .query_row("SELECT value FROM metadata WHERE name = 'crs'", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+
+    assert_eq!(crs, "EPSG:4326");
+}
+
+/// An archive in the layout of mbutil, with tiles as a view joining
+/// deduplicated images, of RGB tiles of 256 pixels on two zoom levels.
+#[test]
+fn imports_external_mbtiles() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/mercator.mbtiles");
+    let imported = ArchiveDataset::open(path).unwrap();
+
+    assert_eq!(imported.size(), (512, 512));
+    assert_eq!(
+        imported.bounds(),
+        Bounds::new(
+            Coord {
+                x: -180.,
+                y: -85.0511,
+            },
+            Coord {
+                x: 180.,
+                y: 85.0511,
+            },
+        )
+    );
+
+    let data = imported.data();
+
+    assert_eq!(data[0][0], [255, 0, 0, 255]);
+    assert_eq!(data[255][511], [0, 255, 0, 255]);
+    assert_eq!(data[256][0], [0, 0, 255, 255]);
+    assert_eq!(data[511][511], [0, 0, 255, 255]);
+}
+
+#[test]
+fn refuses_damaged_archives() {
+    let path = temp_path("damaged").with_extension("pmtiles");
+    pmtiles::export(&database(), 3, &path).unwrap();
+
+    let bytes = std::fs::read(&path).unwrap();
+    let open_damaged = |damage: fn(&mut Vec<u8>)| {
+        let mut damaged = bytes.clone();
+        damage(&mut damaged);
+        std::fs::write(&path, damaged).unwrap();
+
+        ArchiveDataset::open(&path)
+    };
+
+    // Root directory past the end of the file
+    assert!(open_damaged(|bytes| bytes[8..16].copy_from_slice(&u64::MAX.to_le_bytes())).is_err());
+
+    // Zoom level too deep to address
+    assert!(open_damaged(|bytes| bytes[101] = 40).is_err());
+
+    // Deepest zoom level without tiles
+    assert!(open_damaged(|bytes| bytes[101] = 5).is_err());
+
+    assert!(open_damaged(|_| {}).is_ok());
+}
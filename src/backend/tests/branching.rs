@@ -0,0 +1,42 @@
+mod support;
+
+use backend::{deserialize, Dataset, GeoTree};
+use bytemuck::Pod;
+use support::{assert_tiles_match, strip_index, temp_path, Grid};
+
+fn assert_round_trip<D>(name: &str, dataset: &D)
+where
+    D: Dataset,
+    D::Type: Pod + PartialEq + std::fmt::Debug,
+    D::AggregateType: Pod,
+{
+    let path = temp_path(name);
+
+    let tree = GeoTree::build(dataset);
+    tree.write_to_file(&path).unwrap();
+
+    let legacy = strip_index(&path);
+
+    assert_tiles_match(&tree, &deserialize::GeoTree::<D>::new(&path).unwrap());
+    assert_tiles_match(&tree, &deserialize::GeoTree::<D>::new(&legacy).unwrap());
+
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(legacy).unwrap();
+}
+
+#[test]
+fn three_children_per_axis() {
+    // Leaves end up on different levels, as only the last column and row
+    // of every split are wider than the tile size.
+    assert_round_trip("three", &Grid::<3>::unique(150, 100));
+}
+
+#[test]
+fn four_children_per_axis() {
+    assert_round_trip("four", &Grid::<4>::unique(200, 130));
+}
+
+#[test]
+fn non_square_tiles() {
+    assert_round_trip("non_square", &Grid::<2>::unique(100, 21));
+}
@@ -0,0 +1,148 @@
+mod support;
+
+use backend::{
+    deserialize,
+    serialize::{IndexEntry, IndexFooter, IndexLevel, Le64},
+    Dataset, GeoTree, Layer,
+};
+use bytemuck::Pod;
+use support::Grid;
+
+fn write<D>(dataset: &D) -> Vec<u8>
+where
+    D: Dataset,
+    D::Type: Pod,
+    D::AggregateType: Pod,
+{
+    let mut bytes = Vec::new();
+    GeoTree::build(dataset).write(&mut bytes).unwrap();
+
+    bytes
+}
+
+/// The file without its footer, so that it is read by walking the tree.
+fn without_index(bytes: &[u8]) -> Vec<u8> {
+    bytes[..bytes.len() - std::mem::size_of::<IndexFooter>()].to_vec()
+}
+
+/// Looks up every address of the first levels, and one past the end of
+/// every level, both in the index and by walking the tree.
+fn assert_lookups_match<D>(indexed: &deserialize::GeoTree<D>, walked: &deserialize::GeoTree<D>)
+where
+    D: Layer,
+    D::Type: Pod + PartialEq + std::fmt::Debug,
+    D::AggregateType: Pod + PartialEq + std::fmt::Debug,
+{
+    let levels = walked.info().unwrap().levels;
+
+    for z in 0..=levels {
+        let size = D::CHILDREN_PER_AXIS.pow(z as u32);
+
+        for y in 0..=size {
+            for x in 0..=size {
+                let expected = walked.get_tile(x, y, z).unwrap();
+                let actual = indexed.get_tile(x, y, z).unwrap();
+
+                assert_eq!(
+                    expected.map(|tile| (tile.bounds, tile.data)),
+                    actual.map(|tile| (tile.bounds, tile.data)),
+                    "{z}/{y}/{x}"
+                );
+                assert_eq!(
+                    walked.get_tile_aggregate(x, y, z).unwrap(),
+                    indexed.get_tile_aggregate(x, y, z).unwrap(),
+                    "aggregate of {z}/{y}/{x}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn index_matches_the_tree() {
+    let bytes = write(&Grid::<2>::unique(100, 60));
+
+    let indexed = deserialize::GeoTree::<Grid<2>>::from_source(bytes.clone());
+    let walked = deserialize::GeoTree::<Grid<2>>::from_source(without_index(&bytes));
+
+    assert!(indexed.info().unwrap().indexed);
+    assert!(!walked.info().unwrap().indexed);
+
+    assert_lookups_match(&indexed, &walked);
+}
+
+#[test]
+fn index_matches_the_tree_with_three_children_per_axis() {
+    let bytes = write(&Grid::<3>::unique(150, 100));
+
+    let indexed = deserialize::GeoTree::<Grid<3>>::from_source(bytes.clone());
+    let walked = deserialize::GeoTree::<Grid<3>>::from_source(without_index(&bytes));
+
+    assert_lookups_match(&indexed, &walked);
+}
+
+#[test]
+fn out_of_range_addresses_are_none() {
+    let bytes = write(&Grid::<2>::unique(100, 60));
+
+    for read in [
+        deserialize::GeoTree::<Grid<2>>::from_source(bytes.clone()),
+        deserialize::GeoTree::<Grid<2>>::from_source(without_index(&bytes)),
+    ] {
+        let levels = read.info().unwrap().levels;
+
+        assert!(read.get_tile(0, 0, 0).unwrap().is_some());
+
+        assert!(read.get_tile(0, 0, levels).unwrap().is_none());
+        assert!(read.get_tile(2, 0, 1).unwrap().is_none());
+        assert!(read.get_tile(0, 2, 1).unwrap().is_none());
+        assert!(read.get_tile(usize::MAX, usize::MAX, 1).unwrap().is_none());
+        assert!(read.get_tile(0, 0, usize::MAX).unwrap().is_none());
+        assert!(read.get_tile_aggregate(0, 0, 64).unwrap().is_none());
+    }
+}
+
+#[test]
+fn indices_past_the_end_are_ignored() {
+    let bytes = write(&Grid::<2>::unique(100, 60));
+    let walked = deserialize::GeoTree::<Grid<2>>::from_source(without_index(&bytes));
+
+    let footer = bytes.len() - std::mem::size_of::<IndexFooter>();
+
+    // Table of levels past the end of the file
+    let mut damaged = bytes.clone();
+    damaged[footer..footer + 8].copy_from_slice(bytemuck::bytes_of(&Le64::new(bytes.len())));
+
+    let read = deserialize::GeoTree::<Grid<2>>::from_source(damaged);
+    assert!(!read.info().unwrap().indexed);
+    assert_lookups_match(&read, &walked);
+
+    // More levels than the file holds
+    let mut damaged = bytes.clone();
+    damaged[footer + 8..footer + 16].copy_from_slice(bytemuck::bytes_of(&Le64::new(usize::MAX)));
+
+    let read = deserialize::GeoTree::<Grid<2>>::from_source(damaged);
+    assert!(!read.info().unwrap().indexed);
+    assert_lookups_match(&read, &walked);
+}
+
+#[test]
+fn entries_past_the_end_are_missing() {
+    let mut bytes = write(&Grid::<2>::unique(100, 60));
+
+    let footer: IndexFooter =
+        bytemuck::pod_read_unaligned(&bytes[bytes.len() - std::mem::size_of::<IndexFooter>()..]);
+    let level: IndexLevel = bytemuck::pod_read_unaligned(
+        &bytes[footer.offset.get()..footer.offset.get() + std::mem::size_of::<IndexLevel>()],
+    );
+
+    // The size of the root's tile, the last field of its entry
+    let tile_size = level.offset.get() + std::mem::size_of::<IndexEntry>() - 8;
+    bytes[tile_size..tile_size + 8].copy_from_slice(bytemuck::bytes_of(&Le64::new(usize::MAX)));
+
+    let read = deserialize::GeoTree::<Grid<2>>::from_source(bytes);
+
+    assert!(read.info().unwrap().indexed);
+    assert!(read.get_tile(0, 0, 0).unwrap().is_none());
+    assert!(read.get_tile(0, 0, 1).unwrap().is_some());
+}
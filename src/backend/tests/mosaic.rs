@@ -0,0 +1,172 @@
+mod support;
+
+use backend::{
+    earth_map::blue_marble_panel,
+    mosaic::{MosaicDataset, Overlap},
+    Bounds, Dataset, Tile,
+};
+use geo::Coord;
+use std::{io::ErrorKind, path::Path};
+use support::{temp_path, Grid};
+
+/// A source positioned by its bounds, like a georeferenced file.
+type Patch = Grid<2, f32>;
+
+/// Values unique within the patch, counting up from `first`, so that any
+/// value pasted in the wrong place shows.
+fn grid(bounds: Bounds, width: usize, height: usize, first: f32) -> Patch {
+    Grid::new(width, height, move |x, y| first + (y * width + x) as f32).with_bounds(bounds)
+}
+
+fn uniform(bounds: Bounds, width: usize, height: usize, value: f32) -> Patch {
+    Grid::new(width, height, move |_, _| value).with_bounds(bounds)
+}
+
+fn bounds(west: f32, south: f32, east: f32, north: f32) -> Bounds {
+    Bounds::new(Coord { x: west, y: south }, Coord { x: east, y: north })
+}
+
+#[test]
+fn pastes_sources_by_their_bounds() {
+    let west = grid(bounds(-180., -90., 0., 90.), 4, 4, 1.);
+    let east = grid(bounds(0., -90., 180., 90.), 4, 4, 100.);
+
+    let expected: Tile<f32> = west
+        .data()
+        .iter()
+        .zip(&east.data())
+        .map(|(west, east)| west.iter().chain(east).copied().collect())
+        .collect();
+
+    let mosaic = MosaicDataset::new(vec![east, west], Overlap::FirstWins);
+
+    assert_eq!(mosaic.bounds(), bounds(-180., -90., 180., 90.));
+    assert_eq!(mosaic.data(), expected);
+}
+
+#[test]
+fn first_source_wins_where_they_overlap() {
+    let world = bounds(-180., -90., 180., 90.);
+
+    let mosaic = MosaicDataset::new(
+        vec![
+            uniform(world, 4, 2, 1.),
+            uniform(bounds(0., -90., 180., 90.), 2, 2, 3.),
+        ],
+        Overlap::FirstWins,
+    );
+
+    assert_eq!(mosaic.data(), vec![vec![1.; 4]; 2]);
+}
+
+#[test]
+fn overlapping_sources_blend_equally() {
+    let world = bounds(-180., -90., 180., 90.);
+
+    let mosaic = MosaicDataset::new(
+        vec![
+            uniform(world, 4, 2, 1.),
+            uniform(world, 4, 2, 3.),
+            uniform(bounds(0., -90., 180., 90.), 2, 2, 8.),
+        ],
+        Overlap::Blend,
+    );
+
+    // Two sources cover the western half, three the eastern
+    assert_eq!(mosaic.data(), vec![vec![2., 2., 4., 4.]; 2]);
+}
+
+#[test]
+fn fills_gaps_with_the_default() {
+    let mosaic = MosaicDataset::new(
+        vec![
+            uniform(bounds(-180., -90., -90., 90.), 2, 4, 1.),
+            uniform(bounds(90., 0., 180., 90.), 2, 2, 2.),
+        ],
+        Overlap::FirstWins,
+    );
+
+    let data = mosaic.data();
+
+    assert_eq!(data.len(), 4);
+
+    for (y, row) in data.iter().enumerate() {
+        let east = if y < 2 { 2. } else { 0. };
+
+        assert_eq!(row, &[1., 1., 0., 0., 0., 0., east, east], "row {y}");
+    }
+}
+
+#[test]
+fn windows_match_the_whole_mosaic() {
+    let mosaic = MosaicDataset::new(
+        vec![
+            grid(bounds(-180., -90., 0., 90.), 6, 6, 1.),
+            grid(bounds(-90., -45., 90., 45.), 3, 3, 100.),
+            grid(bounds(0., 0., 180., 90.), 5, 2, 200.),
+        ],
+        Overlap::Blend,
+    );
+
+    let data = mosaic.data();
+    assert_eq!(mosaic.size(), (12, 6));
+
+    for (x, y, width, height) in [(0, 0, 12, 6), (3, 1, 5, 4), (11, 5, 1, 1), (6, 0, 6, 3)] {
+        let expected: Tile<f32> = data[y..y + height]
+            .iter()
+            .map(|row| row[x..x + width].to_vec())
+            .collect();
+
+        assert_eq!(
+            mosaic.window(x, y, width, height),
+            expected,
+            "{width}x{height} at {x}, {y}"
+        );
+    }
+}
+
+#[test]
+fn refuses_patterns_without_sources() {
+    let open = |_| Ok::<_, std::io::Error>(uniform(bounds(-180., -90., 180., 90.), 1, 1, 1.));
+
+    let pattern = temp_path("mosaic-missing-*.tif");
+    let missing = MosaicDataset::from_glob(pattern.to_str().unwrap(), Overlap::FirstWins, open);
+    assert_eq!(missing.err().unwrap().kind(), ErrorKind::NotFound);
+
+    let invalid = MosaicDataset::from_glob("[", Overlap::FirstWins, open);
+    assert_eq!(invalid.err().unwrap().kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn reports_sources_that_cannot_be_opened() {
+    let path = temp_path("mosaic-unreadable.tif");
+    std::fs::write(&path, []).unwrap();
+
+    let mosaic =
+        MosaicDataset::<Patch>::from_glob(path.to_str().unwrap(), Overlap::FirstWins, |_| {
+            Err("not a raster")
+        });
+
+    let message = mosaic.err().unwrap().to_string();
+    assert!(message.contains("mosaic-unreadable.tif"), "{message}");
+    assert!(message.contains("not a raster"), "{message}");
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn positions_blue_marble_panels_by_name() {
+    let panel = |name: &str| blue_marble_panel(Path::new(name));
+
+    assert_eq!(
+        panel("world.topo.bathy.200412.3x21600x21600.A1.png"),
+        Some(bounds(-180., 0., -90., 90.))
+    );
+    assert_eq!(
+        panel("/data/world.topo.bathy.200412.3x21600x21600.D2.png"),
+        Some(bounds(90., -90., 180., 0.))
+    );
+
+    assert_eq!(panel("world.topo.bathy.200412.3x21600x21600.png"), None);
+    assert_eq!(panel("world.topo.bathy.200412.3x21600x21600.E1.png"), None);
+}
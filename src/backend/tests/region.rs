@@ -0,0 +1,219 @@
+mod support;
+
+use backend::{
+    deserialize,
+    region::{circle, normalize},
+    Bounds, GeoTree,
+};
+use geo::{Area, Contains, Coord, LineString, MultiPolygon, Point, Polygon};
+use support::Grid;
+
+fn polygon(coords: &[(f32, f32)]) -> Polygon<f32> {
+    Polygon::new(LineString::from(coords.to_vec()), Vec::new())
+}
+
+fn assert_within_world(region: &MultiPolygon<f32>) {
+    for coord in region
+        .0
+        .iter()
+        .flat_map(|polygon| polygon.exterior().coords())
+    {
+        assert!(
+            (-180.0..=180.).contains(&coord.x) && (-90.0..=90.).contains(&coord.y),
+            "{coord:?} outside of the world"
+        );
+    }
+}
+
+fn assert_contains(region: &MultiPolygon<f32>, inside: &[(f32, f32)], outside: &[(f32, f32)]) {
+    for &point in inside {
+        assert!(region.contains(&Point::from(point)), "{point:?} not inside");
+    }
+
+    for &point in outside {
+        assert!(!region.contains(&Point::from(point)), "{point:?} inside");
+    }
+}
+
+#[test]
+fn leaves_ordinary_polygons() {
+    let square = polygon(&[(10., 10.), (20., 10.), (20., 20.), (10., 20.)]);
+    let region = normalize(&square).unwrap();
+
+    assert_eq!(region.0.len(), 1);
+    assert!((region.unsigned_area() - square.unsigned_area()).abs() < 1e-3);
+}
+
+#[test]
+fn splits_at_the_antimeridian() {
+    let region = normalize(&polygon(&[
+        (170., -10.),
+        (-170., -10.),
+        (-170., 10.),
+        (170., 10.),
+    ]))
+    .unwrap();
+
+    assert_eq!(region.0.len(), 2);
+    assert_within_world(&region);
+    assert!((region.unsigned_area() - 400.).abs() < 1e-2);
+
+    assert_contains(
+        &region,
+        &[(175., 0.), (-175., 0.)],
+        &[(0., 0.), (160., 0.), (-160., 0.)],
+    );
+}
+
+#[test]
+fn closes_rings_around_poles() {
+    let ring = |lat: f32| {
+        (-180..180)
+            .step_by(30)
+            .map(|lon| (lon as f32, lat))
+            .collect::<Vec<_>>()
+    };
+
+    let north = normalize(&polygon(&ring(80.))).unwrap();
+
+    assert_within_world(&north);
+    assert!((north.unsigned_area() - 3600.).abs() < 1e-1);
+    assert_contains(&north, &[(0., 85.), (-179., 89.)], &[(0., 75.), (0., -85.)]);
+
+    // Going the other way around makes no difference
+    let mut reversed = ring(-80.);
+    reversed.reverse();
+
+    let south = normalize(&polygon(&reversed)).unwrap();
+
+    assert_within_world(&south);
+    assert!((south.unsigned_area() - 3600.).abs() < 1e-1);
+    assert_contains(
+        &south,
+        &[(0., -85.), (179., -89.)],
+        &[(0., -75.), (0., 85.)],
+    );
+}
+
+#[test]
+fn circles_across_the_antimeridian() {
+    let region = circle(Coord { x: 180., y: 0. }, 500.).unwrap();
+
+    assert_eq!(region.0.len(), 2);
+    assert_within_world(&region);
+    assert_contains(
+        &region,
+        &[(179., 0.), (-179., 0.), (178., 3.)],
+        &[(0., 0.), (170., 0.), (-170., 0.)],
+    );
+}
+
+#[test]
+fn circles_around_poles() {
+    // About 9° of latitude
+    let pole = circle(Coord { x: 0., y: 90. }, 1000.).unwrap();
+
+    assert_within_world(&pole);
+    assert_contains(
+        &pole,
+        &[(-170., 89.), (100., 82.)],
+        &[(0., 80.), (0., -85.)],
+    );
+
+    // The pole is 5° from the centre, within the radius
+    let near = circle(Coord { x: 45., y: -85. }, 1000.).unwrap();
+
+    assert_within_world(&near);
+    assert_contains(
+        &near,
+        &[(-135., -88.), (45., -78.)],
+        &[(-135., -80.), (0., 0.)],
+    );
+}
+
+#[test]
+fn circles_around_both_poles() {
+    let region = circle(Coord { x: 0., y: 0. }, 15000.).unwrap();
+
+    assert_within_world(&region);
+    assert_contains(
+        &region,
+        &[(0., 89.), (0., -89.), (90., 0.), (-90., 0.)],
+        &[(180., 0.), (-179., 10.)],
+    );
+
+    assert!(circle(Coord { x: 0., y: 0. }, 0.).unwrap().0.is_empty());
+    assert!(
+        (circle(Coord { x: 0., y: 0. }, 30000.)
+            .unwrap()
+            .unsigned_area()
+            - 64800.)
+            .abs()
+            < 1e-1
+    );
+}
+
+#[test]
+fn refuses_non_finite_queries() {
+    for value in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+        let square = |x: f32, y: f32| polygon(&[(x, y), (20., 10.), (20., 20.), (10., 20.)]);
+
+        assert!(normalize(&square(value, 10.)).is_none());
+        assert!(normalize(&square(10., value)).is_none());
+
+        assert!(circle(Coord { x: value, y: 0. }, 500.).is_none());
+        assert!(circle(Coord { x: 0., y: value }, 500.).is_none());
+        assert!(circle(Coord { x: 0., y: 0. }, value).is_none());
+    }
+}
+
+#[test]
+fn clamps_latitudes_and_wraps_longitudes() {
+    let region = normalize(&polygon(&[
+        (10., 80.),
+        (20., 80.),
+        (20., 120.),
+        (10., 120.),
+    ]))
+    .unwrap();
+
+    assert_within_world(&region);
+    assert!((region.unsigned_area() - 100.).abs() < 1e-2);
+
+    let region = circle(Coord { x: 540., y: 0. }, 500.).unwrap();
+
+    assert_within_world(&region);
+    assert_contains(&region, &[(179., 0.), (-179., 0.)], &[(0., 0.)]);
+}
+
+#[test]
+fn aggregates_across_the_antimeridian() {
+    let tree = GeoTree::build(&Grid::<2>::unique(64, 64));
+
+    let mut bytes = Vec::new();
+    tree.write(&mut bytes).unwrap();
+    let read = deserialize::GeoTree::<Grid<2>>::from_source(bytes);
+
+    let east = Bounds::new(Coord { x: 90., y: 0. }, Coord { x: 180., y: 90. });
+    let west = Bounds::new(Coord { x: -180., y: 0. }, Coord { x: -90., y: 90. });
+
+    let expected = read.get_aggregate(east.to_polygon()).unwrap().unwrap()
+        + read.get_aggregate(west.to_polygon()).unwrap().unwrap();
+
+    // Read as plain longitudes, the same ring covers the other half instead
+    let query = polygon(&[
+        (90., 0.),
+        (150., 0.),
+        (-150., 0.),
+        (-90., 0.),
+        (-90., 90.),
+        (-150., 90.),
+        (150., 90.),
+        (90., 90.),
+    ]);
+
+    assert_eq!(
+        read.get_aggregate(normalize(&query).unwrap()).unwrap(),
+        Some(expected)
+    );
+}
@@ -0,0 +1,184 @@
+mod support;
+
+use std::ops::Range;
+
+use backend::{
+    deserialize::{self, remote::RemoteIndex},
+    serialize::{IndexEntry, IndexFooter, Le64},
+    Dataset, GeoTree,
+};
+use bytemuck::Pod;
+use common::codec::Codec;
+use support::Grid;
+
+/// Repeats every 16 values, so that most tiles are duplicates, and is
+/// compressed.
+type Stripes = Grid<2, f32, 16, { Codec::Lz4 as u8 }>;
+
+fn stripes() -> Stripes {
+    Grid::new(128, 128, |x, y| ((x % 16) * (y % 16)) as f32)
+}
+
+/// Reads every tile of the database built from `dataset` the way a client
+/// using range requests would, and compares it to reading the whole file.
+fn assert_remote_matches<D>(dataset: &D)
+where
+    D: Dataset,
+    D::Type: Pod + PartialEq + std::fmt::Debug,
+    D::AggregateType: Pod,
+{
+    let mut file = Vec::new();
+    GeoTree::build(dataset).write(&mut file).unwrap();
+
+    let fetch = |range: Range<u64>| &file[range.start as usize..range.end as usize];
+
+    let size = file.len() as u64;
+    let table = RemoteIndex::table_range(fetch(size - RemoteIndex::FOOTER_SIZE..size))
+        .unwrap()
+        .unwrap();
+    let index = RemoteIndex::new(fetch(table));
+
+    let read = deserialize::GeoTree::<D>::from_source(file.clone());
+
+    assert_eq!(index.levels(), read.info().unwrap().levels);
+
+    for z in 0..=index.levels() {
+        let size = D::CHILDREN_PER_AXIS.pow(z as u32);
+
+        for y in 0..=size {
+            for x in 0..=size {
+                let remote = index
+                    .block_range(x, y, z)
+                    .and_then(|block| index.entry(fetch(block), x, y, z).unwrap())
+                    .and_then(|entry| {
+                        RemoteIndex::tile::<D::Type>(&entry, fetch(entry.tile_range())).unwrap()
+                    });
+
+                match (read.get_tile(x, y, z).unwrap(), remote) {
+                    (Some(expected), Some(actual)) => {
+                        assert_eq!(expected.bounds, actual.bounds, "bounds of {z}/{y}/{x}");
+                        assert_eq!(expected.data, actual.data, "data of {z}/{y}/{x}");
+                    }
+                    (None, None) => {}
+                    (expected, actual) => panic!(
+                        "{z}/{y}/{x} expected: {}, read: {}",
+                        expected.is_some(),
+                        actual.is_some()
+                    ),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn inline_tiles() {
+    assert_remote_matches(&Grid::<3>::unique(150, 100));
+}
+
+#[test]
+fn compressed_duplicate_tiles() {
+    let mut file = Vec::new();
+    GeoTree::build(&stripes()).write(&mut file).unwrap();
+
+    let info = deserialize::GeoTree::<Stripes>::from_source(file)
+        .info()
+        .unwrap();
+    assert!(info.compressed_tiles > 0 && info.duplicate_tiles > 0);
+
+    assert_remote_matches(&stripes());
+}
+
+#[test]
+fn missing_index() {
+    let mut file = Vec::new();
+    GeoTree::build(&stripes()).write(&mut file).unwrap();
+
+    let legacy = &file[..file.len() - RemoteIndex::FOOTER_SIZE as usize];
+
+    assert!(
+        RemoteIndex::table_range(&legacy[legacy.len() - RemoteIndex::FOOTER_SIZE as usize..])
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn damaged_footers_are_errors() {
+    let footer = |offset: usize, levels: usize| {
+        bytemuck::bytes_of(&IndexFooter {
+            offset: Le64::new(offset),
+            levels: Le64::new(levels),
+            magic: IndexFooter::MAGIC,
+        })
+        .to_vec()
+    };
+
+    assert_eq!(
+        RemoteIndex::table_range(&footer(64, 2)).unwrap(),
+        Some(64..112)
+    );
+
+    assert!(RemoteIndex::table_range(&footer(64, usize::MAX)).is_err());
+    assert!(RemoteIndex::table_range(&footer(usize::MAX, 1)).is_err());
+}
+
+#[test]
+fn fetches_blocks_of_a_row() {
+    let mut file = Vec::new();
+    // Rows of 64 tiles at the deepest level
+    GeoTree::build(&Grid::<2>::unique(1024, 1024))
+        .write(&mut file)
+        .unwrap();
+
+    let fetch = |range: Range<u64>| &file[range.start as usize..range.end as usize];
+
+    let size = file.len() as u64;
+    let table = RemoteIndex::table_range(fetch(size - RemoteIndex::FOOTER_SIZE..size))
+        .unwrap()
+        .unwrap();
+    let index = RemoteIndex::new(fetch(table));
+
+    let entry = std::mem::size_of::<IndexEntry>() as u64;
+    let z = index.levels() - 1;
+
+    // Neighbours share a block, which is all that is fetched of the level
+    let block = index.block_range(17, 0, z).unwrap();
+    assert_eq!(block.end - block.start, RemoteIndex::BLOCK as u64 * entry);
+    assert_eq!(index.block_range(31, 0, z), Some(block.clone()));
+    assert_ne!(index.block_range(32, 0, z), Some(block));
+
+    for x in [0, 17, 31] {
+        let entry = index.entry(fetch(index.block_range(x, 0, z).unwrap()), x, 0, z);
+        assert!(entry.unwrap().is_some(), "{x}");
+    }
+}
+
+#[test]
+fn truncated_ranges_are_errors() {
+    let mut file = Vec::new();
+    GeoTree::build(&stripes()).write(&mut file).unwrap();
+
+    let fetch = |range: Range<u64>| &file[range.start as usize..range.end as usize];
+
+    let size = file.len() as u64;
+    let table = RemoteIndex::table_range(fetch(size - RemoteIndex::FOOTER_SIZE..size))
+        .unwrap()
+        .unwrap();
+    let index = RemoteIndex::new(fetch(table));
+
+    let block = fetch(index.block_range(1, 1, 1).unwrap());
+    assert!(index.entry(&block[..block.len() - 1], 1, 1, 1).is_err());
+
+    let entry = index.entry(block, 1, 1, 1).unwrap().unwrap();
+    let tile = fetch(entry.tile_range());
+
+    for length in [0, 8, tile.len() / 2, tile.len() - 1] {
+        assert!(
+            RemoteIndex::tile::<f32>(&entry, &tile[..length]).is_err(),
+            "{length} of {} bytes",
+            tile.len()
+        );
+    }
+    assert!(RemoteIndex::tile::<f32>(&entry, tile).unwrap().is_some());
+}
@@ -0,0 +1,431 @@
+mod support;
+
+use std::borrow::Cow;
+
+use backend::{
+    deserialize,
+    serialize::{IndexEntry, IndexFooter, IndexLevel},
+    Bounds, GeoTree, Tile,
+};
+use bytemuck::Pod;
+use bytes::Bytes;
+use common::{
+    codec::{Codec, DecodeError},
+    CompressedTileResponse,
+};
+use geo::Coord;
+use proptest::{collection::vec, prelude::*};
+use support::{node_at, Grid, Value};
+
+/// Tags of the codecs the trees are written with.
+const UNCOMPRESSED: u8 = Codec::None as u8;
+const COMPRESSED: u8 = Codec::ShuffleDeltaLz4 as u8;
+
+/// Small tiles, so that even small rasters are split a few times.
+type Synthetic<T, const N: usize, const CODEC: u8> = Grid<N, T, 8, CODEC>;
+
+trait Arbitrary: Value {
+    /// Mostly a few repeating values, so that constant and duplicate
+    /// tiles show up next to arbitrary ones.
+    fn strategy() -> BoxedStrategy<Self>;
+}
+
+impl Arbitrary for [u8; 4] {
+    fn strategy() -> BoxedStrategy<Self> {
+        prop_oneof![Just([0, 0, 0, 255]), any::<[u8; 4]>()].boxed()
+    }
+}
+
+impl Arbitrary for f32 {
+    fn strategy() -> BoxedStrategy<Self> {
+        prop_oneof![Just(0.), any::<f32>()].boxed()
+    }
+}
+
+impl Arbitrary for u8 {
+    fn strategy() -> BoxedStrategy<Self> {
+        prop_oneof![Just(0), Just(1), any::<u8>()].boxed()
+    }
+}
+
+/// Rasters of up to 64×64 values. Their sides differ by at most a factor
+/// of two, so no split ends up with rows or columns of zero values.
+fn raster<T>() -> impl Strategy<Value = Tile<T>>
+where
+    T: Arbitrary,
+{
+    (1..=64_usize)
+        .prop_flat_map(|height| (Just(height), height.div_ceil(2)..=(height * 2).min(64)))
+        .prop_flat_map(|(height, width)| vec(vec(T::strategy(), width), height))
+}
+
+fn bytes_of<T>(values: &[T]) -> &[u8]
+where
+    T: Pod,
+{
+    bytemuck::cast_slice(values)
+}
+
+/// Writes the tree built from `data` to memory, then checks that every
+/// tile, bound and aggregate reads back exactly as built.
+fn assert_round_trip<T, const N: usize, const CODEC: u8>(data: Tile<T>)
+where
+    T: Value + 'static,
+{
+    let tree = GeoTree::build(&Synthetic::<T, N, CODEC>::from_tile(data));
+
+    let mut bytes = Vec::new();
+    tree.write(&mut bytes).unwrap();
+
+    assert_matches(&tree, &deserialize::GeoTree::from_source(bytes));
+}
+
+fn assert_matches<T, const N: usize, const CODEC: u8>(
+    tree: &GeoTree<Synthetic<T, N, CODEC>>,
+    read: &deserialize::GeoTree<Synthetic<T, N, CODEC>>,
+) where
+    T: Value,
+{
+    let mut z = 0;
+
+    loop {
+        let size = N.pow(z as u32);
+        let mut found = false;
+
+        for y in 0..size {
+            for x in 0..size {
+                let expected = node_at(&tree.root, N, x, y, z);
+                let tile = read.get_tile(x, y, z).unwrap();
+
+                let Some(expected) = expected else {
+                    assert!(tile.is_none(), "{z}/{y}/{x} should not exist");
+                    continue;
+                };
+
+                found = true;
+
+                let tile = tile.unwrap_or_else(|| panic!("{z}/{y}/{x} is missing"));
+                let data = expected.data.as_ref().unwrap();
+
+                assert_eq!(expected.bounds, tile.bounds, "bounds of {z}/{y}/{x}");
+                assert_eq!(data.len(), tile.data.len(), "height of {z}/{y}/{x}");
+
+                for (expected, actual) in data.iter().zip(&tile.data) {
+                    assert_eq!(bytes_of(expected), bytes_of(actual), "data of {z}/{y}/{x}");
+                }
+
+                assert_eq!(
+                    expected.aggregate.as_ref().map(bytemuck::bytes_of),
+                    read.get_tile_aggregate(x, y, z)
+                        .unwrap()
+                        .as_ref()
+                        .map(bytemuck::bytes_of),
+                    "aggregate of {z}/{y}/{x}"
+                );
+            }
+        }
+
+        if !found {
+            break;
+        }
+
+        z += 1;
+    }
+
+    // The whole extent contains the root, so its aggregate is returned as is
+    let everything = Bounds::new(Coord { x: -181., y: -91. }, Coord { x: 181., y: 91. });
+
+    assert_eq!(
+        tree.root.aggregate.as_ref().map(bytemuck::bytes_of),
+        read.get_aggregate(everything.to_polygon())
+            .unwrap()
+            .as_ref()
+            .map(bytemuck::bytes_of),
+    );
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn rgba(data in raster::<[u8; 4]>()) {
+        assert_round_trip::<_, 2, UNCOMPRESSED>(data);
+    }
+
+    #[test]
+    fn rgba_compressed(data in raster::<[u8; 4]>()) {
+        assert_round_trip::<_, 2, COMPRESSED>(data);
+    }
+
+    #[test]
+    fn float(data in raster::<f32>()) {
+        assert_round_trip::<_, 2, UNCOMPRESSED>(data);
+    }
+
+    #[test]
+    fn float_compressed(data in raster::<f32>()) {
+        assert_round_trip::<_, 2, COMPRESSED>(data);
+    }
+
+    #[test]
+    fn byte(data in raster::<u8>()) {
+        assert_round_trip::<_, 2, UNCOMPRESSED>(data);
+    }
+
+    #[test]
+    fn three_children_per_axis(data in raster::<f32>()) {
+        assert_round_trip::<_, 3, COMPRESSED>(data);
+    }
+
+    #[test]
+    fn three_children_per_axis_bytes(data in raster::<u8>()) {
+        assert_round_trip::<_, 3, UNCOMPRESSED>(data);
+    }
+}
+
+#[test]
+fn misaligned_source() {
+    let data = (0..40)
+        .map(|y| (0..30).map(|x| (x * y) as f32).collect())
+        .collect();
+
+    let tree = GeoTree::build(&Synthetic::<f32, 2, COMPRESSED>::from_tile(data));
+
+    let mut bytes = vec![0];
+    tree.write(&mut bytes).unwrap();
+
+    // Starts one byte into the allocation, so it has to be copied
+    let source = Bytes::from(bytes).slice(1..);
+
+    assert_matches(&tree, &deserialize::GeoTree::from_source(source));
+}
+
+#[test]
+fn damaged_values_fail_to_decode() {
+    let values: Vec<f32> = (0..256).map(|x| x as f32).collect();
+
+    for codec in [Codec::None, Codec::Lz4, Codec::ShuffleDeltaLz4] {
+        let encoded = codec.encode(&values);
+
+        assert_eq!(codec.decode::<f32>(&encoded, 256).unwrap(), values);
+
+        let truncated = &encoded[..encoded.len() / 2];
+        assert!(codec.decode::<f32>(truncated, 256).is_err(), "{codec:?}");
+
+        // More values than the tile holds
+        assert!(codec.decode::<f32>(&encoded, 128).is_err(), "{codec:?}");
+    }
+}
+
+#[test]
+fn oversized_tiles_fail_to_decode() {
+    let values: Vec<f32> = (0..256).map(|x| x as f32).collect();
+
+    for codec in [Codec::None, Codec::Lz4, Codec::ShuffleDeltaLz4] {
+        let data = codec.encode(&values);
+
+        let tile = |width, height| CompressedTileResponse {
+            bounds: Bounds::new(Coord { x: 0., y: 0. }, Coord { x: 1., y: 1. }),
+            codec,
+            width,
+            height,
+            data: Cow::Borrowed(&data),
+        };
+
+        assert!(tile(16, 16).decode::<f32>().is_ok(), "{codec:?}");
+
+        // Larger than any tile, or than the data can hold, without
+        // allocating the values
+        for (width, height) in [(u32::MAX, u32::MAX), (1 << 17, 1), (1 << 16, 1 << 16)] {
+            assert!(
+                matches!(
+                    tile(width, height).decode::<f32>(),
+                    Err(DecodeError::Size { .. })
+                ),
+                "{codec:?} {width}x{height}"
+            );
+        }
+    }
+}
+
+#[test]
+fn damaged_tiles_are_errors() {
+    type Compressed = Synthetic<f32, 2, COMPRESSED>;
+
+    let data: Tile<f32> = (0..16)
+        .map(|y| (0..16).map(|x| (y * 16 + x) as f32).collect())
+        .collect();
+
+    let mut bytes = Vec::new();
+    GeoTree::build(&Compressed::from_tile(data))
+        .write(&mut bytes)
+        .unwrap();
+
+    let wire = {
+        let read = deserialize::GeoTree::<Compressed>::from_source(bytes.clone());
+        let wire = read
+            .get_tile_bytes(0, 0, 0)
+            .unwrap()
+            .expect("compressed root");
+
+        wire.as_ptr() as usize - read.reader().data.as_ptr() as usize
+    };
+
+    // The width follows the bounds and the codec, one more no longer fits
+    // the values
+    bytes[wire + 20] += 1;
+
+    let read = deserialize::GeoTree::<Compressed>::from_source(bytes);
+
+    let everything = Bounds::new(Coord { x: -181., y: -91. }, Coord { x: 181., y: 91. });
+
+    assert!(read.get_tile(0, 0, 0).is_err());
+    assert!(read.get_tiles(everything, 0).is_err());
+
+    // Passed on as stored, without decoding the values
+    assert!(read.get_compressed_tile(0, 0, 0).is_ok());
+}
+
+#[test]
+fn constant_tiles_are_not_compressed() {
+    type Uniform = Synthetic<f32, 2, COMPRESSED>;
+
+    let tree = GeoTree::build(&Uniform::new(64, 32, |_, _| 1.));
+
+    let mut bytes = Vec::new();
+    tree.write(&mut bytes).unwrap();
+
+    let read = deserialize::GeoTree::<Uniform>::from_source(bytes);
+    let info = read.info().unwrap();
+
+    // The first of every size is constant, the others refer to it
+    assert_eq!(info.compressed_tiles, 0);
+    assert!(info.constant_tiles > 0 && info.duplicate_tiles > 0);
+    assert_eq!(info.constant_tiles + info.duplicate_tiles, info.nodes);
+    assert!(info.bytes_saved > 0);
+
+    assert_matches(&tree, &read);
+}
+
+#[test]
+fn truncated_files_are_errors() {
+    type Compressed = Synthetic<f32, 2, COMPRESSED>;
+
+    let data: Tile<f32> = (0..32)
+        .map(|y| (0..32).map(|x| (y * 32 + x) as f32).collect())
+        .collect();
+
+    let mut bytes = Vec::new();
+    GeoTree::build(&Compressed::from_tile(data))
+        .write(&mut bytes)
+        .unwrap();
+
+    let everything = Bounds::new(Coord { x: -181., y: -91. }, Coord { x: 181., y: 91. });
+
+    for len in (0..bytes.len()).step_by(7) {
+        // Without its footer, the file is read by walking the tree
+        let read = deserialize::GeoTree::<Compressed>::from_source(bytes[..len].to_vec());
+
+        // Cut within the nodes rather than the index behind them
+        if len < bytes.len() / 2 {
+            assert!(read.info().is_err(), "{len} bytes");
+        }
+
+        for z in 0..3 {
+            let _ = read.get_tile(0, 0, z);
+            let _ = read.get_tiles(everything, z as u32);
+        }
+
+        let _ = read.get_aggregate(everything.to_polygon());
+    }
+}
+
+/// Writes a tree of distinct values, so that no tile is constant or a
+/// duplicate.
+fn written<const CODEC: u8>(size: usize) -> Vec<u8> {
+    let data = (0..size)
+        .map(|y| (0..size).map(|x| (y * size + x) as f32).collect())
+        .collect();
+
+    let mut bytes = Vec::new();
+    GeoTree::build(&Synthetic::<f32, 2, CODEC>::from_tile(data))
+        .write(&mut bytes)
+        .unwrap();
+
+    bytes
+}
+
+/// Position of the table of levels of the index, and of the first level.
+fn index_levels(bytes: &[u8]) -> (usize, IndexLevel) {
+    let footer: IndexFooter =
+        bytemuck::pod_read_unaligned(&bytes[bytes.len() - std::mem::size_of::<IndexFooter>()..]);
+
+    let table = footer.offset.get();
+    let level = bytemuck::pod_read_unaligned(&bytes[table..][..std::mem::size_of::<IndexLevel>()]);
+
+    (table, level)
+}
+
+#[test]
+fn looping_children_are_errors() {
+    type Uncompressed = Synthetic<f32, 2, UNCOMPRESSED>;
+
+    let mut bytes = written::<UNCOMPRESSED>(32);
+
+    // The root is its bounds, the number of rows of children and the
+    // length of the first row, followed by its first child. Zeroed, that
+    // child is the root again.
+    let first_child = std::mem::size_of::<Bounds>() + 2 * std::mem::size_of::<u64>();
+    bytes[first_child..first_child + 8].fill(0);
+
+    let read = deserialize::GeoTree::<Uncompressed>::from_source(bytes);
+
+    // Intersecting the root without containing it, so its children are visited
+    let part = Bounds::new(Coord { x: 0., y: 0. }, Coord { x: 10., y: 10. });
+
+    assert!(read.info().is_err());
+    assert!(read.get_aggregate(part.to_polygon()).is_err());
+}
+
+#[test]
+fn unknown_encodings_are_errors() {
+    type Uncompressed = Synthetic<f32, 2, UNCOMPRESSED>;
+
+    let mut bytes = written::<UNCOMPRESSED>(16);
+
+    let (_, level) = index_levels(&bytes);
+    let root: IndexEntry = bytemuck::pod_read_unaligned(
+        &bytes[level.offset.get()..][..std::mem::size_of::<IndexEntry>()],
+    );
+
+    bytes[root.tile.get()] = 0xff;
+
+    let read = deserialize::GeoTree::<Uncompressed>::from_source(bytes);
+
+    assert!(read.get_tile(0, 0, 0).is_err());
+    assert!(read.info().is_err());
+}
+
+#[test]
+fn empty_index_levels_are_ignored() {
+    type Uncompressed = Synthetic<f32, 2, UNCOMPRESSED>;
+
+    let mut bytes = written::<UNCOMPRESSED>(32);
+
+    let everything = Bounds::new(Coord { x: -181., y: -91. }, Coord { x: 181., y: 91. });
+    let expected = deserialize::GeoTree::<Uncompressed>::from_source(bytes.clone())
+        .get_tiles(everything, 1)
+        .unwrap()
+        .len();
+
+    // The width of the second level
+    let (table, _) = index_levels(&bytes);
+    let width = table + std::mem::size_of::<IndexLevel>() + std::mem::size_of::<u64>();
+    bytes[width..width + 8].fill(0);
+
+    // Read by walking the tree instead
+    let read = deserialize::GeoTree::<Uncompressed>::from_source(bytes);
+
+    assert!(!read.info().unwrap().indexed);
+    assert_eq!(read.get_tiles(everything, 1).unwrap().len(), expected);
+}
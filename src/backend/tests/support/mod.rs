@@ -0,0 +1,329 @@
+#![allow(dead_code)]
+
+use std::{
+    fmt::Debug,
+    path::{Path, PathBuf},
+};
+
+use backend::{
+    deserialize, serialize::IndexFooter, Bounds, Dataset, GeoTree, Layer, Tile, TileNode,
+};
+use bytemuck::{Pod, Zeroable};
+use common::codec::Codec;
+use geo::Coord;
+
+/// A value type together with the aggregate stored for it.
+pub trait Value: Pod + Debug {
+    type Aggregate: Pod + Debug;
+
+    fn aggregate(values: &[Self]) -> Self::Aggregate;
+    fn combine(a: Self::Aggregate, b: Self::Aggregate) -> Self::Aggregate;
+}
+
+impl Value for u32 {
+    type Aggregate = u64;
+
+    fn aggregate(values: &[Self]) -> Self::Aggregate {
+        values.iter().map(|&value| value as u64).sum()
+    }
+
+    fn combine(a: Self::Aggregate, b: Self::Aggregate) -> Self::Aggregate {
+        a + b
+    }
+}
+
+impl Value for f32 {
+    type Aggregate = f64;
+
+    fn aggregate(values: &[Self]) -> Self::Aggregate {
+        values.iter().map(|&value| value as f64).sum()
+    }
+
+    fn combine(a: Self::Aggregate, b: Self::Aggregate) -> Self::Aggregate {
+        a + b
+    }
+}
+
+/// Has fields of every size, to catch padding mistakes.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct ColorStats {
+    sum: [u64; 4],
+    count: u32,
+    opaque: u16,
+    max_alpha: u8,
+    min_alpha: u8,
+}
+
+impl Value for [u8; 4] {
+    type Aggregate = ColorStats;
+
+    fn aggregate(values: &[Self]) -> Self::Aggregate {
+        let mut stats = ColorStats {
+            min_alpha: u8::MAX,
+            ..Zeroable::zeroed()
+        };
+
+        for value in values {
+            for (sum, channel) in stats.sum.iter_mut().zip(value) {
+                *sum += *channel as u64;
+            }
+
+            stats.count += 1;
+            stats.opaque += (value[3] == u8::MAX) as u16;
+            stats.max_alpha = stats.max_alpha.max(value[3]);
+            stats.min_alpha = stats.min_alpha.min(value[3]);
+        }
+
+        stats
+    }
+
+    fn combine(a: Self::Aggregate, b: Self::Aggregate) -> Self::Aggregate {
+        ColorStats {
+            sum: [0, 1, 2, 3].map(|i| a.sum[i] + b.sum[i]),
+            count: a.count + b.count,
+            opaque: a.opaque + b.opaque,
+            max_alpha: a.max_alpha.max(b.max_alpha),
+            min_alpha: a.min_alpha.min(b.min_alpha),
+        }
+    }
+}
+
+/// Smaller than the alignment of the lengths written around it.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct Extent {
+    min: u8,
+    max: u8,
+    count: u8,
+}
+
+impl Value for u8 {
+    type Aggregate = Extent;
+
+    fn aggregate(values: &[Self]) -> Self::Aggregate {
+        Extent {
+            min: values.iter().copied().min().unwrap_or(u8::MAX),
+            max: values.iter().copied().max().unwrap_or(0),
+            count: values.len() as u8,
+        }
+    }
+
+    fn combine(a: Self::Aggregate, b: Self::Aggregate) -> Self::Aggregate {
+        Extent {
+            min: a.min.min(b.min),
+            max: a.max.max(b.max),
+            count: a.count.wrapping_add(b.count),
+        }
+    }
+}
+
+/// A dataset computing every value from its column and row, branching `N`
+/// times per axis into tiles of `TILE` values, compressed with the
+/// [`Codec`] tagged `CODEC`. It covers the whole globe unless positioned
+/// with [`Grid::with_bounds`].
+pub struct Grid<const N: usize, T = u32, const TILE: u32 = 16, const CODEC: u8 = 0> {
+    pub width: usize,
+    pub height: usize,
+    bounds: Bounds,
+    value: Box<dyn Fn(usize, usize) -> T>,
+}
+
+impl<const N: usize, T, const TILE: u32, const CODEC: u8> Grid<N, T, TILE, CODEC> {
+    pub fn new<F>(width: usize, height: usize, value: F) -> Self
+    where
+        F: Fn(usize, usize) -> T + 'static,
+    {
+        Self {
+            width,
+            height,
+            bounds: Bounds::new(Coord { x: -180., y: -90. }, Coord { x: 180., y: 90. }),
+            value: Box::new(value),
+        }
+    }
+
+    /// Holds the values of `data`, which must not be empty.
+    pub fn from_tile(data: Tile<T>) -> Self
+    where
+        T: Copy + 'static,
+    {
+        Self::new(data[0].len(), data.len(), move |x, y| data[y][x])
+    }
+
+    pub fn with_bounds(self, bounds: Bounds) -> Self {
+        Self { bounds, ..self }
+    }
+}
+
+impl<const N: usize> Grid<N> {
+    /// Every value is unique, so that any value ending up in the wrong
+    /// place shows.
+    pub fn unique(width: usize, height: usize) -> Self {
+        Self::new(width, height, move |x, y| (y * width + x) as u32)
+    }
+}
+
+impl<const N: usize, T, const TILE: u32, const CODEC: u8> Layer for Grid<N, T, TILE, CODEC>
+where
+    T: Value,
+{
+    type Type = T;
+    type AggregateType = T::Aggregate;
+
+    fn aggregate2(values: &[Self::AggregateType]) -> Option<Self::AggregateType> {
+        values.iter().copied().reduce(T::combine)
+    }
+
+    const CHILDREN_PER_AXIS: usize = N;
+}
+
+impl<const N: usize, T, const TILE: u32, const CODEC: u8> Dataset for Grid<N, T, TILE, CODEC>
+where
+    T: Value,
+{
+    fn aggregate(values: &[Self::Type]) -> Option<Self::AggregateType> {
+        Some(T::aggregate(values))
+    }
+
+    /// Keeps the nearest value, so that downsampled values stay unique.
+    fn downsample(data: &Tile<Self::Type>) -> Tile<Self::Type> {
+        let size = Self::TILE_SIZE as usize;
+
+        (0..size)
+            .map(|y| {
+                let row = &data[y * data.len() / size];
+
+                (0..size).map(|x| row[x * row.len() / size]).collect()
+            })
+            .collect()
+    }
+
+    fn default() -> Self::Type {
+        T::zeroed()
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+
+    fn window(&self, x: usize, y: usize, width: usize, height: usize) -> Tile<Self::Type> {
+        (y..y + height)
+            .map(|y| (x..x + width).map(|x| (self.value)(x, y)).collect())
+            .collect()
+    }
+
+    fn bounds(&self) -> Bounds {
+        self.bounds
+    }
+
+    const TILE_SIZE: u32 = TILE;
+    const MAX_LEVEL: u32 = 0;
+
+    const CODEC: Codec = match CODEC {
+        0 => Codec::None,
+        1 => Codec::Lz4,
+        _ => Codec::ShuffleDeltaLz4,
+    };
+}
+
+/// A path in the temporary directory, unique to this test process.
+pub fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("{name}-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    path
+}
+
+/// Copies `path` without its index, like files written before the index
+/// existed, so that lookups have to walk the tree.
+pub fn strip_index(path: &Path) -> PathBuf {
+    let stripped = path.with_extension("legacy.db");
+
+    let data = std::fs::read(path).unwrap();
+    std::fs::write(
+        &stripped,
+        &data[..data.len() - std::mem::size_of::<IndexFooter>()],
+    )
+    .unwrap();
+
+    stripped
+}
+
+pub fn node_at<T, U>(
+    root: &TileNode<T, U>,
+    children_per_axis: usize,
+    x: usize,
+    y: usize,
+    z: usize,
+) -> Option<&TileNode<T, U>> {
+    let size = children_per_axis.pow(z as u32);
+
+    if x >= size || y >= size {
+        return None;
+    }
+
+    let mut node = root;
+
+    for level in 1..=z {
+        let divisor = children_per_axis.pow((z - level) as u32);
+
+        node = node
+            .children
+            .get((y / divisor) % children_per_axis)?
+            .get((x / divisor) % children_per_axis)?;
+    }
+
+    Some(node)
+}
+
+fn depth<T, U>(node: &TileNode<T, U>) -> usize {
+    node.children
+        .iter()
+        .flatten()
+        .map(|child| depth(child) + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Checks that every tile of `tree`, and nothing else, can be read back
+/// from `read` by its address.
+pub fn assert_tiles_match<D>(tree: &GeoTree<D>, read: &deserialize::GeoTree<D>)
+where
+    D: Dataset,
+    D::Type: Pod + PartialEq + std::fmt::Debug,
+    D::AggregateType: Pod,
+{
+    let n = D::CHILDREN_PER_AXIS;
+
+    for z in 0..=depth(&tree.root) + 1 {
+        let size = n.pow(z as u32);
+
+        for y in 0..=size {
+            for x in 0..=size {
+                let expected = node_at(&tree.root, n, x, y, z);
+                let actual = read.get_tile(x, y, z).unwrap();
+
+                match (expected, actual) {
+                    (Some(expected), Some(actual)) => {
+                        assert_eq!(expected.bounds, actual.bounds, "bounds of {z}/{y}/{x}");
+                        assert_eq!(
+                            expected.data.as_ref().unwrap(),
+                            &actual
+                                .data
+                                .iter()
+                                .map(|row| row.to_vec())
+                                .collect::<Vec<_>>(),
+                            "data of {z}/{y}/{x}"
+                        );
+                    }
+                    (None, None) => {}
+                    (expected, actual) => panic!(
+                        "{z}/{y}/{x} expected: {}, read: {}",
+                        expected.is_some(),
+                        actual.is_some()
+                    ),
+                }
+            }
+        }
+    }
+}
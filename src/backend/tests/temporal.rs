@@ -0,0 +1,91 @@
+mod support;
+
+use std::collections::BTreeMap;
+
+use backend::{deserialize, temporal::TemporalGeoTree, Bounds, GeoTree};
+use geo::Coord;
+use support::{temp_path, Grid};
+
+const TIMES: [&str; 3] = ["2024-01", "2024-02", "2024-03"];
+
+/// A series where every timestep is a grid of a different size, so that
+/// each has its own aggregate. Its files are named after `name`.
+fn series(name: &str) -> (TemporalGeoTree<Grid<2>>, Vec<u64>) {
+    let mut timesteps = BTreeMap::new();
+    let mut aggregates = Vec::new();
+
+    for (i, time) in TIMES.into_iter().enumerate() {
+        let path = temp_path(&format!("{name}-{time}"));
+
+        let tree = GeoTree::build(&Grid::<2>::unique(20 + i, 10));
+        tree.write_to_file(&path).unwrap();
+
+        aggregates.push(tree.root.aggregate.unwrap());
+        timesteps.insert(time.to_owned(), deserialize::GeoTree::new(&path).unwrap());
+
+        // Stays mapped until the series is dropped
+        std::fs::remove_file(path).unwrap();
+    }
+
+    (TemporalGeoTree::new(timesteps), aggregates)
+}
+
+fn series_between(
+    series: &TemporalGeoTree<Grid<2>>,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Vec<(String, Option<u64>)> {
+    let everything = Bounds::new(Coord { x: -181., y: -91. }, Coord { x: 181., y: 91. });
+
+    series
+        .get_aggregate_series(from, to, everything.to_polygon())
+        .unwrap()
+        .into_iter()
+        .map(|step| (step.time.to_owned(), step.aggregate))
+        .collect()
+}
+
+#[test]
+fn aggregates_every_timestep_in_order() {
+    let (series, aggregates) = series("every-timestep");
+
+    let expected: Vec<_> = TIMES
+        .iter()
+        .zip(aggregates)
+        .map(|(time, aggregate)| (time.to_string(), Some(aggregate)))
+        .collect();
+
+    assert_eq!(series_between(&series, None, None), expected);
+}
+
+#[test]
+fn bounds_are_inclusive_and_may_be_open() {
+    let (series, aggregates) = series("inclusive-bounds");
+
+    let times = |from, to| -> Vec<_> {
+        series_between(&series, from, to)
+            .into_iter()
+            .map(|(time, _)| time)
+            .collect()
+    };
+
+    assert_eq!(times(Some("2024-02"), Some("2024-03")), TIMES[1..]);
+    assert_eq!(times(None, Some("2024-02")), TIMES[..2]);
+    assert_eq!(times(Some("2024-02"), None), TIMES[1..]);
+    assert_eq!(times(Some("2024-02"), Some("2024-02")), TIMES[1..2]);
+
+    // Labels between timesteps bound the range by where they sort
+    assert_eq!(times(Some("2024-01-15"), Some("2024-02-15")), TIMES[1..2]);
+
+    assert_eq!(
+        series_between(&series, Some("2024-03"), None),
+        vec![("2024-03".to_string(), Some(aggregates[2]))]
+    );
+}
+
+#[test]
+fn reversed_bounds_are_empty() {
+    let (series, _) = series("reversed-bounds");
+
+    assert!(series_between(&series, Some("2024-03"), Some("2024-01")).is_empty());
+}
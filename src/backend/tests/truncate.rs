@@ -0,0 +1,43 @@
+mod support;
+
+use backend::{deserialize, update, GeoTree};
+use support::Grid;
+
+#[test]
+fn keeps_top_levels() {
+    let tree = GeoTree::build(&Grid::<2>::unique(200, 150));
+
+    let mut bytes = Vec::new();
+    tree.write(&mut bytes).unwrap();
+
+    let full = deserialize::GeoTree::<Grid<2>>::from_source(bytes);
+
+    let mut bytes = Vec::new();
+    update::truncate(&full, 2, &mut bytes).unwrap();
+
+    let truncated = deserialize::GeoTree::<Grid<2>>::from_source(bytes);
+
+    assert!(full.info().unwrap().levels > 2);
+    assert_eq!(truncated.info().unwrap().levels, 2);
+
+    for z in 0..2 {
+        let size = 1 << z;
+
+        for y in 0..size {
+            for x in 0..size {
+                let expected = full.get_tile(x, y, z).unwrap().unwrap();
+                let actual = truncated.get_tile(x, y, z).unwrap().unwrap();
+
+                assert_eq!(expected.bounds, actual.bounds, "bounds of {z}/{y}/{x}");
+                assert_eq!(expected.data, actual.data, "data of {z}/{y}/{x}");
+                assert_eq!(
+                    full.get_tile_aggregate(x, y, z).unwrap(),
+                    truncated.get_tile_aggregate(x, y, z).unwrap(),
+                    "aggregate of {z}/{y}/{x}"
+                );
+            }
+        }
+    }
+
+    assert!(truncated.get_tile(0, 0, 2).unwrap().is_none());
+}
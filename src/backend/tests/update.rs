@@ -0,0 +1,109 @@
+mod support;
+
+use backend::{deserialize, update::update, Bounds, GeoTree};
+use geo::{Coord, Intersects};
+use support::{assert_tiles_match, node_at, temp_path, Grid};
+
+/// Added to the values of [`Grid::unique`] within the changed region.
+const CHANGE: u32 = 1_000_000;
+
+#[test]
+fn only_changes_tiles_in_the_region() {
+    let (width, height) = (64, 32);
+    let grid = Grid::<2>::unique(width, height);
+
+    // The north-western quarter, 16 by 16 pixels of 5.625 degrees, has
+    // changed, like in a new version of part of a source dataset
+    let region = Bounds::new(Coord { x: -180., y: 0. }, Coord { x: -90., y: 90. });
+    let changed = Grid::<2>::new(width, height, move |x, y| {
+        let value = (y * width + x) as u32;
+
+        if x < 16 && y < 16 {
+            value + CHANGE
+        } else {
+            value
+        }
+    });
+
+    let old_path = temp_path("update-old");
+    let new_path = temp_path("update-new");
+
+    let old_tree = GeoTree::build(&grid);
+    old_tree.write_to_file(&old_path).unwrap();
+    let old = deserialize::GeoTree::<Grid<2>>::new(&old_path).unwrap();
+
+    update(
+        &old,
+        &changed,
+        region,
+        std::fs::File::create(&new_path).unwrap(),
+    )
+    .unwrap();
+    let updated = deserialize::GeoTree::<Grid<2>>::new(&new_path).unwrap();
+
+    // Reads back the same as building the changed dataset from scratch
+    let new_tree = GeoTree::build(&changed);
+    assert_tiles_match(&new_tree, &updated);
+
+    let mut inside = 0;
+    let mut outside = 0;
+
+    for z in 0..=3 {
+        let size = 2_usize.pow(z as u32);
+
+        for y in 0..size {
+            for x in 0..size {
+                let Some(old_node) = node_at(&old_tree.root, 2, x, y, z) else {
+                    continue;
+                };
+                let new_node = node_at(&new_tree.root, 2, x, y, z).unwrap();
+
+                if old_node.bounds.intersects(&region) {
+                    if old_node.data != new_node.data {
+                        inside += 1;
+                    }
+
+                    continue;
+                }
+
+                // Untouched tiles are copied from the old file as they were
+                assert_eq!(
+                    old.get_tile_bytes(x, y, z).unwrap(),
+                    updated.get_tile_bytes(x, y, z).unwrap(),
+                    "{z}/{y}/{x} changed outside the region"
+                );
+                assert_eq!(old_node.data, new_node.data);
+
+                outside += 1;
+            }
+        }
+    }
+
+    assert!(
+        inside > 0 && outside > 0,
+        "{inside} inside, {outside} outside"
+    );
+
+    std::fs::remove_file(old_path).unwrap();
+    std::fs::remove_file(new_path).unwrap();
+}
+
+#[test]
+fn copies_compressed_tiles_as_they_are() {
+    type Compressed = Grid<2, u32, 16, 2>;
+
+    let grid = Compressed::new(64, 32, |x, y| (y * 64 + x) as u32);
+
+    let mut old_bytes = Vec::new();
+    GeoTree::build(&grid).write(&mut old_bytes).unwrap();
+    let old = deserialize::GeoTree::<Compressed>::from_source(old_bytes.clone());
+    assert!(old.info().unwrap().compressed_tiles > 0);
+
+    // Nothing changes outside the globe, so every tile is copied
+    let region = Bounds::new(Coord { x: 190., y: 0. }, Coord { x: 200., y: 10. });
+
+    let mut new_bytes = Vec::new();
+    update(&old, &grid, region, &mut new_bytes).unwrap();
+
+    assert!(old_bytes == new_bytes, "tiles were encoded again");
+}
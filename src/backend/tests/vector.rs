@@ -0,0 +1,101 @@
+mod support;
+
+use backend::{
+    vector::{self, tile_bounds, VectorStore},
+    Bounds,
+};
+use geo::{Coord, LineString};
+use support::temp_path;
+
+fn equator() -> LineString<f32> {
+    (0..=360)
+        .map(|lon| Coord {
+            x: lon as f32 - 180.,
+            y: 0.,
+        })
+        .collect()
+}
+
+fn assert_within(lines: &[LineString<f32>], bounds: Bounds) {
+    for coord in lines.iter().flat_map(|line| line.coords()) {
+        assert!(
+            coord.x >= bounds.min().x - 1e-4
+                && coord.x <= bounds.max().x + 1e-4
+                && coord.y >= bounds.min().y - 1e-4
+                && coord.y <= bounds.max().y + 1e-4,
+            "{coord:?} outside of {bounds:?}"
+        );
+    }
+}
+
+#[test]
+fn clips_to_tiles() {
+    let store = VectorStore::new([equator()]);
+
+    // The equator is the border between the two rows of level 1
+    let bounds = Bounds::new(Coord { x: -10., y: -5. }, Coord { x: 20., y: 5. });
+    let lines = store.lines(bounds, 0.);
+
+    assert_within(&lines, bounds);
+
+    let length = lines
+        .iter()
+        .flat_map(|line| line.lines())
+        .map(|segment| segment.dx().abs())
+        .sum::<f32>();
+
+    assert!((length - 30.).abs() < 1e-3, "clipped length {length}");
+
+    assert!(store
+        .lines(
+            Bounds::new(Coord { x: 0., y: 10. }, Coord { x: 10., y: 20. }),
+            0.
+        )
+        .is_empty());
+}
+
+#[test]
+fn simplifies_coarse_tiles() {
+    let store = VectorStore::new([equator()]);
+
+    let coarse = store.tile(0, 0, 0).unwrap();
+    let points = coarse.iter().map(|line| line.0.len()).sum::<usize>();
+
+    // A straight line needs only its ends, once per indexed piece
+    assert!(points < 20, "{points} points");
+    assert_within(&coarse, tile_bounds(0, 0, 0).unwrap());
+}
+
+#[test]
+fn tile_layout() {
+    assert_eq!(
+        tile_bounds(0, 0, 0).unwrap(),
+        Bounds::new(Coord { x: -180., y: -90. }, Coord { x: 180., y: 90. })
+    );
+
+    assert_eq!(
+        tile_bounds(3, 1, 2).unwrap(),
+        Bounds::new(Coord { x: 90., y: 0. }, Coord { x: 180., y: 45. })
+    );
+}
+
+#[test]
+fn refuses_levels_past_the_deepest() {
+    let store = VectorStore::new([equator()]);
+
+    assert!(store.tile(0, 0, vector::MAX_LEVEL).is_some());
+    assert!(store.tile(0, 0, vector::MAX_LEVEL + 1).is_none());
+    assert!(store.tile(0, 0, 64).is_none());
+    assert!(tile_bounds(0, 0, usize::MAX).is_none());
+}
+
+#[test]
+fn file_round_trip() {
+    let path = temp_path("vector").with_extension("vec");
+
+    let store = VectorStore::new([equator()]);
+    store.write_to_file(&path).unwrap();
+
+    let read = VectorStore::open(&path).unwrap();
+    assert_eq!(read.tile(1, 1, 2), store.tile(1, 1, 2));
+}
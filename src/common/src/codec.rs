@@ -0,0 +1,170 @@
+use bytemuck::Pod;
+use serde::{Deserialize, Serialize};
+
+/// How the values of a compressed tile are encoded. Every codec works on
+/// the values of a tile laid out row after row.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Codec {
+    /// Tiles are stored uncompressed.
+    #[default]
+    None = 0,
+    /// LZ4 block compression of the raw values.
+    Lz4 = 1,
+    /// Splits the values into byte planes and stores every byte as the
+    /// difference to the previous byte of its plane before compressing
+    /// with LZ4. For RGBA this is PNG's `Sub` filter on every channel,
+    /// for `f32` it keeps the slowly changing sign and exponent bytes
+    /// together.
+    ShuffleDeltaLz4 = 2,
+}
+
+impl TryFrom<u8> for Codec {
+    type Error = u8;
+
+    fn try_from(tag: u8) -> Result<Self, Self::Error> {
+        match tag {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Lz4),
+            2 => Ok(Self::ShuffleDeltaLz4),
+            _ => Err(tag),
+        }
+    }
+}
+
+impl Codec {
+    pub fn encode<T>(self, values: &[T]) -> Vec<u8>
+    where
+        T: Pod,
+    {
+        let bytes = bytemuck::cast_slice(values);
+
+        match self {
+            Self::None => bytes.to_vec(),
+            Self::Lz4 => lz4_flex::block::compress(bytes),
+            Self::ShuffleDeltaLz4 => {
+                lz4_flex::block::compress(&shuffle_delta(bytes, std::mem::size_of::<T>()))
+            }
+        }
+    }
+
+    /// Whether `data` can decode to `len` values. LZ4 writes at least one
+    /// byte for every 255 bytes of a match, so it cannot expand further.
+    pub fn fits<T>(self, data: &[u8], len: usize) -> bool {
+        let Some(size) = len.checked_mul(std::mem::size_of::<T>()) else {
+            return false;
+        };
+
+        match self {
+            Self::None => size == data.len(),
+            Self::Lz4 | Self::ShuffleDeltaLz4 => size / 255 <= data.len(),
+        }
+    }
+
+    /// Decodes `len` values encoded by [`Codec::encode`], failing if `data`
+    /// is damaged or does not hold exactly `len` values.
+    pub fn decode<T>(self, data: &[u8], len: usize) -> Result<Vec<T>, DecodeError>
+    where
+        T: Pod,
+    {
+        let mut values = vec![T::zeroed(); len];
+        let out = bytemuck::cast_slice_mut::<_, u8>(&mut values);
+        let expected = out.len();
+
+        let actual = match self {
+            Self::None => {
+                if data.len() == expected {
+                    out.copy_from_slice(data);
+                }
+
+                data.len()
+            }
+            Self::Lz4 => lz4_flex::block::decompress_into(data, out)?,
+            Self::ShuffleDeltaLz4 => {
+                let filtered = lz4_flex::block::decompress(data, expected)?;
+
+                if filtered.len() == expected {
+                    undo_shuffle_delta(&filtered, out, std::mem::size_of::<T>());
+                }
+
+                filtered.len()
+            }
+        };
+
+        if actual != expected {
+            return Err(DecodeError::Length { expected, actual });
+        }
+
+        Ok(values)
+    }
+}
+
+/// Why the values of a tile could not be decoded, as when it was cut short
+/// in transfer.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The values took up `actual` bytes instead of `expected`.
+    Length {
+        expected: usize,
+        actual: usize,
+    },
+    Lz4(lz4_flex::block::DecompressError),
+    /// The tile claims to be `width` by `height` values, more than any tile
+    /// or its data holds.
+    Size {
+        width: u32,
+        height: u32,
+    },
+}
+
+impl From<lz4_flex::block::DecompressError> for DecodeError {
+    fn from(error: lz4_flex::block::DecompressError) -> Self {
+        Self::Lz4(error)
+    }
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Length { expected, actual } => {
+                write!(f, "tile has {actual} bytes of values instead of {expected}")
+            }
+            Self::Lz4(error) => write!(f, "damaged LZ4 block: {error}"),
+            Self::Size { width, height } => {
+                write!(f, "tile of {width} by {height} values cannot be stored")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+fn shuffle_delta(bytes: &[u8], size: usize) -> Vec<u8> {
+    let count = bytes.len() / size;
+    let mut out = vec![0; bytes.len()];
+
+    for (plane, out) in out.chunks_exact_mut(count.max(1)).enumerate().take(size) {
+        let mut previous = 0_u8;
+
+        for (i, out) in out.iter_mut().enumerate() {
+            let byte = bytes[i * size + plane];
+            *out = byte.wrapping_sub(previous);
+            previous = byte;
+        }
+    }
+
+    out
+}
+
+fn undo_shuffle_delta(filtered: &[u8], out: &mut [u8], size: usize) {
+    let count = out.len() / size;
+
+    for (plane, filtered) in filtered.chunks_exact(count.max(1)).enumerate().take(size) {
+        let mut previous = 0_u8;
+
+        for (i, delta) in filtered.iter().enumerate() {
+            previous = previous.wrapping_add(*delta);
+            out[i * size + plane] = previous;
+        }
+    }
+}
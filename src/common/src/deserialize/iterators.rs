@@ -2,7 +2,10 @@ use crate::{deserialize::reader::Reader, Bounds};
 use bytemuck::Pod;
 use geo::{Contains, Intersects};
 
-use super::tree::{Pointer, TileData};
+use super::{
+    tree::{Pointer, TileData},
+    Error,
+};
 
 pub struct ContainsIterator<'a, DataType, AggregateType, Query> {
     query: Query,
@@ -33,11 +36,18 @@ where
     AggregateType: Pod,
     Query: Contains<Bounds> + Intersects<Bounds>,
 {
-    type Item = TileData<'a, DataType, AggregateType>;
+    type Item = Result<TileData<'a, DataType, AggregateType>, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
         while let Some(current) = self.queue.pop() {
-            let node = self.reader.load(&current);
+            let node = match self.reader.load(&current) {
+                Ok(node) => node,
+                Err(err) => {
+                    // Nothing after a damaged node can be trusted
+                    self.queue.clear();
+                    return Some(Err(err));
+                }
+            };
 
             if self.query.contains(&node.bounds) {
                 return Some(self.reader.read());
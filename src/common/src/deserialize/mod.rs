@@ -0,0 +1,305 @@
+use bytemuck::Pod;
+use geo::{Coord, CoordNum};
+use serde::Serialize;
+use tree::{Pointer, StoredTile, TileData, TileNode};
+
+use crate::{
+    codec::DecodeError,
+    format::{Le64, TileEncoding},
+    Bounds, MAX_TILE_SIZE,
+};
+
+mod iterators;
+pub mod reader;
+pub mod remote;
+pub mod tree;
+
+pub use tree::GeoTree;
+
+/// Why a tile stored in a database could not be read.
+#[derive(Debug)]
+pub enum Error {
+    /// The compressed tile is not in the wire format.
+    Wire(bincode::Error),
+    /// The values of the compressed tile are damaged.
+    Decode(DecodeError),
+    /// A value at `position` reaches past the end of the data, as in a
+    /// truncated file.
+    OutOfBounds { position: usize },
+    /// A value at `position` cannot be what the format stores there.
+    Invalid { position: usize },
+}
+
+impl From<bincode::Error> for Error {
+    fn from(error: bincode::Error) -> Self {
+        Self::Wire(error)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(error: DecodeError) -> Self {
+        Self::Decode(error)
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(error: Error) -> Self {
+        Self::new(std::io::ErrorKind::InvalidData, error)
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Wire(error) => write!(f, "invalid compressed tile: {error}"),
+            Self::Decode(error) => write!(f, "{error}"),
+            Self::OutOfBounds { position } => {
+                write!(
+                    f,
+                    "value at byte {position} reaches past the end of the data"
+                )
+            }
+            Self::Invalid { position } => write!(f, "invalid value at byte {position}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Clone, Copy)]
+pub struct AlignedReader<'a> {
+    inner: &'a [u8],
+    position: usize,
+}
+
+impl<'a> AlignedReader<'a> {
+    pub fn new(inner: &'a [u8]) -> Self {
+        Self { inner, position: 0 }
+    }
+
+    /// Reads from `position` onwards, aligning relative to the start of
+    /// `inner` just like the writer does.
+    pub fn at(inner: &'a [u8], position: usize) -> Self {
+        Self { inner, position }
+    }
+
+    fn read<T>(&mut self) -> Result<&'a T, Error>
+    where
+        T: Pod,
+    {
+        let position = self.position;
+        let bytes = self.take::<T>(Some(std::mem::size_of::<T>()))?;
+
+        // Only misaligned if `inner` itself is
+        bytemuck::try_from_bytes(bytes).map_err(|_| Error::Invalid { position })
+    }
+
+    fn read_slice<T>(&mut self, len: usize) -> Result<&'a [T], Error>
+    where
+        T: Pod,
+    {
+        let position = self.position;
+        let bytes = self.take::<T>(std::mem::size_of::<T>().checked_mul(len))?;
+
+        bytemuck::try_cast_slice(bytes).map_err(|_| Error::Invalid { position })
+    }
+
+    /// Takes the next `size` bytes after aligning them for `T`, failing if
+    /// they reach past the end of `inner`.
+    fn take<T>(&mut self, size: Option<usize>) -> Result<&'a [u8], Error> {
+        let position = self.position;
+        let start = position.saturating_add(self.padding::<T>());
+
+        let bytes = size
+            .and_then(|size| self.inner.get(start..start.checked_add(size)?))
+            .ok_or(Error::OutOfBounds { position })?;
+
+        self.position = start + bytes.len();
+
+        Ok(bytes)
+    }
+
+    pub fn padding<T>(&self) -> usize {
+        let alignment = std::mem::align_of::<T>();
+        let remainder = self.position % alignment;
+
+        if remainder == 0 {
+            0
+        } else {
+            alignment - remainder
+        }
+    }
+}
+
+/// Reads a value as written by the backend, failing instead of reading
+/// past the end of the data.
+pub trait Deserialize<'de>: Sized {
+    fn deserialize(bytes: &mut AlignedReader<'de>) -> Result<Self, Error>;
+}
+
+impl<'a, T, U> Deserialize<'a> for TileData<'a, T, U>
+where
+    T: Pod,
+    U: Pod,
+{
+    fn deserialize(reader: &mut AlignedReader<'a>) -> Result<Self, Error> {
+        let aggregate = Deserialize::deserialize(reader)?;
+        let (encoding, tile) = deserialize_tile(reader)?;
+
+        Ok(Self {
+            aggregate,
+            tile,
+            encoding,
+        })
+    }
+}
+
+/// Reads a tile written by the deduplicating serializer, expanding
+/// constant and referenced tiles. Compressed tiles are left as they are.
+fn deserialize_tile<'a, T>(
+    reader: &mut AlignedReader<'a>,
+) -> Result<(TileEncoding, Option<StoredTile<'a, T>>), Error>
+where
+    T: Pod,
+{
+    let start = reader.position;
+    let encoding = TileEncoding::try_from(*reader.read::<u8>()?)
+        .map_err(|_| Error::Invalid { position: start })?;
+
+    let tile = match encoding {
+        TileEncoding::Empty => None,
+        TileEncoding::Inline => Some(StoredTile::Rows(Deserialize::deserialize(reader)?)),
+        TileEncoding::Constant => {
+            let position = reader.position;
+            let height = read_len(reader)?;
+            let row = <&[T]>::deserialize(reader)?;
+
+            if height > MAX_TILE_SIZE {
+                return Err(Error::Invalid { position });
+            }
+
+            Some(StoredTile::Rows(vec![row; height]))
+        }
+        TileEncoding::Compressed => Some(StoredTile::Compressed(Deserialize::deserialize(reader)?)),
+        TileEncoding::Reference => {
+            let position = read_len(reader)?;
+
+            // Always an earlier tile, so references cannot loop
+            if position >= start {
+                return Err(Error::Invalid { position: start });
+            }
+
+            deserialize_tile(&mut AlignedReader::at(reader.inner, position))?.1
+        }
+    };
+
+    Ok((encoding, tile))
+}
+
+/// Reads a length or position, which cannot be past the end of the data.
+fn read_len(reader: &mut AlignedReader<'_>) -> Result<usize, Error> {
+    let position = reader.position;
+    let len = u64::from(*reader.read::<Le64>()?);
+
+    usize::try_from(len)
+        .ok()
+        .filter(|&len| len <= reader.inner.len())
+        .ok_or(Error::OutOfBounds { position })
+}
+
+impl<'a, T> Deserialize<'a> for TileNode<'a, T>
+where
+    T: Pod,
+{
+    fn deserialize(reader: &mut AlignedReader<'a>) -> Result<Self, Error> {
+        let start = reader.position;
+        let bounds = Deserialize::deserialize(reader)?;
+        let children: Vec<&[Pointer<T>]> = Deserialize::deserialize(reader)?;
+
+        // Always written after their parent, so walks cannot loop
+        if children
+            .iter()
+            .copied()
+            .flatten()
+            .any(|child| u64::from(child.position) <= start as u64)
+        {
+            return Err(Error::Invalid { position: start });
+        }
+
+        Ok(Self { bounds, children })
+    }
+}
+
+#[derive(Serialize)]
+pub struct TileRef<'a, T> {
+    pub data: Option<Vec<&'a [T]>>,
+    pub bounds: Bounds,
+}
+
+impl<'a, T: Pod> Deserialize<'a> for &'a T {
+    fn deserialize(reader: &mut AlignedReader<'a>) -> Result<Self, Error> {
+        reader.read()
+    }
+}
+
+impl<T> Deserialize<'_> for Coord<T>
+where
+    T: CoordNum + Pod,
+{
+    fn deserialize(reader: &mut AlignedReader<'_>) -> Result<Self, Error> {
+        let x = *reader.read::<T>()?;
+        let y = *reader.read::<T>()?;
+
+        Ok(Self { x, y })
+    }
+}
+
+impl Deserialize<'_> for Bounds {
+    fn deserialize(reader: &mut AlignedReader<'_>) -> Result<Self, Error> {
+        let min = Coord::deserialize(reader)?;
+        let max = Coord::deserialize(reader)?;
+
+        Ok(Self::new(min, max))
+    }
+}
+
+impl<'a, T> Deserialize<'a> for &'a [T]
+where
+    T: Pod,
+{
+    fn deserialize(reader: &mut AlignedReader<'a>) -> Result<Self, Error> {
+        let len = read_len(reader)?;
+
+        reader.read_slice(len)
+    }
+}
+
+impl<'a, T> Deserialize<'a> for Vec<&'a [T]>
+where
+    T: Pod,
+{
+    fn deserialize(reader: &mut AlignedReader<'a>) -> Result<Self, Error> {
+        // Rows are only allocated as they are read, so a damaged height
+        // fails at the end of the data
+        let height = read_len(reader)?;
+
+        (0..height)
+            .map(|_| <&[T] as Deserialize>::deserialize(reader))
+            .collect()
+    }
+}
+
+impl<'a, T> Deserialize<'a> for Option<T>
+where
+    T: Deserialize<'a>,
+{
+    fn deserialize(reader: &mut AlignedReader<'a>) -> Result<Self, Error> {
+        let option = *reader.read::<u8>()?;
+
+        if option != 1 {
+            return Ok(None);
+        }
+
+        Ok(Some(Deserialize::deserialize(reader)?))
+    }
+}
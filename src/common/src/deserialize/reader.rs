@@ -0,0 +1,43 @@
+use bytemuck::Pod;
+
+use super::{
+    tree::{Pointer, TileNode},
+    AlignedReader, Deserialize, Error,
+};
+
+pub struct Reader<'a> {
+    pub data: &'a [u8],
+    pub position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub fn read<T>(&mut self) -> Result<T, Error>
+    where
+        T: Deserialize<'a>,
+    {
+        let mut reader = AlignedReader::at(self.data, self.position);
+        let out = T::deserialize(&mut reader)?;
+
+        self.position = reader.position;
+
+        Ok(out)
+    }
+
+    pub fn load<T>(&mut self, pointer: &Pointer<T>) -> Result<TileNode<'a, T>, Error>
+    where
+        T: Pod,
+    {
+        let position = usize::try_from(u64::from(pointer.position)).unwrap_or(usize::MAX);
+
+        let mut reader = AlignedReader::at(self.data, position);
+        let out = Deserialize::deserialize(&mut reader)?;
+
+        self.position = reader.position;
+
+        Ok(out)
+    }
+}
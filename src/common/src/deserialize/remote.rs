@@ -0,0 +1,149 @@
+use std::{borrow::Cow, ops::Range};
+
+use bytemuck::Pod;
+
+use crate::{
+    format::{IndexEntry, IndexFooter, IndexLevel, Le64},
+    TileResponse,
+};
+
+use super::{deserialize_tile, AlignedReader, Error};
+
+/// Reads a database piece by piece, such as one hosted on a static file
+/// server and fetched with HTTP range requests. It only tells which bytes
+/// it needs, fetching them is up to the caller:
+///
+/// 1. the last [`RemoteIndex::FOOTER_SIZE`] bytes of the file,
+/// 2. the table of levels at [`RemoteIndex::table_range`],
+/// 3. the entries of a block of tiles of a row at
+///    [`RemoteIndex::block_range`], which neighbouring tiles share,
+/// 4. the tile itself at [`IndexEntry::tile_range`].
+///
+/// Only files with an index can be read this way.
+#[derive(Debug)]
+pub struct RemoteIndex {
+    levels: Vec<IndexLevel>,
+}
+
+impl RemoteIndex {
+    pub const FOOTER_SIZE: u64 = std::mem::size_of::<IndexFooter>() as u64;
+
+    /// Tiles of a row whose entries are fetched together.
+    pub const BLOCK: usize = 16;
+
+    /// Where the table of levels is stored, `None` if the file ends without
+    /// an index, or an error if the footer is damaged.
+    pub fn table_range(footer: &[u8]) -> Result<Option<Range<u64>>, Error> {
+        let Ok(footer) = bytemuck::try_pod_read_unaligned::<IndexFooter>(footer) else {
+            return Ok(None);
+        };
+
+        if footer.magic != IndexFooter::MAGIC {
+            return Ok(None);
+        }
+
+        let start = u64::from(footer.offset);
+        let range = u64::from(footer.levels)
+            .checked_mul(std::mem::size_of::<IndexLevel>() as u64)
+            .and_then(|size| Some(start..start.checked_add(size)?));
+
+        range.map(Some).ok_or(Error::Invalid { position: 0 })
+    }
+
+    pub fn new(table: &[u8]) -> Self {
+        let levels = table
+            .chunks_exact(std::mem::size_of::<IndexLevel>())
+            .map(bytemuck::pod_read_unaligned)
+            .collect();
+
+        Self { levels }
+    }
+
+    pub fn levels(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Where the entries of the block of tiles holding `x` in row `y` of
+    /// level `z` are stored: [`RemoteIndex::BLOCK`] tiles starting at a
+    /// multiple of it, or fewer at the end of the row. Returns `None` if
+    /// there is no such tile.
+    pub fn block_range(&self, x: usize, y: usize, z: usize) -> Option<Range<u64>> {
+        let level = self.levels.get(z)?;
+        let width = u64::from(level.width);
+
+        let (x, y) = (x as u64, y as u64);
+
+        if x >= width || y >= u64::from(level.height) {
+            return None;
+        }
+
+        let first = x - x % Self::BLOCK as u64;
+        let count = (width - first).min(Self::BLOCK as u64);
+
+        let size = std::mem::size_of::<IndexEntry>() as u64;
+        let start = y
+            .checked_mul(width)?
+            .checked_add(first)?
+            .checked_mul(size)?
+            .checked_add(level.offset.into())?;
+
+        Some(start..start.checked_add(count * size)?)
+    }
+
+    /// Parses the entry of the tile at `x`, `y` and `z` from the bytes at
+    /// [`RemoteIndex::block_range`]. Returns `None` if there is no tile at
+    /// the address, and an error if the bytes are cut short.
+    pub fn entry(
+        &self,
+        block: &[u8],
+        x: usize,
+        y: usize,
+        z: usize,
+    ) -> Result<Option<IndexEntry>, Error> {
+        if self.block_range(x, y, z).is_none() {
+            return Ok(None);
+        }
+
+        let size = std::mem::size_of::<IndexEntry>();
+        let start = x % Self::BLOCK * size;
+
+        let bytes = block
+            .get(start..start + size)
+            .ok_or(Error::OutOfBounds { position: start })?;
+        let entry = bytemuck::pod_read_unaligned::<IndexEntry>(bytes);
+
+        Ok((entry.data != Le64::default()).then_some(entry))
+    }
+
+    /// Reads the tile of `entry` from the bytes at [`IndexEntry::tile_range`],
+    /// returning an error if they are cut short or damaged.
+    pub fn tile<T>(entry: &IndexEntry, bytes: &[u8]) -> Result<Option<TileResponse<T>>, Error>
+    where
+        T: Pod,
+    {
+        let range = entry.tile_range();
+
+        if (bytes.len() as u64) < range.end - range.start {
+            return Err(Error::OutOfBounds {
+                position: bytes.len(),
+            });
+        }
+
+        // Copied, as the values are read in place and need their alignment
+        let mut aligned = vec![0_u64; bytes.len().div_ceil(8)];
+        bytemuck::cast_slice_mut::<_, u8>(&mut aligned)[..bytes.len()].copy_from_slice(bytes);
+        let bytes = &bytemuck::cast_slice::<_, u8>(&aligned)[..bytes.len()];
+
+        let offset = u64::from(entry.tile) - range.start;
+        let (_, tile) = deserialize_tile::<T>(&mut AlignedReader::at(bytes, offset as usize))?;
+
+        let Some(tile) = tile else {
+            return Ok(None);
+        };
+
+        Ok(Some(TileResponse {
+            data: tile.decode()?.into_iter().map(Cow::into_owned).collect(),
+            bounds: entry.bounds(),
+        }))
+    }
+}
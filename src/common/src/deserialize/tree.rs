@@ -0,0 +1,659 @@
+use std::{borrow::Cow, collections::VecDeque, fmt::Display};
+
+use bytemuck::{Pod, Zeroable};
+use bytes::Bytes;
+use geo::{Contains, Intersects};
+
+use crate::{
+    deserialize::{reader::Reader, Error},
+    format::{IndexEntry, IndexFooter, IndexLevel, Le64, TileEncoding},
+    Bounds, CompressedTileResponse, Layer, TileRefResponse,
+};
+
+use super::iterators::ContainsIterator;
+
+pub struct GeoTree<D>
+where
+    D: Layer,
+{
+    /// The mapped file, or an aligned copy of a database held in memory.
+    data: Bytes,
+    index: Option<IndexFooter>,
+    _dataset: std::marker::PhantomData<fn() -> D>,
+}
+
+impl<D> std::fmt::Debug for GeoTree<D>
+where
+    D: Layer,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GeoTree")
+            .field("size", &self.data.len())
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+impl<D> GeoTree<D>
+where
+    D: Layer,
+{
+    /// Maps the database at `path`. Not available in the browser, where
+    /// databases are read with [`GeoTree::from_source`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new<P>(path: P) -> std::io::Result<Self>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let file = std::fs::File::open(path)?;
+        let data = unsafe { memmap2::Mmap::map(&file)? };
+
+        Ok(Self::with_data(Bytes::from_owner(data)))
+    }
+
+    /// Reads a database from any bytes, such as a `Vec<u8>` written by the
+    /// backend or copied from an `ArrayBuffer`, [`Bytes`] or a
+    /// `&'static [u8]` embedded with `include_bytes!`.
+    pub fn from_source<S>(source: S) -> Self
+    where
+        S: AsRef<[u8]> + Send + 'static,
+    {
+        let data = Bytes::from_owner(source);
+
+        // Values are read in place, so they need the alignment a mapped
+        // file would have. Only misaligned sources are copied.
+        if (data.as_ptr() as usize).is_multiple_of(std::mem::align_of::<u64>()) {
+            return Self::with_data(data);
+        }
+
+        let mut aligned = vec![0_u64; data.len().div_ceil(8)];
+        bytemuck::cast_slice_mut::<_, u8>(&mut aligned)[..data.len()].copy_from_slice(&data);
+
+        Self::with_data(Bytes::from_owner(Aligned(aligned)).slice(..data.len()))
+    }
+
+    fn with_data(data: Bytes) -> Self {
+        // Older files end with the last node instead of an index. Indices
+        // reaching past the end of the file are ignored the same way.
+        let index = data
+            .len()
+            .checked_sub(std::mem::size_of::<IndexFooter>())
+            .and_then(|start| {
+                let footer = bytemuck::pod_read_unaligned::<IndexFooter>(&data[start..]);
+
+                (footer.magic == IndexFooter::MAGIC && index_fits(&data[..start], &footer))
+                    .then_some(footer)
+            });
+
+        Self {
+            data,
+            index,
+            _dataset: Default::default(),
+        }
+    }
+
+    pub fn reader(&self) -> Reader<'_> {
+        Reader::new(&self.data)
+    }
+
+    fn levels(&self) -> &[IndexLevel] {
+        let Some(footer) = self.index else {
+            return &[];
+        };
+
+        let offset = footer.offset.get();
+        let size = footer.levels.get() * std::mem::size_of::<IndexLevel>();
+
+        bytemuck::cast_slice(&self.data[offset..offset + size])
+    }
+
+    /// Looks up a tile in the index, without touching the tree.
+    fn entry(&self, x: usize, y: usize, z: usize) -> Option<&IndexEntry> {
+        let level = self.levels().get(z)?;
+
+        let width = level.width.get();
+
+        if x >= width || y >= level.height.get() {
+            return None;
+        }
+
+        let size = std::mem::size_of::<IndexEntry>();
+        let position = level.offset.get() + (y * width + x) * size;
+
+        let entry: &IndexEntry = bytemuck::from_bytes(&self.data[position..position + size]);
+
+        // Entries pointing past the end of the file are taken as missing
+        let len = self.data.len() as u64;
+        let tile_end = u64::from(entry.tile).checked_add(u64::from(entry.tile_size));
+
+        let inside = u64::from(entry.data) < len && tile_end.is_some_and(|end| end <= len);
+
+        (entry.data != Le64::default() && inside).then_some(entry)
+    }
+
+    pub fn get_tiles(
+        &self,
+        area: Bounds,
+        level: u32,
+    ) -> Result<Vec<TileRefResponse<'_, D::Type>>, Error>
+    where
+        D::Type: Pod,
+        D::AggregateType: Pod,
+    {
+        if self.index.is_some() {
+            return self.get_indexed_tiles(area, level as usize);
+        }
+
+        let mut reader = Reader::new(&self.data);
+
+        fn inner<'a, T, U>(
+            level: u32,
+            current_level: u32,
+            pointer: &Pointer<T>,
+            area: Bounds,
+            reader: &mut Reader<'a>,
+        ) -> Result<Vec<TileRefResponse<'a, T>>, Error>
+        where
+            T: Pod,
+            U: Pod,
+        {
+            let node = reader.load(pointer)?;
+
+            if !node.bounds.intersects(&area) {
+                return Ok(Vec::new());
+            }
+
+            if current_level == level {
+                let data = reader.read::<TileData<T, U>>()?;
+
+                return data
+                    .tile
+                    .into_iter()
+                    .map(|tile| {
+                        Ok(TileRefResponse {
+                            bounds: node.bounds,
+                            data: tile.decode()?,
+                        })
+                    })
+                    .collect();
+            }
+
+            let mut tiles = Vec::new();
+
+            for child in node.children.iter().copied().flatten() {
+                tiles.extend(inner::<_, U>(
+                    level,
+                    current_level + 1,
+                    child,
+                    area,
+                    reader,
+                )?);
+            }
+
+            Ok(tiles)
+        }
+
+        inner::<_, D::AggregateType>(level, 0, &Pointer::default(), area, &mut reader)
+    }
+
+    /// Reads the tiles of `level` intersecting `area` straight from the
+    /// index. Only the rows and columns the area spans are visited.
+    fn get_indexed_tiles(
+        &self,
+        area: Bounds,
+        level: usize,
+    ) -> Result<Vec<TileRefResponse<'_, D::Type>>, Error>
+    where
+        D::Type: Pod,
+        D::AggregateType: Pod,
+    {
+        let (Some(index), Some(root)) = (self.levels().get(level), self.entry(0, 0, 0)) else {
+            return Ok(Vec::new());
+        };
+
+        let root = root.bounds();
+        let (width, height) = (index.width.get(), index.height.get());
+
+        let tile_width = root.width() / width as f32;
+        let tile_height = root.height() / height as f32;
+
+        let column = |x: f32| {
+            ((x - root.min().x) / tile_width)
+                .floor()
+                .clamp(0., (width - 1) as f32) as usize
+        };
+        let row = |y: f32| {
+            ((root.max().y - y) / tile_height)
+                .floor()
+                .clamp(0., (height - 1) as f32) as usize
+        };
+
+        // Widened by one, as tiles only touching the area intersect it too
+        let columns =
+            column(area.min().x).saturating_sub(1)..=(column(area.max().x) + 1).min(width - 1);
+        let rows = row(area.max().y).saturating_sub(1)..=(row(area.min().y) + 1).min(height - 1);
+
+        rows.flat_map(|y| columns.clone().map(move |x| (x, y)))
+            .filter_map(|(x, y)| self.entry(x, y, level))
+            .filter(|entry| entry.bounds().intersects(&area))
+            .map(|entry| {
+                let data = Reader {
+                    data: &self.data,
+                    position: entry.data.get(),
+                }
+                .read::<TileData<D::Type, D::AggregateType>>()?;
+
+                let Some(tile) = data.tile else {
+                    return Ok(None);
+                };
+
+                Ok(Some(TileRefResponse {
+                    bounds: entry.bounds(),
+                    data: tile.decode()?,
+                }))
+            })
+            .filter_map(Result::transpose)
+            .collect()
+    }
+
+    /// Reads and decodes a tile, returning `None` if there is none at `x`,
+    /// `y` and `z`, and an error if it is damaged.
+    pub fn get_tile(
+        &self,
+        x: usize,
+        y: usize,
+        z: usize,
+    ) -> Result<Option<TileRefResponse<'_, D::Type>>, Error>
+    where
+        D::Type: Pod,
+        D::AggregateType: Pod,
+    {
+        let Some((
+            bounds,
+            TileData {
+                tile: Some(tile), ..
+            },
+        )) = self.data(x, y, z)?
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(TileRefResponse {
+            data: tile.decode()?,
+            bounds,
+        }))
+    }
+
+    /// Returns the tile as it is stored, if it is stored compressed.
+    pub fn get_compressed_tile(
+        &self,
+        x: usize,
+        y: usize,
+        z: usize,
+    ) -> Result<Option<CompressedTileResponse<'_>>, Error>
+    where
+        D::Type: Pod,
+        D::AggregateType: Pod,
+    {
+        match self.data(x, y, z)? {
+            // Tiles referencing an identical tile elsewhere keep their own bounds
+            Some((
+                bounds,
+                TileData {
+                    tile: Some(StoredTile::Compressed(wire)),
+                    ..
+                },
+            )) => Ok(Some(CompressedTileResponse {
+                bounds,
+                ..StoredTile::<D::Type>::response(wire)?
+            })),
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the tile as it is stored, already serialized as a
+    /// [`CompressedTileResponse`], as a view into the database. Only tiles
+    /// stored compressed at their own position are available this way.
+    pub fn get_tile_bytes(&self, x: usize, y: usize, z: usize) -> Result<Option<Bytes>, Error>
+    where
+        D::Type: Pod,
+        D::AggregateType: Pod,
+    {
+        let Some((
+            _,
+            TileData {
+                encoding: TileEncoding::Compressed,
+                tile: Some(StoredTile::Compressed(wire)),
+                ..
+            },
+        )) = self.data(x, y, z)?
+        else {
+            return Ok(None);
+        };
+
+        let start = wire.as_ptr() as usize - self.data.as_ptr() as usize;
+
+        Ok(Some(self.data.slice(start..start + wire.len())))
+    }
+
+    /// Reads the aggregate and tile of the node at `x`, `y` and `z`, along
+    /// with its bounds.
+    fn data(
+        &self,
+        x: usize,
+        y: usize,
+        z: usize,
+    ) -> Result<Located<'_, D::Type, D::AggregateType>, Error>
+    where
+        D::Type: Pod,
+        D::AggregateType: Pod,
+    {
+        let Some((bounds, mut reader)) = self.find(x, y, z)? else {
+            return Ok(None);
+        };
+
+        Ok(Some((bounds, reader.read()?)))
+    }
+
+    /// Finds the node at `x`, `y` and `z`, returning its bounds and a
+    /// reader positioned at its data.
+    fn find(&self, x: usize, y: usize, z: usize) -> Result<Option<(Bounds, Reader<'_>)>, Error>
+    where
+        D::Type: Pod,
+    {
+        if self.index.is_some() {
+            let Some(entry) = self.entry(x, y, z) else {
+                return Ok(None);
+            };
+
+            let reader = Reader {
+                data: &self.data,
+                position: entry.data.get(),
+            };
+
+            return Ok(Some((entry.bounds(), reader)));
+        }
+
+        let max = u32::try_from(z)
+            .ok()
+            .and_then(|z| D::CHILDREN_PER_AXIS.checked_pow(z));
+
+        if !max.is_some_and(|max| x < max && y < max) {
+            return Ok(None);
+        }
+
+        let mut reader = Reader::new(&self.data);
+
+        let mut current = reader.read::<TileNode<D::Type>>()?;
+
+        for level in 1..=z {
+            // Digit of `x` and `y` in base `CHILDREN_PER_AXIS` for this level
+            let divisor = D::CHILDREN_PER_AXIS.pow((z - level) as u32);
+
+            let row = (y / divisor) % D::CHILDREN_PER_AXIS;
+            let col = (x / divisor) % D::CHILDREN_PER_AXIS;
+
+            if let Some(child) = current.children.get(row).and_then(|row| row.get(col)) {
+                current = reader.load(child)?;
+            } else {
+                return Ok(None);
+            }
+        }
+
+        Ok(Some((current.bounds, reader)))
+    }
+
+    pub fn get_tile_aggregate(
+        &self,
+        x: usize,
+        y: usize,
+        z: usize,
+    ) -> Result<Option<D::AggregateType>, Error>
+    where
+        D::Type: Pod,
+        D::AggregateType: Pod,
+    {
+        Ok(self
+            .data(x, y, z)?
+            .and_then(|(_, data)| data.aggregate.copied()))
+    }
+
+    /// Walks the whole tree, summarizing its shape and how much space
+    /// tile deduplication and compression saved.
+    pub fn info(&self) -> Result<TreeInfo, Error>
+    where
+        D::Type: Pod,
+        D::AggregateType: Pod,
+    {
+        let mut reader = Reader::new(&self.data);
+        let mut info = TreeInfo {
+            size: self.data.len(),
+            indexed: self.index.is_some(),
+            ..Default::default()
+        };
+
+        let mut queue = VecDeque::from([(Pointer::default(), 0)]);
+
+        while let Some((pointer, level)) = queue.pop_front() {
+            let node = reader.load::<D::Type>(&pointer)?;
+            let data = reader.read::<TileData<D::Type, D::AggregateType>>()?;
+
+            info.nodes += 1;
+            info.levels = info.levels.max(level + 1);
+
+            if let Some(tile) = data.tile {
+                let (width, height) = tile.size()?;
+
+                // Sizes of damaged compressed tiles may not fit a `usize`
+                let row = width
+                    .saturating_mul(std::mem::size_of::<D::Type>())
+                    .saturating_add(std::mem::size_of::<Le64>());
+                let inline = height
+                    .saturating_mul(row)
+                    .saturating_add(std::mem::size_of::<Le64>());
+
+                let saved = match (data.encoding, tile) {
+                    (TileEncoding::Constant, _) => {
+                        info.constant_tiles += 1;
+                        inline.saturating_sub(std::mem::size_of::<Le64>() + row)
+                    }
+                    (TileEncoding::Reference, _) => {
+                        info.duplicate_tiles += 1;
+                        inline - std::mem::size_of::<Le64>()
+                    }
+                    (TileEncoding::Compressed, StoredTile::Compressed(wire)) => {
+                        info.compressed_tiles += 1;
+                        inline.saturating_sub(std::mem::size_of::<Le64>() + wire.len())
+                    }
+                    _ => {
+                        info.inline_tiles += 1;
+                        0
+                    }
+                };
+
+                info.bytes_saved = info.bytes_saved.saturating_add(saved);
+            }
+
+            for &child in node.children.iter().copied().flatten() {
+                queue.push_back((child, level + 1));
+            }
+        }
+
+        Ok(info)
+    }
+
+    pub fn get_aggregate<Query>(&self, query: Query) -> Result<Option<D::AggregateType>, Error>
+    where
+        D::Type: Pod,
+        D::AggregateType: Pod,
+        Query: Contains<Bounds> + Intersects<Bounds>,
+    {
+        let reader = Reader::new(&self.data);
+
+        let mut iter = ContainsIterator::<D::Type, D::AggregateType, Query>::new(reader, query);
+
+        iter.try_fold(None, |acc, data| {
+            Ok(match (acc, data?.aggregate) {
+                (Some(acc), Some(&aggregate)) => D::aggregate2(&[acc, aggregate]),
+                (Some(acc), None) => Some(acc),
+                (None, Some(&aggregate)) => Some(aggregate),
+                _ => None,
+            })
+        })
+    }
+}
+
+/// The data of a node along with its bounds, if there is a node.
+type Located<'a, T, U> = Option<(Bounds, TileData<'a, T, U>)>;
+
+#[derive(Debug, Default)]
+pub struct TreeInfo {
+    pub size: usize,
+    pub indexed: bool,
+    pub nodes: usize,
+    pub levels: usize,
+
+    pub inline_tiles: usize,
+    pub constant_tiles: usize,
+    pub compressed_tiles: usize,
+    pub duplicate_tiles: usize,
+    pub bytes_saved: usize,
+}
+
+impl Display for TreeInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const MIB: f64 = 1024. * 1024.;
+
+        let saved = self.bytes_saved as f64;
+        let total = self.size as f64 + saved;
+
+        writeln!(f, "size:             {:.1} MiB", self.size as f64 / MIB)?;
+        writeln!(f, "indexed:          {}", self.indexed)?;
+        writeln!(f, "nodes:            {}", self.nodes)?;
+        writeln!(f, "levels:           {}", self.levels)?;
+        writeln!(f, "inline tiles:     {}", self.inline_tiles)?;
+        writeln!(f, "constant tiles:   {}", self.constant_tiles)?;
+        writeln!(f, "compressed tiles: {}", self.compressed_tiles)?;
+        writeln!(f, "duplicate tiles:  {}", self.duplicate_tiles)?;
+        write!(
+            f,
+            "space saved:      {:.1} MiB ({:.1}%)",
+            saved / MIB,
+            100. * saved / total.max(1.)
+        )
+    }
+}
+
+/// Whether the table of levels of `footer` and the entries of every level
+/// lie within `body`, the file up to the footer, at 8-byte boundaries, and
+/// no level is empty.
+fn index_fits(body: &[u8], footer: &IndexFooter) -> bool {
+    let fits = |offset: Le64, count: u64, size: usize| {
+        let offset = u64::from(offset);
+
+        offset.is_multiple_of(8)
+            && count
+                .checked_mul(size as u64)
+                .and_then(|size| offset.checked_add(size))
+                .is_some_and(|end| end <= body.len() as u64)
+    };
+
+    let size = std::mem::size_of::<IndexLevel>();
+
+    if !fits(footer.offset, footer.levels.into(), size) {
+        return false;
+    }
+
+    let offset = footer.offset.get();
+    let table: &[IndexLevel] =
+        bytemuck::cast_slice(&body[offset..offset + footer.levels.get() * size]);
+
+    table.iter().all(|level| {
+        u64::from(level.width)
+            .checked_mul(level.height.into())
+            .filter(|&count| count > 0)
+            .is_some_and(|count| fits(level.offset, count, std::mem::size_of::<IndexEntry>()))
+    })
+}
+
+struct Aligned(Vec<u64>);
+
+impl AsRef<[u8]> for Aligned {
+    fn as_ref(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.0)
+    }
+}
+
+#[repr(transparent)]
+#[derive(Clone, Copy, Pod, Zeroable, Debug)]
+pub struct Pointer<T> {
+    pub position: Le64,
+    _type: std::marker::PhantomData<T>,
+}
+
+impl<T> Default for Pointer<T> {
+    fn default() -> Self {
+        Self {
+            position: Le64::default(),
+            _type: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TileNode<'a, T> {
+    pub bounds: Bounds,
+    pub children: Vec<&'a [Pointer<T>]>,
+}
+
+#[derive(Debug)]
+pub struct TileData<'a, T, U> {
+    #[allow(dead_code)]
+    pub aggregate: Option<&'a U>,
+    pub tile: Option<StoredTile<'a, T>>,
+    pub encoding: TileEncoding,
+}
+
+/// A tile borrowed from the database. Compressed tiles are only decoded
+/// when their values are needed.
+#[derive(Clone, Debug)]
+pub enum StoredTile<'a, T> {
+    Rows(Vec<&'a [T]>),
+    /// A [`CompressedTileResponse`] in the wire format.
+    Compressed(&'a [u8]),
+}
+
+impl<'a, T> StoredTile<'a, T>
+where
+    T: Pod,
+{
+    /// Parses a compressed tile, borrowing its data.
+    fn response(wire: &'a [u8]) -> Result<CompressedTileResponse<'a>, Error> {
+        Ok(bincode::deserialize(wire)?)
+    }
+
+    /// Width and height of the tile in values.
+    pub fn size(&self) -> Result<(usize, usize), Error> {
+        match self {
+            Self::Rows(rows) => Ok((rows.first().map_or(0, |row| row.len()), rows.len())),
+            Self::Compressed(wire) => {
+                let response = Self::response(wire)?;
+
+                Ok((response.width as usize, response.height as usize))
+            }
+        }
+    }
+
+    /// The rows of the tile, decoding them if they are compressed.
+    pub fn decode(self) -> Result<Vec<Cow<'a, [T]>>, Error> {
+        match self {
+            Self::Rows(rows) => Ok(rows.into_iter().map(Cow::Borrowed).collect()),
+            Self::Compressed(wire) => Ok(Self::response(wire)?
+                .decode::<T>()?
+                .data
+                .into_iter()
+                .map(Cow::Owned)
+                .collect()),
+        }
+    }
+}
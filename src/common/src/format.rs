@@ -0,0 +1,123 @@
+use std::ops::Range;
+
+use bytemuck::{Pod, Zeroable};
+use geo::Coord;
+
+use crate::Bounds;
+
+// Values are written and read in place as they are laid out in memory
+#[cfg(target_endian = "big")]
+compile_error!("databases are little-endian and can only be used on little-endian targets");
+
+/// An offset or length in the file. Stored as a little-endian `u64`, so
+/// that files written on one platform can be read on any other, including
+/// 32-bit WASM.
+#[repr(C, align(8))]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Pod, Zeroable)]
+pub struct Le64([u8; 8]);
+
+impl Le64 {
+    pub fn new(value: usize) -> Self {
+        Self((value as u64).to_le_bytes())
+    }
+
+    pub fn get(self) -> usize {
+        usize::try_from(u64::from(self)).expect("offset fits in usize")
+    }
+}
+
+impl From<Le64> for u64 {
+    fn from(value: Le64) -> Self {
+        u64::from_le_bytes(value.0)
+    }
+}
+
+/// How the data of a tile is stored, written as a tag in front of it.
+/// `Empty` and `Inline` match the tags of an `Option`.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileEncoding {
+    Empty = 0,
+    /// Every row is stored.
+    Inline = 1,
+    /// Every value is equal, so only the height and one row are stored.
+    Constant = 2,
+    /// Byte-identical to an earlier tile, so only its position is stored.
+    Reference = 3,
+    /// Stored as a [`crate::CompressedTileResponse`] in the wire format,
+    /// so the tile can be served as is.
+    Compressed = 4,
+}
+
+impl TryFrom<u8> for TileEncoding {
+    type Error = u8;
+
+    /// Fails with the tag if it is not one of an encoding.
+    fn try_from(tag: u8) -> Result<Self, u8> {
+        match tag {
+            0 => Ok(Self::Empty),
+            1 => Ok(Self::Inline),
+            2 => Ok(Self::Constant),
+            3 => Ok(Self::Reference),
+            4 => Ok(Self::Compressed),
+            _ => Err(tag),
+        }
+    }
+}
+
+/// Locates the data of one tile in the index written after the nodes.
+/// A `data` of 0 marks a missing tile, since the root node starts there.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct IndexEntry {
+    /// `min.x`, `min.y`, `max.x` and `max.y` of the tile.
+    pub extent: [f32; 4],
+    /// Position of the aggregate and tile of the node.
+    pub data: Le64,
+    /// Position and size of the tag and data of the tile. Duplicate tiles
+    /// point to the tile they duplicate, so that the tile can be read on
+    /// its own, without following a [`TileEncoding::Reference`].
+    pub tile: Le64,
+    pub tile_size: Le64,
+}
+
+impl IndexEntry {
+    pub fn bounds(&self) -> Bounds {
+        let [min_x, min_y, max_x, max_y] = self.extent;
+
+        Bounds::new(Coord { x: min_x, y: min_y }, Coord { x: max_x, y: max_y })
+    }
+
+    /// Bytes holding the tile, starting at the 8-byte boundary before it so
+    /// that its values keep the alignment they have in the file.
+    pub fn tile_range(&self) -> Range<u64> {
+        let start = u64::from(self.tile);
+
+        start & !7..start + u64::from(self.tile_size)
+    }
+}
+
+/// Locates the entries of one level, stored row by row.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct IndexLevel {
+    pub offset: Le64,
+    pub width: Le64,
+    pub height: Le64,
+}
+
+/// Written at the very end of the file, pointing to a table of
+/// [`IndexLevel`]s. Files without it are read by walking the tree.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct IndexFooter {
+    pub offset: Le64,
+    pub levels: Le64,
+    pub magic: [u8; 8],
+}
+
+impl IndexFooter {
+    /// Changed whenever the index changes, so that files with an older
+    /// index are read by walking the tree instead.
+    pub const MAGIC: [u8; 8] = *b"GEOIDX02";
+}
@@ -1,9 +1,37 @@
-use bytemuck::Zeroable;
+use std::borrow::Cow;
+
+use bytemuck::{Pod, Zeroable};
+use codec::{Codec, DecodeError};
 use serde::{Deserialize, Serialize};
 
+pub mod codec;
+pub mod deserialize;
+pub mod format;
+
 pub type Bounds = geo::Rect<f32>;
 pub type Tile<T> = Vec<Vec<T>>;
-pub type TileRef<'a, T> = Vec<&'a [T]>;
+pub type TileRef<'a, T> = Vec<Cow<'a, [T]>>;
+
+/// The values stored in a tree and how it branches, all that is needed to
+/// read a database.
+pub trait Layer {
+    type Type;
+    type AggregateType;
+
+    fn aggregate2(_values: &[Self::AggregateType]) -> Option<Self::AggregateType> {
+        None
+    }
+
+    const CHILDREN_PER_AXIS: usize;
+}
+
+/// The lines of a vector layer within one tile, clipped to its bounds and
+/// simplified for its level.
+pub type VectorTile = Vec<geo::LineString<f32>>;
+
+/// Sent in the `Accept` header by clients able to decode
+/// [`CompressedTileResponse`], and as the `Content-Type` of such responses.
+pub const COMPRESSED_TILE_MEDIA_TYPE: &str = "application/x-compressed-tile";
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Coordinate {
@@ -11,6 +39,18 @@ pub struct Coordinate {
     pub lon: f32,
 }
 
+/// Mean radius of the earth in kilometres, as used by `geo`'s `Haversine`.
+/// The frontend scales its unit sphere to it when circles are queried.
+pub const EARTH_RADIUS_KM: f32 = 6371.009;
+
+/// A circle on the surface of the earth, sent to aggregate the values within
+/// `radius_km` of `center`, measured along great circles.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct CircleQuery {
+    pub center: Coordinate,
+    pub radius_km: f32,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct TileResponse<T> {
     pub data: Tile<T>,
@@ -18,11 +58,61 @@ pub struct TileResponse<T> {
 }
 
 #[derive(Debug, Serialize)]
-pub struct TileRefResponse<'a, T> {
+pub struct TileRefResponse<'a, T>
+where
+    T: Clone,
+{
     pub data: TileRef<'a, T>,
     pub bounds: Bounds,
 }
 
+/// A tile passed on as it is stored in the database, with its rows
+/// encoded as one block by `codec`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompressedTileResponse<'a> {
+    pub bounds: Bounds,
+    pub codec: Codec,
+    pub width: u32,
+    pub height: u32,
+    #[serde(borrow)]
+    pub data: Cow<'a, [u8]>,
+}
+
+/// Wider and taller than any tile, to tell damaged tiles apart before
+/// allocating their values.
+pub(crate) const MAX_TILE_SIZE: usize = 1 << 16;
+
+impl CompressedTileResponse<'_> {
+    /// Decodes the values, failing without allocating them if the size of
+    /// the tile is larger than any tile or than `data` can hold.
+    pub fn decode<T>(&self) -> Result<TileResponse<T>, DecodeError>
+    where
+        T: Pod,
+    {
+        let width = self.width as usize;
+        let height = self.height as usize;
+
+        let len = Some(width)
+            .filter(|&width| width <= MAX_TILE_SIZE && height <= MAX_TILE_SIZE)
+            .and_then(|width| width.checked_mul(height))
+            .filter(|&len| self.codec.fits::<T>(&self.data, len))
+            .ok_or(DecodeError::Size {
+                width: self.width,
+                height: self.height,
+            })?;
+
+        let values = self.codec.decode::<T>(&self.data, len)?;
+
+        Ok(TileResponse {
+            data: values
+                .chunks_exact(width.max(1))
+                .map(<[T]>::to_vec)
+                .collect(),
+            bounds: self.bounds,
+        })
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct TileMetadata {
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use common::TileResponse;
+use common::{deserialize::GeoTree, TileResponse, VectorTile};
 use web_time::Duration;
 use winit::{
     application::ApplicationHandler,
@@ -9,25 +9,48 @@ use winit::{
     window::WindowAttributes,
 };
 
-use crate::{safe_get_subdivision_level, types::PerformanceMetrics, State};
+use crate::{
+    camera::{OrbitMode, View},
+    safe_get_subdivision_level,
+    state::Action,
+    types::PerformanceMetrics,
+    utils::remote::RemoteDatabase,
+    State,
+};
 
 #[derive(Debug)]
 pub enum CustomEvent {
     CreateState(Box<State>),
     HttpResponse(CustomResponseType),
+    SetTimestep(Option<String>),
+    LoadDatabase(Vec<u8>),
+    RemoteDatabase(RemoteDatabase),
+    /// Flies the camera to the view over the given number of seconds.
+    FlyTo(View, f32),
+    /// Makes the named key do the action, or nothing.
+    BindKey(String, Option<Action>),
+    /// Turns the camera so that north is up.
+    ResetNorth,
+    SetOrbitMode(OrbitMode),
+    /// Raises the terrain by the given times its true height.
+    SetTerrainExaggeration(f32),
 }
 
 #[derive(Debug)]
 pub enum CustomResponseType {
     SatelliteImage(TileResponse<[u8; 4]>, (u32, u32, u32)),
     Population(TileResponse<f32>, (u32, u32, u32)),
-    LightPollution(TileResponse<f32>, (u32, u32, u32)),
+    LightPollution(TileResponse<f32>, (u32, u32, u32), Option<String>),
+    Elevation(TileResponse<f32>, (u32, u32, u32)),
+    /// A tile of the vector layer at the given index.
+    Vector(VectorTile, (u32, u32, u32), usize),
 }
 
 pub struct App {
     state: Option<State>,
+    /// Events that need the state, received before it was created.
+    pending: Vec<CustomEvent>,
     perf_metrics: PerformanceMetrics,
-    #[allow(dead_code)]
     proxy_eventloop: EventLoopProxy<CustomEvent>,
 }
 
@@ -35,6 +58,7 @@ impl App {
     pub fn _new(proxy: EventLoopProxy<CustomEvent>) -> Self {
         Self {
             state: None,
+            pending: Vec::new(),
             perf_metrics: PerformanceMetrics::new(),
             proxy_eventloop: proxy,
         }
@@ -68,6 +92,26 @@ impl ApplicationHandler<CustomEvent> for App {
                 ))
                 .expect("added canvas to map element");
 
+            listen_to_page_event(&self.proxy_eventloop, "timestep", |detail| {
+                CustomEvent::SetTimestep(detail.as_string())
+            });
+
+            let proxy_eventloop = self.proxy_eventloop.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Some(database) = crate::load_offline_database().await {
+                    let _ = proxy_eventloop.send_event(CustomEvent::LoadDatabase(database));
+                }
+            });
+
+            if let Some(url) = crate::get_remote_database() {
+                let proxy_eventloop = self.proxy_eventloop.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    if let Some(database) = RemoteDatabase::open(url).await {
+                        let _ = proxy_eventloop.send_event(CustomEvent::RemoteDatabase(database));
+                    }
+                });
+            }
+
             let proxy_eventloop = self.proxy_eventloop.clone();
             wasm_bindgen_futures::spawn_local(async move {
                 proxy_eventloop
@@ -153,6 +197,10 @@ impl ApplicationHandler<CustomEvent> for App {
         match (event, &mut self.state) {
             (CustomEvent::CreateState(state), None) => {
                 self.state = Some(*state);
+
+                for event in self.pending.drain(..) {
+                    let _ = self.proxy_eventloop.send_event(event);
+                }
             }
 
             (
@@ -172,14 +220,97 @@ impl ApplicationHandler<CustomEvent> for App {
             //     state.window.request_redraw();
             // }
             (
-                CustomEvent::HttpResponse(CustomResponseType::LightPollution(tile, id)),
+                CustomEvent::HttpResponse(CustomResponseType::LightPollution(tile, id, timestep)),
                 Some(state),
             ) => {
                 state.earth_state.update_tile_buffer = true;
-                state.earth_state.insert_lp_tile(id, tile);
+                state.earth_state.insert_lp_tile(id, timestep, tile);
+                state.window.request_redraw();
+            }
+            (CustomEvent::HttpResponse(CustomResponseType::Elevation(tile, id)), Some(state)) => {
+                state.earth_state.update_tile_buffer = true;
+                state.earth_state.insert_elevation_tile(id, tile);
+                state.window.request_redraw();
+            }
+            (
+                CustomEvent::HttpResponse(CustomResponseType::Vector(tile, id, layer)),
+                Some(state),
+            ) => {
+                state.vector_state.insert_tile(id, layer, tile);
+                state.window.request_redraw();
+            }
+            (
+                event @ (CustomEvent::LoadDatabase(_)
+                | CustomEvent::RemoteDatabase(_)
+                | CustomEvent::FlyTo(..)
+                | CustomEvent::BindKey(..)
+                | CustomEvent::ResetNorth
+                | CustomEvent::SetOrbitMode(_)
+                | CustomEvent::SetTerrainExaggeration(_)),
+                None,
+            ) => {
+                self.pending.push(event);
+            }
+            (CustomEvent::LoadDatabase(database), Some(state)) => {
+                state
+                    .earth_state
+                    .set_offline_database(GeoTree::from_source(database));
+                state.window.request_redraw();
+            }
+            (CustomEvent::RemoteDatabase(database), Some(state)) => {
+                state.earth_state.set_remote_database(database);
+                state.window.request_redraw();
+            }
+            (CustomEvent::FlyTo(view, duration), Some(state)) => {
+                state.camera_state.controller.fly_to(view, duration);
+                state.window.request_redraw();
+            }
+            (CustomEvent::BindKey(key, action), Some(state)) => {
+                state.keymap.bind(key, action);
+            }
+            (CustomEvent::ResetNorth, Some(state)) => {
+                state.camera_state.controller.reset_north();
+                state.window.request_redraw();
+            }
+            (CustomEvent::SetTerrainExaggeration(exaggeration), Some(state)) => {
+                state
+                    .earth_state
+                    .set_terrain_exaggeration(exaggeration, &state.queue);
+                state.window.request_redraw();
+            }
+            (CustomEvent::SetOrbitMode(orbit_mode), Some(state)) => {
+                state.camera_state.controller.set_orbit_mode(orbit_mode);
+                state.window.request_redraw();
+            }
+            (CustomEvent::SetTimestep(timestep), Some(state)) => {
+                state.earth_state.set_lp_timestep(timestep);
                 state.window.request_redraw();
             }
             _ => {}
         }
     }
 }
+
+/// Forwards `CustomEvent`s dispatched on the page's `window` with the given
+/// name to the event loop, letting the page's controls talk to the app.
+#[cfg(target_arch = "wasm32")]
+fn listen_to_page_event<F>(proxy: &EventLoopProxy<CustomEvent>, name: &str, to_event: F)
+where
+    F: Fn(wasm_bindgen::JsValue) -> CustomEvent + 'static,
+{
+    use wasm_bindgen::{closure::Closure, JsCast};
+
+    let proxy = proxy.clone();
+    let listener =
+        Closure::<dyn Fn(web_sys::CustomEvent)>::new(move |event: web_sys::CustomEvent| {
+            let _ = proxy.send_event(to_event(event.detail()));
+        });
+
+    web_sys::window()
+        .expect("retrieved window")
+        .add_event_listener_with_callback(name, listener.as_ref().unchecked_ref())
+        .expect("added event listener");
+
+    // The listener lives for as long as the page
+    listener.forget();
+}
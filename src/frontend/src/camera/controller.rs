@@ -1,12 +1,56 @@
 use crate::camera::uniform::CameraUniform;
 use crate::AnimationState;
 
-use super::{Camera, Projection};
-use glam::Vec2;
+use super::{Camera, Projection, View};
+use crate::types::ray_intersects_sphere;
+use glam::{Mat4, Vec2, Vec3};
+use std::collections::HashSet;
 use web_time::Duration;
 use winit::dpi::PhysicalPosition;
 use winit::event::MouseScrollDelta;
 
+/// Radians per second the globe turns at while a key is held, for every
+/// radius of the globe the camera is above the surface.
+const KEY_ROTATION_SPEED: f32 = 0.6;
+const MAX_KEY_ROTATION_SPEED: f32 = 1.5;
+/// Share of the distance to the surface zoomed per second while a key is held.
+const KEY_ZOOM_SPEED: f32 = 1.5;
+/// Radians per second the camera tilts at while a key is held.
+const KEY_TILT_SPEED: f32 = 0.8;
+/// Radians the camera tilts by for every pixel two fingers move up.
+const TOUCH_TILT_SPEED: f32 = 0.005;
+/// How quickly a flung globe slows down, as a share of its speed per second.
+const FLING_DECAY: f32 = 4.;
+/// Pixels per second below which a flung globe stops.
+const MIN_FLING_SPEED: f32 = 10.;
+/// Seconds taken to turn north up again.
+const RESET_NORTH_DURATION: f32 = 0.6;
+
+/// Ways the camera moves for as long as a key is held.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Movement {
+    RotateLeft,
+    RotateRight,
+    RotateUp,
+    RotateDown,
+    ZoomIn,
+    ZoomOut,
+    TiltUp,
+    TiltDown,
+}
+
+/// How dragging turns the globe.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OrbitMode {
+    /// Turns the globe freely, as if rolling a ball, so that north may end
+    /// up in any direction.
+    #[default]
+    Arcball,
+    /// Changes only the latitude and longitude in the center of the screen,
+    /// keeping north up.
+    NorthUp,
+}
+
 #[derive(Debug)]
 pub struct CameraController {
     scroll: f32,
@@ -15,9 +59,20 @@ pub struct CameraController {
 
     pub rotating: bool,
     pub tilting: bool,
+    pub orbit_mode: OrbitMode,
 
     pub last_position: Vec2,
     pub current_position: Vec2,
+    /// The point on the globe under the cursor, on the unit sphere.
+    pub cursor_on_globe: Option<Vec3>,
+    /// Where on the screen the last zoom is anchored, so that the point of
+    /// the globe there stays put.
+    zoom_anchor: Option<Vec2>,
+    /// Where a flung globe was let go, in pixels, and how fast it is still
+    /// turning as if dragged from there, in pixels per second.
+    fling: Option<(Vec2, Vec2)>,
+    /// Movements of keys currently held.
+    movements: HashSet<Movement>,
 
     min: f32,
     max: f32,
@@ -44,9 +99,14 @@ impl CameraController {
 
             rotating: false,
             tilting: false,
+            orbit_mode: OrbitMode::default(),
 
             last_position: Vec2::ZERO,
             current_position: Vec2::ZERO,
+            cursor_on_globe: None,
+            zoom_anchor: None,
+            fling: None,
+            movements: HashSet::new(),
 
             min,
             max,
@@ -61,9 +121,37 @@ impl CameraController {
         let position = Vec2::new(mouse_dx as f32, mouse_dy as f32);
 
         self.current_position = position;
+        self.cursor_on_globe = self.unproject(position);
+    }
+
+    /// The point on the globe under `cursor`, in pixels, if there is one,
+    /// found by casting a ray through it with the inverse view-projection.
+    pub fn unproject(&self, cursor: Vec2) -> Option<Vec3> {
+        self.cast_ray(cursor, self.camera.calc_matrix())
+    }
+
+    /// The point on the unit sphere, placed in front of the camera by
+    /// `view`, under `cursor`.
+    fn cast_ray(&self, cursor: Vec2, view: Mat4) -> Option<Vec3> {
+        let inv_view_proj = (self.projection.calc_matrix() * view).inverse();
+
+        let ndc = Vec2::new(
+            2. * cursor.x / self.projection.size.x - 1.,
+            1. - 2. * cursor.y / self.projection.size.y,
+        );
+
+        let near = inv_view_proj.project_point3(ndc.extend(0.));
+        let far = inv_view_proj.project_point3(ndc.extend(1.));
+
+        ray_intersects_sphere(near, (far - near).normalize(), Vec3::ZERO, 1.).map(Vec3::normalize)
     }
 
-    pub fn process_mouse_wheel(&mut self, delta: &MouseScrollDelta) {
+    /// Zooms by `delta`, keeping the point of the globe under `anchor`, in
+    /// pixels, where it is on the screen.
+    pub fn process_mouse_wheel(&mut self, delta: &MouseScrollDelta, anchor: Vec2) {
+        self.camera.cancel_flight();
+        self.zoom_anchor = Some(anchor);
+
         self.scroll = self.speed
             * (self.camera.radius / 100.).sqrt()
             * match delta {
@@ -71,6 +159,70 @@ impl CameraController {
                 MouseScrollDelta::PixelDelta(PhysicalPosition { y: scroll, .. }) => *scroll as f32,
             };
 
+        self.untilt_with_radius();
+    }
+
+    /// Zooms by `scale` times as close to the surface, as two fingers
+    /// spread apart, keeping the point of the globe under `center` put.
+    pub fn process_pinch(&mut self, center: Vec2, scale: f32) {
+        self.camera.cancel_flight();
+
+        let point = self.unproject(center);
+        let height = self.camera.radius - 1.;
+
+        self.camera.radius = (1. + height / scale).clamp(self.min, self.max);
+
+        if let Some(point) = point {
+            self.keep_under(center, point);
+        }
+
+        self.untilt_with_radius();
+    }
+
+    /// Turns the heading as two fingers turn by `angle` radians clockwise,
+    /// unless north is kept up.
+    pub fn process_twist(&mut self, angle: f32) {
+        if self.orbit_mode == OrbitMode::NorthUp {
+            return;
+        }
+
+        // The globe turns with the fingers, so the top of the screen faces
+        // further counterclockwise
+        self.camera.twist(-angle);
+    }
+
+    /// Tilts the camera as two fingers move up the screen by `distance`
+    /// pixels, when close enough to the surface to tilt.
+    pub fn process_touch_tilt(&mut self, distance: f32) {
+        if self.camera.radius < 1.1 {
+            self.camera.tilt(distance * TOUCH_TILT_SPEED);
+        }
+    }
+
+    /// Drags the globe with one finger from `from` to `to`, in pixels.
+    pub fn process_touch_drag(&mut self, from: Vec2, to: Vec2) {
+        if !self.rotating {
+            self.rotating = true;
+            self.last_position = from;
+        }
+
+        self.fling = None;
+        self.process_cursor_moved(to.x as f64, to.y as f64);
+    }
+
+    /// Keeps the globe turning after it was let go of while moving at
+    /// `velocity`, in pixels per second, slowing down as it goes.
+    pub fn process_fling(&mut self, velocity: Vec2) {
+        self.rotating = false;
+        self.fling = Some((self.current_position, velocity));
+    }
+
+    pub fn stop_fling(&mut self) {
+        self.fling = None;
+    }
+
+    /// Levels the camera out as it moves away from the surface.
+    fn untilt_with_radius(&mut self) {
         self.camera.angle = {
             let min = 0.;
             let max = self.camera.angle;
@@ -82,7 +234,60 @@ impl CameraController {
         };
     }
 
+    /// Flies to `view` over `duration` seconds, kept within the distances
+    /// the camera can be zoomed to.
+    pub fn fly_to(&mut self, view: View, duration: f32) {
+        self.fling = None;
+
+        let view = View {
+            radius: view.radius.clamp(self.min, self.max),
+            ..view
+        };
+
+        self.camera.fly_to(view, duration);
+    }
+
+    /// Starts or stops `movement`, as its key is pressed or released.
+    pub fn process_movement(&mut self, movement: Movement, pressed: bool) {
+        if pressed {
+            self.movements.insert(movement);
+        } else {
+            self.movements.remove(&movement);
+        }
+    }
+
+    /// Stops all movements, as when keys are released outside the window.
+    pub fn stop_movements(&mut self) {
+        self.movements.clear();
+    }
+
+    pub fn is_moving(&self) -> bool {
+        !self.movements.is_empty()
+    }
+
+    /// Turns the globe so that north is up again, keeping the point in the
+    /// center of the screen.
+    pub fn reset_north(&mut self) {
+        let view = View {
+            heading: 0.,
+            ..View::from_camera(&self.camera)
+        };
+
+        self.camera.fly_to(view, RESET_NORTH_DURATION);
+    }
+
+    /// Switches how dragging turns the globe, turning north up when it is
+    /// to be kept up.
+    pub fn set_orbit_mode(&mut self, orbit_mode: OrbitMode) {
+        self.orbit_mode = orbit_mode;
+
+        if orbit_mode == OrbitMode::NorthUp {
+            self.reset_north();
+        }
+    }
+
     pub fn process_drag_start(&mut self) {
+        self.fling = None;
         self.rotating = true;
     }
 
@@ -93,18 +298,32 @@ impl CameraController {
     pub fn update_camera(&mut self, duration: Duration) -> AnimationState {
         let duration = duration.as_secs_f32();
 
+        // The point under the anchor as shown, before the zoom moves it
+        let anchored = self
+            .zoom_anchor
+            .filter(|_| self.scroll != 0.)
+            .and_then(|anchor| Some((anchor, self.unproject(anchor)?)));
+
         let scroll_factor =
             self.scroll * self.speed * (self.camera.radius / 100.).sqrt() * duration;
         self.camera.radius = (self.camera.radius - scroll_factor).clamp(self.min, self.max);
 
+        if let Some((anchor, point)) = anchored {
+            self.keep_under(anchor, point);
+        }
+
         if self.rotating {
-            self.camera.rotate(
-                self.last_position,
-                self.current_position,
-                self.sensitivity,
-                self.size,
-                &self.projection,
-            );
+            self.drag(self.last_position, self.current_position);
+        }
+
+        // Flung from where it was let go, so that it turns as steadily as
+        // when it was dragged
+        if let Some((position, velocity)) = self.fling {
+            self.drag(position, position + velocity * duration);
+
+            let velocity = velocity * (-FLING_DECAY * duration).exp();
+
+            self.fling = (velocity.length() >= MIN_FLING_SPEED).then_some((position, velocity));
         }
 
         if self.tilting && self.camera.radius < 1.1 {
@@ -113,10 +332,91 @@ impl CameraController {
             self.camera.tilt(factor * sensitivity);
         }
 
+        if !self.movements.is_empty() {
+            self.apply_movements(duration);
+        }
+
         self.last_position = self.current_position;
         self.scroll = 0.0;
 
-        self.camera.animate(duration)
+        let animation = self.camera.animate(duration);
+
+        // The globe moved under the cursor
+        self.cursor_on_globe = self.unproject(self.current_position);
+
+        match self.fling {
+            Some(_) => AnimationState::Animating,
+            None => animation,
+        }
+    }
+
+    /// Turns the globe as the cursor is dragged from `from` to `to`, as the
+    /// orbit mode says.
+    fn drag(&mut self, from: Vec2, to: Vec2) {
+        let drag = match self.orbit_mode {
+            OrbitMode::Arcball => Camera::rotate,
+            OrbitMode::NorthUp => Camera::drag_orbit,
+        };
+
+        drag(
+            &mut self.camera,
+            from,
+            to,
+            self.sensitivity,
+            self.size,
+            &self.projection,
+        );
+    }
+
+    /// Turns the globe so that `point`, on the unit sphere, is under `cursor`
+    /// once the camera reaches where it is moving to. Points the camera will
+    /// no longer see there, as when zooming out near the edge of the globe,
+    /// are left where they are.
+    fn keep_under(&mut self, cursor: Vec2, point: Vec3) {
+        let Some(target) = self.cast_ray(cursor, self.camera.calc_target_matrix_unturned()) else {
+            return;
+        };
+
+        self.camera.turn_point_to(point, target);
+
+        if self.orbit_mode == OrbitMode::NorthUp {
+            self.camera.level();
+        }
+    }
+
+    /// Moves the targets of the camera for the held keys, which it then
+    /// eases towards like any other movement.
+    fn apply_movements(&mut self, duration: f32) {
+        let held = |movement| self.movements.contains(&movement) as i32 as f32;
+
+        let height = (self.camera.radius - 1.).max(0.);
+        let speed = (KEY_ROTATION_SPEED * height).min(MAX_KEY_ROTATION_SPEED) * duration;
+
+        // Turning about the vertical axis moves the surface sideways, and
+        // about the horizontal axis up and down
+        let yaw = (held(Movement::RotateRight) - held(Movement::RotateLeft)) * speed;
+        let pitch = (held(Movement::RotateDown) - held(Movement::RotateUp)) * speed;
+
+        if yaw != 0. || pitch != 0. {
+            match self.orbit_mode {
+                OrbitMode::Arcball => self.camera.turn(yaw, pitch),
+                OrbitMode::NorthUp => self.camera.orbit(-pitch, yaw),
+            }
+        }
+
+        let zoom = held(Movement::ZoomIn) - held(Movement::ZoomOut);
+
+        if zoom != 0. {
+            self.camera.cancel_flight();
+            self.camera.radius = (self.camera.radius - zoom * KEY_ZOOM_SPEED * height * duration)
+                .clamp(self.min, self.max);
+        }
+
+        let tilt = held(Movement::TiltUp) - held(Movement::TiltDown);
+
+        if self.camera.radius < 1.1 {
+            self.camera.tilt(tilt * KEY_TILT_SPEED * duration);
+        }
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
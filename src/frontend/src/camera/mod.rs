@@ -1,9 +1,9 @@
-use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2, SQRT_2};
+use std::f32::consts::{FRAC_1_SQRT_2, FRAC_PI_2, PI, SQRT_2};
 
 use glam::{Mat3, Mat4, Quat, Vec2, Vec3, Vec3Swizzles};
 
 mod controller;
-pub use controller::CameraController;
+pub use controller::{CameraController, Movement, OrbitMode};
 
 mod projection;
 pub use projection::Projection;
@@ -15,8 +15,28 @@ use crate::AnimationState;
 
 mod uniform;
 
+mod view;
+pub use view::View;
+
 const R: f32 = 0.8;
 
+/// How far out, in radii of the globe, a flight halfway around it rises
+/// above the straight path between its ends, to show where it goes.
+const FLIGHT_HOP: f32 = 1.5;
+
+/// Degrees of latitude the center of the screen is kept within when
+/// orbiting with north up, where north is still a direction.
+const MAX_ORBIT_LAT: f32 = 89.;
+
+/// An eased flight between two views, started by [`Camera::fly_to`].
+#[derive(Clone, Copy, Debug)]
+struct Flight {
+    from: (Quat, f32, f32),
+    to: (Quat, f32, f32),
+    elapsed: f32,
+    duration: f32,
+}
+
 #[derive(Debug)]
 pub struct Camera {
     pub radius: f32,
@@ -29,6 +49,7 @@ pub struct Camera {
     pub current_orientation: Quat,
 
     friction: f32,
+    flight: Option<Flight>,
 }
 
 impl Camera {
@@ -44,6 +65,7 @@ impl Camera {
             current_orientation: Quat::IDENTITY,
 
             friction: 5.,
+            flight: None,
         }
     }
 
@@ -53,10 +75,99 @@ impl Camera {
             * Mat4::from_quat(self.current_orientation)
     }
 
+    /// The view matrix the camera is moving to, before the globe is turned.
+    pub fn calc_target_matrix_unturned(&self) -> Mat4 {
+        Mat4::from_rotation_x(self.angle) * Mat4::from_translation(Vec3::Z * self.radius)
+    }
+
     pub fn tilt(&mut self, delta: f32) {
+        if delta != 0. {
+            self.flight = None;
+        }
+
         self.angle = (self.angle + delta).clamp(0., FRAC_PI_2);
     }
 
+    /// Turns the globe by `yaw` radians about the vertical axis of the
+    /// screen and `pitch` radians about its horizontal axis.
+    pub fn turn(&mut self, yaw: f32, pitch: f32) {
+        self.flight = None;
+
+        self.orientation =
+            (Quat::from_rotation_y(yaw) * Quat::from_rotation_x(pitch) * self.orientation)
+                .normalize();
+    }
+
+    /// Turns the globe by `angle` radians counterclockwise about the center
+    /// of the screen, turning the heading by as much.
+    pub fn twist(&mut self, angle: f32) {
+        self.flight = None;
+
+        self.orientation = (Quat::from_rotation_z(angle) * self.orientation).normalize();
+    }
+
+    /// Turns the globe the shortest way that moves `point`, on its surface,
+    /// to `target`, where it is to be once turned.
+    pub fn turn_point_to(&mut self, point: Vec3, target: Vec3) {
+        self.flight = None;
+
+        let rotation = Quat::from_rotation_arc(self.orientation * point, target);
+
+        self.orientation = (rotation * self.orientation).normalize();
+    }
+
+    /// Turns the globe about the center of the screen so that north is up.
+    pub fn level(&mut self) {
+        self.flight = None;
+
+        self.orientation = View {
+            heading: 0.,
+            ..View::from_camera(self)
+        }
+        .orientation();
+    }
+
+    /// Moves the camera to `view` at once.
+    pub fn set_view(&mut self, view: View) {
+        self.flight = None;
+
+        self.orientation = view.orientation();
+        self.current_orientation = self.orientation;
+        self.radius = view.radius;
+        self.current_radius = self.radius;
+        self.angle = view.tilt.to_radians().clamp(0., FRAC_PI_2);
+        self.current_angle = self.angle;
+    }
+
+    /// Flies the camera to `view` over `duration` seconds, easing in and
+    /// out, until it arrives or is moved by other means.
+    pub fn fly_to(&mut self, view: View, duration: f32) {
+        if duration <= 0. {
+            self.set_view(view);
+            return;
+        }
+
+        self.flight = Some(Flight {
+            from: (
+                self.current_orientation,
+                self.current_radius,
+                self.current_angle,
+            ),
+            to: (
+                view.orientation(),
+                view.radius,
+                view.tilt.to_radians().clamp(0., FRAC_PI_2),
+            ),
+            elapsed: 0.,
+            duration,
+        });
+    }
+
+    /// Stops a flight where it is, as when the camera is moved by hand.
+    pub fn cancel_flight(&mut self) {
+        self.flight = None;
+    }
+
     pub fn rotate(
         &mut self,
         previous_position: Vec2,
@@ -67,10 +178,7 @@ impl Camera {
     ) {
         let screen_center = projection.size * 0.5;
 
-        let angular_diameter = 2.0 * (object_radius / self.current_radius).asin();
-
-        let object_screen_radius =
-            (angular_diameter / projection.fovy) * (projection.size.min_element() / 2.0);
+        let object_screen_radius = self.object_screen_radius(object_radius, projection);
 
         let p1 = Vec2::new(-1., 1.) * (previous_position - screen_center) / object_screen_radius;
         let p2 = Vec2::new(-1., 1.) * (current_position - screen_center) / object_screen_radius;
@@ -97,10 +205,95 @@ impl Camera {
 
         let rotation = Quat::from_axis_angle(axis, sensitivity * angle / scaling_ratio).normalize();
 
+        self.flight = None;
+
         self.orientation = (rotation * self.orientation).normalize();
     }
 
+    /// Moves the point in the center of the screen as the cursor is dragged
+    /// from `previous_position` to `current_position`, keeping north up.
+    pub fn drag_orbit(
+        &mut self,
+        previous_position: Vec2,
+        current_position: Vec2,
+        sensitivity: f32,
+        object_radius: f32,
+        projection: &Projection,
+    ) {
+        let delta = (current_position - previous_position) * sensitivity
+            / self.object_screen_radius(object_radius, projection);
+
+        if delta == Vec2::ZERO {
+            return;
+        }
+
+        // The surface follows the cursor, so dragging down shows the north
+        self.orbit(delta.y, -delta.x);
+    }
+
+    /// Moves the point in the center of the screen by `lat` and `lon`
+    /// radians, turning the globe so that north is up.
+    pub fn orbit(&mut self, lat: f32, lon: f32) {
+        self.flight = None;
+
+        let view = View::from_camera(self);
+
+        self.orientation = View {
+            lat: (view.lat + lat.to_degrees()).clamp(-MAX_ORBIT_LAT, MAX_ORBIT_LAT),
+            lon: view.lon + lon.to_degrees(),
+            heading: 0.,
+            ..view
+        }
+        .orientation();
+    }
+
+    /// Degrees clockwise from north that the top of the screen faces, as
+    /// currently shown.
+    pub fn heading(&self) -> f32 {
+        View::from_orientation(
+            self.current_orientation,
+            self.current_radius,
+            self.current_angle,
+        )
+        .heading
+    }
+
+    /// Radius in pixels of a sphere of `object_radius` at the center of the
+    /// screen.
+    fn object_screen_radius(&self, object_radius: f32, projection: &Projection) -> f32 {
+        let angular_diameter = 2.0 * (object_radius / self.current_radius).asin();
+
+        (angular_diameter / projection.fovy) * (projection.size.min_element() / 2.0)
+    }
+
     pub fn animate(&mut self, duration: f32) -> AnimationState {
+        if let Some(flight) = &mut self.flight {
+            flight.elapsed += duration;
+
+            let t = (flight.elapsed / flight.duration).min(1.);
+            let eased = t * t * (3. - 2. * t);
+
+            let (from_orientation, from_radius, from_angle) = flight.from;
+            let (to_orientation, to_radius, to_angle) = flight.to;
+
+            let hop = from_orientation.angle_between(to_orientation) / PI * FLIGHT_HOP;
+
+            self.orientation = from_orientation.slerp(to_orientation, eased);
+            self.radius =
+                from_radius + (to_radius - from_radius) * eased + hop * (PI * eased).sin();
+            self.angle = from_angle + (to_angle - from_angle) * eased;
+
+            self.current_orientation = self.orientation;
+            self.current_radius = self.radius;
+            self.current_angle = self.angle;
+
+            if t < 1. {
+                return AnimationState::Animating;
+            }
+
+            self.flight = None;
+        }
+
         let mut animation_state = AnimationState::Finished;
 
         if !self.current_orientation.abs_diff_eq(self.orientation, 1e-6) {
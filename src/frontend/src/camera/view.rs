@@ -0,0 +1,108 @@
+use std::{fmt, str::FromStr};
+
+use glam::{Mat3, Quat, Vec3};
+
+use crate::types::convert_point_on_surface_to_lat_lon;
+
+use super::Camera;
+
+/// Where the camera looks from, in terms that can be saved and shared.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct View {
+    /// Degrees of latitude of the point in the center of the screen.
+    pub lat: f32,
+    /// Degrees of longitude of the point in the center of the screen.
+    pub lon: f32,
+    /// Degrees clockwise from north that the top of the screen faces.
+    pub heading: f32,
+    /// Distance of the camera from the center of the globe, in radii of
+    /// the globe.
+    pub radius: f32,
+    /// Degrees the camera is tilted away from looking straight down.
+    pub tilt: f32,
+}
+
+impl View {
+    /// The orientation of the globe that puts `lat` and `lon` in the center
+    /// of the screen, turned so that the top of the screen faces `heading`.
+    pub fn orientation(&self) -> Quat {
+        let (lat, lon) = (self.lat.to_radians(), self.lon.to_radians());
+
+        // The point looked at, and the directions north and east along the
+        // surface there, as the globe's shader maps latitude and longitude
+        let down = Vec3::new(-lon.sin() * lat.cos(), lon.cos() * lat.cos(), -lat.sin());
+        let north = Vec3::new(lon.sin() * lat.sin(), -lon.cos() * lat.sin(), -lat.cos());
+        let east = down.cross(north);
+
+        // The surface faces the camera, which looks along +Z, with north up
+        let north_up = Quat::from_mat3(&Mat3::from_cols(east, north, -down)).inverse();
+
+        Quat::from_rotation_z(self.heading.to_radians()) * north_up
+    }
+
+    /// The view `camera` is moving to.
+    pub fn from_camera(camera: &Camera) -> Self {
+        Self::from_orientation(camera.orientation, camera.radius, camera.angle)
+    }
+
+    /// The view of the globe turned to `orientation`, from `radius` away
+    /// and tilted by `angle` radians.
+    pub fn from_orientation(orientation: Quat, radius: f32, angle: f32) -> Self {
+        let center = orientation.inverse() * Vec3::NEG_Z;
+        let coord = convert_point_on_surface_to_lat_lon(center);
+
+        let north_up = View {
+            lat: coord.y,
+            lon: coord.x,
+            heading: 0.,
+            radius,
+            tilt: 0.,
+        };
+
+        // Where north is on the screen, turned from the top by the heading
+        let north = orientation * north_up.orientation().inverse() * Vec3::Y;
+
+        Self {
+            heading: (-north.x).atan2(north.y).to_degrees(),
+            tilt: angle.to_degrees(),
+            ..north_up
+        }
+    }
+}
+
+/// Written as `lat,lon,heading,radius,tilt`, short enough to share.
+impl fmt::Display for View {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.5},{:.5},{:.1},{:.4},{:.1}",
+            self.lat, self.lon, self.heading, self.radius, self.tilt
+        )
+    }
+}
+
+impl FromStr for View {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut values = s.split(',').map(|value| value.trim().parse::<f32>());
+        let mut next = || match values.next() {
+            Some(Ok(value)) if value.is_finite() => Ok(value),
+            _ => Err(()),
+        };
+
+        let view = View {
+            lat: next()?,
+            lon: next()?,
+            heading: next()?,
+            radius: next()?,
+            tilt: next()?,
+        };
+
+        if values.next().is_some() {
+            return Err(());
+        }
+
+        Ok(view)
+    }
+}
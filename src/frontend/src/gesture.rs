@@ -0,0 +1,307 @@
+//! Tells what the fingers on the screen are doing from where they are, apart
+//! from winit's events so that gestures can be recognized on their own.
+
+use std::collections::BTreeMap;
+
+use glam::Vec2;
+use web_time::{Duration, Instant};
+
+/// Pixels two fingers move before it is decided whether they tilt the
+/// camera or pinch and twist.
+const TWO_FINGER_SLOP: f32 = 10.;
+/// Time over which the speed of a finger is averaged, and past which a
+/// finger held still before it is lifted no longer flings the globe.
+const FLING_WINDOW: Duration = Duration::from_millis(100);
+/// Pixels per second a finger has to move at as it is lifted to fling.
+const MIN_FLING_SPEED: f32 = 200.;
+
+/// What the fingers did since they last moved.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gesture {
+    /// One finger moved between two points, in pixels.
+    Drag { from: Vec2, to: Vec2 },
+    /// One finger was lifted while moving at `velocity`, in pixels per
+    /// second.
+    Fling { velocity: Vec2 },
+    /// Two fingers moved apart to `scale` times their distance, about the
+    /// point between them.
+    Pinch { center: Vec2, scale: f32 },
+    /// Two fingers turned clockwise on the screen by `angle` radians.
+    Twist { angle: f32 },
+    /// Two fingers side by side moved up the screen by `distance` pixels.
+    Tilt { distance: f32 },
+}
+
+/// What two fingers are taken to be doing once they moved far enough.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum TwoFingers {
+    #[default]
+    Undecided,
+    Tilt,
+    PinchAndTwist,
+}
+
+#[derive(Debug, Default)]
+pub struct GestureRecognizer {
+    touches: BTreeMap<u64, Vec2>,
+    /// Where two fingers were as the second was put down.
+    pair_start: Option<[Vec2; 2]>,
+    two_fingers: TwoFingers,
+    /// Averaged velocity of a single finger, and when it last moved.
+    velocity: Vec2,
+    last_move: Option<Instant>,
+}
+
+impl GestureRecognizer {
+    pub fn fingers(&self) -> usize {
+        self.touches.len()
+    }
+
+    /// A finger with `id` was put down at `position`.
+    pub fn start(&mut self, id: u64, position: Vec2) {
+        self.touches.insert(id, position);
+        self.restart();
+    }
+
+    /// The finger with `id` moved to `position` at `time`.
+    pub fn move_to(&mut self, id: u64, position: Vec2, time: Instant) -> Vec<Gesture> {
+        let before = self.pair();
+
+        let Some(previous) = self
+            .touches
+            .get_mut(&id)
+            .map(|touch| std::mem::replace(touch, position))
+        else {
+            return Vec::new();
+        };
+
+        match (before, self.pair()) {
+            (Some(before), Some(after)) => self.move_pair(before, after),
+            (None, None) if self.touches.len() == 1 => {
+                self.track_velocity(position - previous, time);
+
+                vec![Gesture::Drag {
+                    from: previous,
+                    to: position,
+                }]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The finger with `id` was lifted at `time`, flinging the globe if it
+    /// was the only one and still moving.
+    pub fn end(&mut self, id: u64, time: Instant) -> Option<Gesture> {
+        let single = self.touches.len() == 1;
+
+        self.touches.remove(&id)?;
+
+        let moving = self
+            .last_move
+            .is_some_and(|last_move| time.duration_since(last_move) < FLING_WINDOW);
+        let velocity = self.velocity;
+
+        self.restart();
+
+        (single && moving && velocity.length() >= MIN_FLING_SPEED)
+            .then_some(Gesture::Fling { velocity })
+    }
+
+    /// The finger with `id` left without a gesture, as when the browser
+    /// took it over.
+    pub fn cancel(&mut self, id: u64) {
+        self.touches.remove(&id);
+        self.restart();
+    }
+
+    /// Starts telling gestures apart anew, as fingers are put down or
+    /// lifted.
+    fn restart(&mut self) {
+        self.pair_start = self.pair();
+        self.two_fingers = TwoFingers::Undecided;
+        self.velocity = Vec2::ZERO;
+        self.last_move = None;
+    }
+
+    fn pair(&self) -> Option<[Vec2; 2]> {
+        let mut touches = self.touches.values().copied();
+
+        match (touches.next(), touches.next(), touches.next()) {
+            (Some(a), Some(b), None) => Some([a, b]),
+            _ => None,
+        }
+    }
+
+    fn track_velocity(&mut self, moved: Vec2, time: Instant) {
+        if let Some(last_move) = self.last_move {
+            let elapsed = time.duration_since(last_move).as_secs_f32();
+
+            if elapsed > 0. {
+                let t = (elapsed / FLING_WINDOW.as_secs_f32()).min(1.);
+
+                self.velocity = self.velocity.lerp(moved / elapsed, t);
+            }
+        }
+
+        self.last_move = Some(time);
+    }
+
+    fn move_pair(&mut self, [a0, b0]: [Vec2; 2], [a1, b1]: [Vec2; 2]) -> Vec<Gesture> {
+        let (mut a0, mut b0) = (a0, b0);
+
+        if self.two_fingers == TwoFingers::Undecided {
+            let Some([a, b]) = self.pair_start else {
+                return Vec::new();
+            };
+
+            let (moved_a, moved_b) = (a1 - a, b1 - b);
+
+            if moved_a.length().max(moved_b.length()) < TWO_FINGER_SLOP {
+                return Vec::new();
+            }
+
+            // Fingers side by side, both moving up or both down
+            let side_by_side = (b1 - a1).x.abs() > (b1 - a1).y.abs();
+            let vertical = |moved: Vec2| moved.y.abs() > moved.x.abs();
+            let together = moved_a.y * moved_b.y > 0. && vertical(moved_a) && vertical(moved_b);
+
+            self.two_fingers = if side_by_side && together {
+                TwoFingers::Tilt
+            } else {
+                TwoFingers::PinchAndTwist
+            };
+
+            // What the fingers did while it was undecided
+            (a0, b0) = (a, b);
+        }
+
+        match self.two_fingers {
+            TwoFingers::Undecided => Vec::new(),
+            TwoFingers::Tilt => vec![Gesture::Tilt {
+                distance: ((a0 + b0) - (a1 + b1)).y / 2.,
+            }],
+            TwoFingers::PinchAndTwist => {
+                let (before, after) = (b0 - a0, b1 - a1);
+
+                if before == Vec2::ZERO || after == Vec2::ZERO {
+                    return Vec::new();
+                }
+
+                // The screen's Y axis points down, so turning towards it is
+                // turning clockwise
+                vec![
+                    Gesture::Pinch {
+                        center: (a1 + b1) / 2.,
+                        scale: after.length() / before.length(),
+                    },
+                    Gesture::Twist {
+                        angle: before.angle_to(after),
+                    },
+                ]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    #[test]
+    fn one_finger_drags_and_flings() {
+        let mut gestures = GestureRecognizer::default();
+        let start = Instant::now();
+
+        gestures.start(0, Vec2::ZERO);
+
+        for step in 1..=5 {
+            let time = start + Duration::from_millis(16 * step);
+            let position = Vec2::new(10. * step as f32, 0.);
+
+            assert_eq!(
+                gestures.move_to(0, position, time),
+                vec![Gesture::Drag {
+                    from: position - Vec2::new(10., 0.),
+                    to: position
+                }]
+            );
+        }
+
+        let Some(Gesture::Fling { velocity }) = gestures.end(0, start + Duration::from_millis(90))
+        else {
+            panic!("no fling");
+        };
+
+        assert!(velocity.x > MIN_FLING_SPEED && velocity.y == 0.);
+    }
+
+    #[test]
+    fn finger_held_still_does_not_fling() {
+        let mut gestures = GestureRecognizer::default();
+        let start = Instant::now();
+
+        gestures.start(0, Vec2::ZERO);
+        gestures.move_to(0, Vec2::new(10., 0.), start);
+        gestures.move_to(0, Vec2::new(20., 0.), start + Duration::from_millis(16));
+
+        assert_eq!(gestures.end(0, start + Duration::from_millis(500)), None);
+    }
+
+    #[test]
+    fn fingers_side_by_side_moving_up_tilt() {
+        let mut gestures = GestureRecognizer::default();
+        let time = Instant::now();
+
+        gestures.start(0, Vec2::new(100., 300.));
+        gestures.start(1, Vec2::new(200., 300.));
+
+        assert!(gestures.move_to(0, Vec2::new(100., 295.), time).is_empty());
+        assert!(gestures.move_to(1, Vec2::new(200., 295.), time).is_empty());
+
+        // Past the slop, what was held back is reported at once
+        assert_eq!(
+            gestures.move_to(0, Vec2::new(100., 285.), time),
+            vec![Gesture::Tilt { distance: 10. }]
+        );
+        assert_eq!(
+            gestures.move_to(1, Vec2::new(200., 285.), time),
+            vec![Gesture::Tilt { distance: 5. }]
+        );
+    }
+
+    #[test]
+    fn fingers_turning_and_spreading_twist_and_pinch() {
+        let mut gestures = GestureRecognizer::default();
+        let time = Instant::now();
+
+        gestures.start(0, Vec2::new(100., 100.));
+        gestures.start(1, Vec2::new(200., 100.));
+
+        // The second finger swings down below the first, twice as far away
+        let gesture = gestures.move_to(1, Vec2::new(100., 300.), time);
+
+        let [Gesture::Pinch { center, scale }, Gesture::Twist { angle }] = gesture[..] else {
+            panic!("not a pinch and twist: {gesture:?}");
+        };
+
+        assert_eq!(center, Vec2::new(100., 200.));
+        assert!((scale - 2.).abs() < 1e-5);
+        assert!((angle - FRAC_PI_2).abs() < 1e-5);
+    }
+
+    #[test]
+    fn two_fingers_do_not_fling() {
+        let mut gestures = GestureRecognizer::default();
+        let start = Instant::now();
+
+        gestures.start(0, Vec2::ZERO);
+        gestures.start(1, Vec2::new(100., 0.));
+        gestures.move_to(0, Vec2::new(50., 0.), start);
+        gestures.move_to(0, Vec2::new(80., 0.), start + Duration::from_millis(16));
+
+        assert_eq!(gestures.end(1, start + Duration::from_millis(20)), None);
+        assert_eq!(gestures.end(0, start + Duration::from_millis(20)), None);
+    }
+}
@@ -1,12 +1,18 @@
+use std::cell::OnceCell;
+
+use app::CustomEvent;
 use wasm_bindgen::prelude::*;
 use web_sys::js_sys;
+use winit::event_loop::EventLoopProxy;
 
 pub mod state;
 pub mod types;
 pub use state::*;
 mod app;
 pub mod camera;
+pub mod gesture;
 pub mod utils;
+pub mod views;
 
 #[cfg(feature = "debug")]
 fn init_debug() {
@@ -21,6 +27,53 @@ extern "C" {
     fn handle_new_perf_data(data: js_sys::Map);
 
     fn get_subdivision_level() -> js_sys::Number;
+
+    /// Moves the page's subdivision control, which the level is read from.
+    fn set_subdivision_level(level: u32);
+
+    fn get_offline_database() -> js_sys::Promise;
+
+    fn get_remote_database() -> Option<String>;
+
+    /// Shows the aggregate of a selected region as returned by the server,
+    /// or that it could not be computed.
+    fn show_region_aggregate(result: Option<String>);
+
+    /// Shows the latitude and longitude under the cursor with the values of
+    /// the shown layers there, or nothing when the cursor is off the globe.
+    fn handle_cursor_readout(data: Option<js_sys::Map>);
+
+    /// Turns the page's compass to the degrees clockwise from north that
+    /// the top of the screen faces.
+    fn handle_heading(heading: f32);
+}
+
+thread_local! {
+    static PROXY: OnceCell<EventLoopProxy<CustomEvent>> = const { OnceCell::new() };
+}
+
+/// Lets functions called by the page reach the event loop.
+#[cfg(target_arch = "wasm32")]
+fn connect_to_page(proxy: EventLoopProxy<CustomEvent>) {
+    PROXY.with(|cell| {
+        let _ = cell.set(proxy);
+    });
+}
+
+/// Sends `event` from a function called by the page to the event loop.
+pub(crate) fn send_to_app(event: CustomEvent) {
+    PROXY.with(|proxy| {
+        if let Some(proxy) = proxy.get() {
+            let _ = proxy.send_event(event);
+        }
+    });
+}
+
+/// Raises the terrain, when it is shown, by `exaggeration` times its true
+/// height.
+#[wasm_bindgen]
+pub fn set_terrain_exaggeration(exaggeration: f32) {
+    send_to_app(CustomEvent::SetTerrainExaggeration(exaggeration));
 }
 
 pub fn safe_get_subdivision_level() -> Option<usize> {
@@ -36,14 +89,28 @@ pub fn safe_get_subdivision_level() -> Option<usize> {
     Some(n as usize)
 }
 
+/// The database the page loaded for offline use, if any.
+pub async fn load_offline_database() -> Option<Vec<u8>> {
+    let buffer = wasm_bindgen_futures::JsFuture::from(get_offline_database())
+        .await
+        .ok()?;
+
+    if buffer.is_null() || buffer.is_undefined() {
+        return None;
+    }
+
+    Some(js_sys::Uint8Array::new(&buffer).to_vec())
+}
+
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen(start)]
 pub async fn run() {
-    use app::{App, CustomEvent};
+    use app::App;
     use winit::{event_loop::EventLoop, platform::web::EventLoopExtWebSys};
 
     let event_loop = EventLoop::<CustomEvent>::with_user_event().build().unwrap();
     let proxy_event_loop = event_loop.create_proxy();
+    connect_to_page(event_loop.create_proxy());
     event_loop.set_control_flow(winit::event_loop::ControlFlow::Wait);
 
     let app = App::_new(proxy_event_loop);
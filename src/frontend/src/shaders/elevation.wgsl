@@ -0,0 +1,94 @@
+// Shared by the globe and the lines drawn on it, so that both are raised
+// by the same terrain. Prepended to the shaders using it.
+
+struct Metadata {
+    tiles: array<TileMetadata, 256>,
+}
+
+struct TileMetadata {
+    nw_lat: f32,
+    nw_lon: f32,
+    se_lat: f32,
+    se_lon: f32,
+    width: u32,
+    height: u32,
+    level: u32,
+    data_type: u32,
+}
+
+@group(1) @binding(8) var t_elevation: texture_2d_array<f32>;
+@group(1) @binding(9) var<uniform> elevation_metadata: Metadata;
+// The exaggeration of the terrain in x, or 0 when it is flat
+@group(1) @binding(10) var<uniform> terrain: vec4<f32>;
+
+// Radius of the globe in meters, which elevations are divided by
+const EARTH_RADIUS: f32 = 6371009.0;
+const ELEVATION_LAYERS: u32 = 64u;
+
+// `position` raised along the normal by the exaggerated elevation below
+// it, in radii of the globe, or as it is when the terrain is flat
+fn raise_by_terrain(position: vec3<f32>) -> vec3<f32> {
+    if (terrain.x <= 0.0) {
+        return position;
+    }
+
+    let normal = normalize(position);
+    let lon = degrees(atan2(-normal.x, normal.y));
+    let lat = degrees(asin(-normal.z));
+
+    let layer = elevation_tile(lat, lon);
+
+    if (layer < 0) {
+        return position;
+    }
+
+    return position + normal * sample_elevation(layer, lat, lon) * terrain.x / EARTH_RADIUS;
+}
+
+// The layer of the most detailed elevation tile covering `lat` and `lon`,
+// in degrees, or -1 if there is none
+fn elevation_tile(lat: f32, lon: f32) -> i32 {
+    var best = -1;
+    var best_level = 0u;
+
+    for (var layer = 0u; layer < ELEVATION_LAYERS; layer++) {
+        let tile = elevation_metadata.tiles[layer];
+
+        // Empty layers have no size
+        if (tile.width == 0u || lat > tile.nw_lat || lat < tile.se_lat || lon < tile.nw_lon || lon > tile.se_lon) {
+            continue;
+        }
+
+        if (best < 0 || tile.level >= best_level) {
+            best = i32(layer);
+            best_level = tile.level;
+        }
+    }
+
+    return best;
+}
+
+fn load_elevation(layer: i32, texel: vec2<i32>, size: vec2<i32>) -> f32 {
+    return textureLoad(t_elevation, clamp(texel, vec2<i32>(0), size - 1), layer, 0).r;
+}
+
+// The elevation in meters at `lat` and `lon`, in degrees, interpolated
+// between the texels of the tile in `layer`
+fn sample_elevation(layer: i32, lat: f32, lon: f32) -> f32 {
+    let tile = elevation_metadata.tiles[layer];
+    let size = vec2<i32>(i32(tile.width), i32(tile.height));
+
+    // Rows run from north to south, and texels are sampled at their centers
+    let uv = vec2<f32>(
+        (lon - tile.nw_lon) / (tile.se_lon - tile.nw_lon),
+        (tile.nw_lat - lat) / (tile.nw_lat - tile.se_lat),
+    );
+    let texel = uv * vec2<f32>(size) - 0.5;
+    let base = vec2<i32>(floor(texel));
+    let t = fract(texel);
+
+    let north = mix(load_elevation(layer, base, size), load_elevation(layer, base + vec2<i32>(1, 0), size), t.x);
+    let south = mix(load_elevation(layer, base + vec2<i32>(0, 1), size), load_elevation(layer, base + vec2<i32>(1, 1), size), t.x);
+
+    return mix(north, south, t.y);
+}
@@ -0,0 +1,34 @@
+struct Camera {
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_lines(
+    model: VertexInput,
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    // Raised with the globe, so that lines stay on top of the terrain
+    out.clip_position = camera.view_proj * vec4<f32>(raise_by_terrain(model.position), 1.0);
+    out.color = model.color;
+
+    return out;
+}
+
+@fragment
+fn fs_lines(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -22,28 +22,15 @@ fn vs_main(
 ) -> VertexOutput {
     var out: VertexOutput;
 
-    let world_position = vec4<f32>(model.position, 1.0);
+    let position = raise_by_terrain(model.position);
+
+    let world_position = vec4<f32>(position, 1.0);
     out.pos = vec3<f32>(world_position.x, world_position.y, world_position.z);
     out.clip_position = camera.view_proj * world_position;
 
     return out;
 }
 
-struct Metadata {
-    tiles: array<TileMetadata, 256>,
-}
-
-struct TileMetadata {
-    nw_lat: f32,
-    nw_lon: f32,
-    se_lat: f32,
-    se_lon: f32,
-    width: u32,
-    height: u32,
-    level: u32,
-    data_type: u32,
-}
-
 
 @group(1) @binding(0) var t_diffuse: texture_2d_array<f32>; 
 @group(1) @binding(1) var s_diffuse: sampler;
@@ -52,7 +39,55 @@ struct TileMetadata {
 @group(1) @binding(4) var<uniform> metadata: Metadata;
 @group(1) @binding(5) var<uniform> metadata_2: Metadata;
 @group(1) @binding(6) var<uniform> shader_mode: vec4<u32>;
+@group(1) @binding(7) var<uniform> query_poi: QueryPoi;
+
+// Light from the northwest, as is usual for shaded relief, in east, north
+// and up along the surface
+const TERRAIN_LIGHT: vec3<f32> = vec3<f32>(-0.57735, 0.57735, 0.57735);
+
+struct QueryPoi {
+    center: vec3<f32>,
+    cos_radius: f32,
+    // 0 without a selection, 1 for a circle and 2 for a polygon
+    mode: u32,
+    vertex_count: u32,
+    // Longitude and latitude in degrees
+    vertices: array<vec4<f32>, 32>,
+}
+
+const HIGHLIGHT: vec4<f32> = vec4<f32>(1.0, 0.6, 0.1, 1.0);
+
+fn in_selection(pos: vec3<f32>, lat: f32, lon: f32) -> bool {
+    if (query_poi.mode == 1u) {
+        return dot(pos, query_poi.center) >= query_poi.cos_radius;
+    }
+
+    if (query_poi.mode != 2u || query_poi.vertex_count < 3u) {
+        return false;
+    }
+
+    // Polygons reaching across the antimeridian are unwrapped past ±180°
+    return in_polygon(lat, lon) || in_polygon(lat, lon - 360.0) || in_polygon(lat, lon + 360.0);
+}
+
+fn in_polygon(lat: f32, lon: f32) -> bool {
+    // Even-odd rule, counting the edges crossed going east
+    var inside = false;
+    var j = query_poi.vertex_count - 1u;
+
+    for (var i = 0u; i < query_poi.vertex_count; i++) {
+        let a = query_poi.vertices[i].xy;
+        let b = query_poi.vertices[j].xy;
 
+        if ((a.y > lat) != (b.y > lat)) && (lon < (b.x - a.x) * (lat - a.y) / (b.y - a.y) + a.x) {
+            inside = !inside;
+        }
+
+        j = i;
+    }
+
+    return inside;
+}
 
 struct SampledTexture{
     highest_z: u32,
@@ -120,12 +155,42 @@ fn calc_uv(lat:f32, lon:f32, tile:TileMetadata)->vec2<f32>{
     let v = 1.-(lat - tile.se_lat) / (tile.nw_lat - tile.se_lat);
 
 
-    let scaled_u = u * f32(tile.width)/256.;
-    let scaled_v = v * f32(tile.height)/256.;
+    // Tiles are padded to the size of the texture layers
+    let size = vec2<f32>(textureDimensions(t_diffuse));
+
+    let scaled_u = u * f32(tile.width)/size.x;
+    let scaled_v = v * f32(tile.height)/size.y;
     return vec2<f32>(scaled_u,scaled_v);
 }
 
 
+// How brightly the terrain at `lat` and `lon`, in degrees, is lit, where
+// flat ground is 1, from the slopes of the exaggerated elevation a texel
+// to either side
+fn terrain_shade(lat: f32, lon: f32) -> f32 {
+    let layer = elevation_tile(lat, lon);
+
+    if (layer < 0) {
+        return 1.0;
+    }
+
+    let tile = elevation_metadata.tiles[layer];
+    let step = vec2<f32>(
+        (tile.se_lon - tile.nw_lon) / f32(tile.width),
+        (tile.nw_lat - tile.se_lat) / f32(tile.height),
+    );
+
+    let meters_per_degree = EARTH_RADIUS * PI / 180.0;
+    let east = (sample_elevation(layer, lat, lon + step.x) - sample_elevation(layer, lat, lon - step.x))
+        / (2.0 * step.x * meters_per_degree * max(cos(radians(lat)), 0.01));
+    let north = (sample_elevation(layer, lat + step.y, lon) - sample_elevation(layer, lat - step.y, lon))
+        / (2.0 * step.y * meters_per_degree);
+
+    let normal = normalize(vec3<f32>(-east * terrain.x, -north * terrain.x, 1.0));
+
+    return 0.25 + 0.75 * max(dot(normal, TERRAIN_LIGHT), 0.0) / TERRAIN_LIGHT.z;
+}
+
 @fragment
 fn fs_tiles(in: VertexOutput) -> @location(0) vec4<f32> {
 
@@ -188,6 +253,16 @@ fn fs_tiles(in: VertexOutput) -> @location(0) vec4<f32> {
         return_color=return_color*0.03+lp_color;
     }
 
+    if (terrain.x > 0.0) {
+        let shade = terrain_shade((lat - 0.5) * 180.0, (lon - 0.5) * 360.0);
+
+        return_color = vec4<f32>(return_color.rgb * shade, return_color.a);
+    }
+
+    if (in_selection(pos, (lat - 0.5) * 180.0, (lon - 0.5) * 360.0)) {
+        return_color = mix(return_color, HIGHLIGHT, 0.35);
+    }
+
     return return_color;
 }
 
@@ -213,7 +288,6 @@ fn sample_gradient(i: f32, max_value:f32, gradient_index: u32)-> vec4<f32>{
     }
 
 
-
     const n_colors = 4.;
 
     let sample_location = (i/max_value)*(n_colors-1.);
@@ -227,10 +301,6 @@ fn sample_gradient(i: f32, max_value:f32, gradient_index: u32)-> vec4<f32>{
 }
 
 
-
-
-
-
 @fragment
 fn fs_wireframe(in: VertexOutput) -> @location(0) vec4<f32> {
     return vec4<f32>(in.pos, 1.0);
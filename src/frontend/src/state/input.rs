@@ -1,15 +1,62 @@
 use winit::{
     dpi::PhysicalPosition,
-    event::{KeyEvent, MouseButton, WindowEvent},
-    keyboard::{KeyCode, PhysicalKey},
+    event::{ElementState, KeyEvent, MouseButton, WindowEvent},
+    keyboard::PhysicalKey,
     window::CursorIcon,
 };
 
-use super::State;
+use crate::camera::OrbitMode;
+
+use super::{Action, State};
 
 impl State {
     pub fn input(&mut self, event: &WindowEvent) {
         match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+
+                // The polygon is closed by letting go of Ctrl
+                if !self.modifiers.control_key() {
+                    if let Some(region) = self.earth_state.query_poi.end_polygon() {
+                        self.query_region(region);
+                    }
+                }
+            }
+
+            // Shift and drag to select a circle
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } if self.modifiers.shift_key() => {
+                if let Some(point) = self.cursor_on_globe() {
+                    self.earth_state.query_poi.start_circle(point);
+                    self.window.request_redraw();
+                }
+            }
+
+            WindowEvent::MouseInput {
+                state: ElementState::Released,
+                button: MouseButton::Left,
+                ..
+            } if self.earth_state.query_poi.is_dragging() => {
+                if let Some(region) = self.earth_state.query_poi.end_circle() {
+                    self.query_region(region);
+                }
+            }
+
+            // Ctrl and click to add the vertices of a polygon
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } if self.modifiers.control_key() => {
+                if let Some(point) = self.cursor_on_globe() {
+                    self.earth_state.query_poi.add_vertex(point);
+                    self.window.request_redraw();
+                }
+            }
+
             WindowEvent::MouseInput {
                 state,
                 button: MouseButton::Left,
@@ -34,11 +81,9 @@ impl State {
                 ..
             } => {
                 self.camera_state.controller.tilting = state.is_pressed();
-                // self.earth_state.query_poi.start_query_poi();
                 self.window.set_cursor(if state.is_pressed() {
                     CursorIcon::EResize
                 } else {
-                    // self.earth_state.query_poi.end_query_poi();
                     CursorIcon::Grab
                 });
             }
@@ -56,7 +101,13 @@ impl State {
                 ..
             } => {
                 self.camera_state.controller.process_cursor_moved(*x, *y);
-                // self.earth_state.query_poi.process_cursor_moved(*x, *y);
+
+                if self.earth_state.query_poi.is_dragging() {
+                    if let Some(point) = self.cursor_on_globe() {
+                        self.earth_state.query_poi.process_cursor_moved(point);
+                        self.window.request_redraw();
+                    }
+                }
 
                 if self.camera_state.controller.rotating || self.camera_state.controller.tilting {
                     self.window.request_redraw();
@@ -65,10 +116,14 @@ impl State {
                         .controller
                         .update_camera(web_time::Duration::ZERO);
                 }
+
+                self.publish_cursor_readout();
             }
 
             WindowEvent::MouseWheel { delta, .. } => {
-                self.camera_state.controller.process_mouse_wheel(delta);
+                let controller = &mut self.camera_state.controller;
+
+                controller.process_mouse_wheel(delta, controller.current_position);
                 self.window.request_redraw();
             }
 
@@ -76,33 +131,69 @@ impl State {
                 event:
                     KeyEvent {
                         state,
-                        physical_key: PhysicalKey::Code(KeyCode::KeyW),
+                        physical_key: PhysicalKey::Code(code),
+                        repeat,
                         ..
                     },
                 ..
             } => {
-                if state.is_pressed() {
-                    self.set_render_wireframe(!self.render_wireframe);
-                    self.window.request_redraw();
+                if let Some(action) = self.keymap.action(*code) {
+                    self.perform(action, state.is_pressed(), *repeat);
                 }
             }
 
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        state,
-                        physical_key: PhysicalKey::Code(KeyCode::KeyL),
-                        ..
-                    },
-                ..
-            } => {
-                if state.is_pressed() {
-                    self.earth_state
-                        .set_render_lp_map(!self.earth_state.render_lp_map, &self.queue);
-                    self.window.request_redraw();
-                }
+            // Keys released elsewhere would otherwise keep the camera moving
+            WindowEvent::Focused(false) => {
+                self.camera_state.controller.stop_movements();
             }
+
             _ => {}
         }
     }
+
+    /// Does what a key is bound to as it is `pressed` or released, where
+    /// `repeat` is set for presses repeated while the key is held.
+    fn perform(&mut self, action: Action, pressed: bool, repeat: bool) {
+        if let Action::Move(movement) = action {
+            self.camera_state
+                .controller
+                .process_movement(movement, pressed);
+            self.window.request_redraw();
+
+            return;
+        }
+
+        if !pressed || repeat {
+            return;
+        }
+
+        match action {
+            Action::Move(_) => {}
+            Action::ResetNorth => self.camera_state.controller.reset_north(),
+            Action::ToggleOrbitMode => {
+                let controller = &mut self.camera_state.controller;
+
+                controller.set_orbit_mode(match controller.orbit_mode {
+                    OrbitMode::Arcball => OrbitMode::NorthUp,
+                    OrbitMode::NorthUp => OrbitMode::Arcball,
+                });
+            }
+            Action::ToggleWireframe => self.set_render_wireframe(!self.render_wireframe),
+            Action::ToggleTerrain => self
+                .earth_state
+                .set_render_terrain(!self.earth_state.render_terrain, &self.queue),
+            Action::ToggleLightPollution => self
+                .earth_state
+                .set_render_lp_map(!self.earth_state.render_lp_map, &self.queue),
+            Action::ToggleVectors => self
+                .vector_state
+                .set_render_vectors(!self.vector_state.render_vectors),
+            Action::ToggleGraticule => self
+                .vector_state
+                .set_render_graticule(!self.vector_state.render_graticule),
+            Action::ClearSelection => self.earth_state.query_poi.cancel(),
+        }
+
+        self.window.request_redraw();
+    }
 }
@@ -0,0 +1,124 @@
+use std::{collections::HashMap, str::FromStr};
+
+use wasm_bindgen::prelude::*;
+use winit::keyboard::KeyCode;
+
+use crate::{app::CustomEvent, camera::Movement};
+
+/// What a key does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Moves the camera for as long as the key is held.
+    Move(Movement),
+    ResetNorth,
+    /// Switches between turning the globe freely and keeping north up.
+    ToggleOrbitMode,
+    ToggleWireframe,
+    ToggleLightPollution,
+    ToggleTerrain,
+    ToggleVectors,
+    ToggleGraticule,
+    ClearSelection,
+}
+
+impl FromStr for Action {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "rotate_left" => Action::Move(Movement::RotateLeft),
+            "rotate_right" => Action::Move(Movement::RotateRight),
+            "rotate_up" => Action::Move(Movement::RotateUp),
+            "rotate_down" => Action::Move(Movement::RotateDown),
+            "zoom_in" => Action::Move(Movement::ZoomIn),
+            "zoom_out" => Action::Move(Movement::ZoomOut),
+            "tilt_up" => Action::Move(Movement::TiltUp),
+            "tilt_down" => Action::Move(Movement::TiltDown),
+            "reset_north" => Action::ResetNorth,
+            "toggle_orbit_mode" => Action::ToggleOrbitMode,
+            "toggle_wireframe" => Action::ToggleWireframe,
+            "toggle_light_pollution" => Action::ToggleLightPollution,
+            "toggle_terrain" => Action::ToggleTerrain,
+            "toggle_vectors" => Action::ToggleVectors,
+            "toggle_graticule" => Action::ToggleGraticule,
+            "clear_selection" => Action::ClearSelection,
+            _ => return Err(()),
+        })
+    }
+}
+
+/// Which key does what. Keys are named as in the `code` of the browser's
+/// keyboard events, such as `KeyW` or `ArrowUp`, which is also how winit
+/// names its key codes.
+#[derive(Debug)]
+pub struct Keymap {
+    bindings: HashMap<String, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        use Movement::*;
+
+        let bindings = [
+            ("ArrowLeft", Action::Move(RotateLeft)),
+            ("KeyA", Action::Move(RotateLeft)),
+            ("ArrowRight", Action::Move(RotateRight)),
+            ("KeyD", Action::Move(RotateRight)),
+            ("ArrowUp", Action::Move(RotateUp)),
+            ("KeyW", Action::Move(RotateUp)),
+            ("ArrowDown", Action::Move(RotateDown)),
+            ("KeyS", Action::Move(RotateDown)),
+            ("Equal", Action::Move(ZoomIn)),
+            ("NumpadAdd", Action::Move(ZoomIn)),
+            ("Minus", Action::Move(ZoomOut)),
+            ("NumpadSubtract", Action::Move(ZoomOut)),
+            ("PageUp", Action::Move(TiltUp)),
+            ("PageDown", Action::Move(TiltDown)),
+            ("Home", Action::ResetNorth),
+            ("KeyO", Action::ToggleOrbitMode),
+            ("KeyF", Action::ToggleWireframe),
+            ("KeyL", Action::ToggleLightPollution),
+            ("KeyT", Action::ToggleTerrain),
+            ("KeyV", Action::ToggleVectors),
+            ("KeyG", Action::ToggleGraticule),
+            ("Escape", Action::ClearSelection),
+        ];
+
+        Self {
+            bindings: bindings
+                .into_iter()
+                .map(|(key, action)| (key.to_owned(), action))
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    pub fn action(&self, code: KeyCode) -> Option<Action> {
+        self.bindings.get(&format!("{code:?}")).copied()
+    }
+
+    /// Makes `key` do `action`, or nothing at all.
+    pub fn bind(&mut self, key: String, action: Option<Action>) {
+        match action {
+            Some(action) => self.bindings.insert(key, action),
+            None => self.bindings.remove(&key),
+        };
+    }
+}
+
+/// Makes the key with the given `code`, such as `KeyW`, do `action`, such as
+/// `rotate_up` or `toggle_graticule`, or nothing without one. Returns whether
+/// the action is known.
+#[wasm_bindgen]
+pub fn bind_key(code: String, action: Option<String>) -> bool {
+    let action = match action.as_deref().map(str::parse) {
+        Some(Ok(action)) => Some(action),
+        Some(Err(())) => return false,
+        None => None,
+    };
+
+    crate::send_to_app(CustomEvent::BindKey(code, action));
+
+    true
+}
@@ -1,15 +1,25 @@
 use std::sync::Arc;
 
-use crate::{app::CustomEvent, camera::CameraState, types::earth::EarthState};
+use crate::{
+    app::CustomEvent,
+    camera::{CameraState, View},
+    types::{earth::EarthState, vector::VectorState},
+};
 use depth_texture::DepthTexture;
+pub use keymap::{bind_key, Action, Keymap};
+use readout::CursorReadout;
 use touch::TouchState;
 use web_time::Duration;
 use wgpu::FragmentState;
-use winit::{event_loop::EventLoopProxy, window::Window};
+use winit::{event_loop::EventLoopProxy, keyboard::ModifiersState, window::Window};
 
 mod depth_texture;
 mod input;
+mod keymap;
+mod query;
+mod readout;
 mod touch;
+mod url;
 
 pub enum AnimationState {
     Animating,
@@ -26,12 +36,21 @@ pub struct State {
     pub window: Arc<Window>,
     pub texture_pipeline_globe: wgpu::RenderPipeline,
     pub wireframe_pipeline: wgpu::RenderPipeline,
+    pub line_pipeline: wgpu::RenderPipeline,
     pub render_wireframe: bool,
 
     pub touch_state: TouchState,
+    pub modifiers: ModifiersState,
+    pub keymap: Keymap,
+    /// Last told to the page, so that it is only told about changes.
+    cursor_readout: Option<CursorReadout>,
+    heading: Option<f32>,
+    /// Last written to the page's address, for the same reason.
+    url_hash: String,
 
     pub camera_state: CameraState,
     pub earth_state: EarthState,
+    pub vector_state: VectorState,
 
     pub delta: Duration,
     pub eventloop: EventLoopProxy<CustomEvent>,
@@ -81,7 +100,16 @@ impl State {
             surface.configure(&device, &config);
         }
 
-        let shader = device.create_shader_module(wgpu::include_wgsl!("../shaders/shader.wgsl"));
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("shader.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("../shaders/elevation.wgsl"),
+                    include_str!("../shaders/shader.wgsl")
+                )
+                .into(),
+            ),
+        });
 
         let surface_caps = surface.get_capabilities(&adapter);
 
@@ -105,6 +133,7 @@ impl State {
 
         let camera_state = CameraState::create(&device, &size);
         let earth_state = EarthState::create(&device, eventloop.clone());
+        let vector_state = VectorState::create(&device, eventloop.clone());
         let depth_texture = DepthTexture::create(&device, &config);
 
         let globe_pipeline_layout =
@@ -219,7 +248,74 @@ impl State {
             cache: None,
         });
 
-        Self {
+        let line_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("lines.wgsl"),
+            source: wgpu::ShaderSource::Wgsl(
+                concat!(
+                    include_str!("../shaders/elevation.wgsl"),
+                    include_str!("../shaders/lines.wgsl")
+                )
+                .into(),
+            ),
+        });
+
+        // Lines share the globe's textures, to be raised by its terrain
+        let line_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Line Pipeline Layout"),
+            bind_group_layouts: &[
+                &camera_state.bind_group_layout,
+                &earth_state.texture_bind_group_layout,
+            ],
+            push_constant_ranges: &[],
+        });
+
+        // Lines are tested against the globe's depth, so that lines on its
+        // far side are hidden, but do not write it themselves.
+        let line_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Line Pipeline"),
+            layout: Some(&line_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &line_shader,
+                entry_point: Some("vs_lines"),
+                buffers: &[VectorState::descriptor()],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(FragmentState {
+                module: &line_shader,
+                entry_point: Some("fs_lines"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DepthTexture::DEPTH_TEXTURE_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Less,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        let mut state = Self {
             eventloop,
             surface,
             device,
@@ -230,12 +326,23 @@ impl State {
             depth_texture,
             texture_pipeline_globe,
             wireframe_pipeline,
+            line_pipeline,
             touch_state: Default::default(),
+            modifiers: ModifiersState::empty(),
+            keymap: Keymap::default(),
+            cursor_readout: None,
+            heading: None,
+            url_hash: String::new(),
             earth_state,
+            vector_state,
             camera_state,
             delta: Duration::ZERO,
             render_wireframe: false,
-        }
+        };
+
+        state.restore_url_state();
+
+        state
     }
 
     pub fn window(&self) -> &Window {
@@ -262,10 +369,22 @@ impl State {
             &self.camera_state.controller.camera,
             &self.queue,
         );
+        self.vector_state.update_visible_tiles(
+            &self.camera_state.controller.projection,
+            &self.camera_state.controller.camera,
+        );
+        self.vector_state.update(&self.device);
 
-        if let AnimationState::Animating = self.camera_state.update(&self.queue, self.delta) {
-            self.window.request_redraw();
+        match self.camera_state.update(&self.queue, self.delta) {
+            AnimationState::Animating => self.window.request_redraw(),
+            // Written once the camera settles, as browsers limit how often
+            // the address may change
+            AnimationState::Finished => self.update_url_state(),
         }
+
+        crate::views::set_current_view(View::from_camera(&self.camera_state.controller.camera));
+        self.publish_cursor_readout();
+        self.publish_heading();
     }
 
     pub fn set_render_wireframe(&mut self, render_as_wireframe: bool) {
@@ -357,6 +476,38 @@ impl State {
             render_pass.draw_indexed(0..indices, 0, 0..1);
         }
 
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Line render"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                occlusion_query_set: None,
+                timestamp_writes: None,
+            });
+
+            render_pass.set_pipeline(&self.line_pipeline);
+
+            self.camera_state.render(&mut render_pass);
+            self.earth_state.bind_textures(&mut render_pass);
+            let vertices = self.vector_state.render(&mut render_pass);
+
+            render_pass.draw(0..vertices, 0..1);
+        }
+
         self.queue.submit(std::iter::once(encoder.finish()));
         output.present();
 
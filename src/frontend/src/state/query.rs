@@ -0,0 +1,36 @@
+use glam::Vec3;
+
+use crate::types::Region;
+
+use super::State;
+
+impl State {
+    /// The point on the globe under the cursor, on the unit sphere.
+    pub fn cursor_on_globe(&self) -> Option<Vec3> {
+        self.camera_state.controller.cursor_on_globe
+    }
+
+    /// Sends `region` for the aggregate of the light pollution within it,
+    /// which the page shows once it arrives.
+    pub fn query_region(&self, region: Region) {
+        wasm_bindgen_futures::spawn_local(async move {
+            let request = match &region {
+                Region::Circle(circle) => {
+                    gloo_net::http::Request::post("/aggregate/lp/circle").json(circle)
+                }
+                Region::Polygon(polygon) => {
+                    gloo_net::http::Request::post("/aggregate/lp").json(polygon)
+                }
+            };
+
+            let response = request.expect("serializable region").send().await;
+
+            let result = match response {
+                Ok(response) if response.ok() => response.text().await.ok(),
+                _ => None,
+            };
+
+            crate::show_region_aggregate(result);
+        });
+    }
+}
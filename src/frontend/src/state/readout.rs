@@ -0,0 +1,93 @@
+use wasm_bindgen::JsValue;
+use web_sys::js_sys;
+
+use crate::types::convert_point_on_surface_to_lat_lon;
+
+use super::State;
+
+/// Degrees the heading has to change by for the page to be told about it.
+const HEADING_STEP: f32 = 0.1;
+
+/// What the page is told about the point under the cursor.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CursorReadout {
+    lat: f32,
+    lon: f32,
+    satellite: Option<[u8; 4]>,
+    light_pollution: Option<f32>,
+    elevation: Option<f32>,
+}
+
+impl CursorReadout {
+    fn to_map(self) -> js_sys::Map {
+        let map = js_sys::Map::new();
+        let js_str = |s: &str| JsValue::from_str(s);
+        let js_f32 = |s: f32| JsValue::from_f64(s as f64);
+
+        map.set(&js_str("lat"), &js_f32(self.lat));
+        map.set(&js_str("lon"), &js_f32(self.lon));
+
+        if let Some([r, g, b, _]) = self.satellite {
+            map.set(
+                &js_str("satellite"),
+                &js_str(&format!("#{r:02x}{g:02x}{b:02x}")),
+            );
+        }
+
+        if let Some(light_pollution) = self.light_pollution {
+            map.set(&js_str("light_pollution"), &js_f32(light_pollution));
+        }
+
+        if let Some(elevation) = self.elevation {
+            map.set(&js_str("elevation"), &js_f32(elevation));
+        }
+
+        map
+    }
+}
+
+impl State {
+    /// Tells the page where the cursor is on the globe and the values of
+    /// the shown layers there, or that it is off the globe, whenever that
+    /// changed since it was last told.
+    pub fn publish_cursor_readout(&mut self) {
+        let readout = self.cursor_on_globe().map(|point| {
+            let coord = convert_point_on_surface_to_lat_lon(point);
+
+            CursorReadout {
+                lat: coord.y,
+                lon: coord.x,
+                satellite: self.earth_state.sample_satellite(coord),
+                light_pollution: self.earth_state.sample_light_pollution(coord),
+                elevation: self.earth_state.sample_elevation(coord),
+            }
+        });
+
+        if readout == self.cursor_readout {
+            return;
+        }
+
+        self.cursor_readout = readout;
+
+        crate::handle_cursor_readout(readout.map(CursorReadout::to_map));
+    }
+
+    /// Tells the page which way the top of the screen faces, for its
+    /// compass, whenever that changed noticeably since it was last told.
+    pub fn publish_heading(&mut self) {
+        let heading = self.camera_state.controller.camera.heading();
+
+        if let Some(last) = self.heading {
+            // Headings just either side of south are close together
+            let change = (heading - last + 180.).rem_euclid(360.) - 180.;
+
+            if change.abs() < HEADING_STEP {
+                return;
+            }
+        }
+
+        self.heading = Some(heading);
+
+        crate::handle_heading(heading);
+    }
+}
@@ -1,97 +1,69 @@
-use std::collections::HashMap;
+use glam::Vec2;
+use web_time::Instant;
+use winit::event::{Touch, TouchPhase, WindowEvent};
 
-use winit::event::{MouseScrollDelta, Touch, TouchPhase, WindowEvent};
+use crate::gesture::{Gesture, GestureRecognizer};
 
 use super::State;
 
 #[derive(Default, Debug)]
 pub struct TouchState {
-    touches: HashMap<u64, (f64, f64)>,
-    distance: Option<f64>,
+    gestures: GestureRecognizer,
 }
 
 impl State {
     pub fn touch(&mut self, event: &WindowEvent) {
-        match event {
-            WindowEvent::Touch(Touch {
-                phase: TouchPhase::Started,
-                location,
-                id,
-                ..
-            }) => {
-                self.camera_state.controller.rotating = false;
+        let WindowEvent::Touch(Touch {
+            phase,
+            location,
+            id,
+            ..
+        }) = event
+        else {
+            return;
+        };
 
-                self.touch_state
-                    .touches
-                    .insert(*id, (location.x, location.y));
-            }
+        let position = Vec2::new(location.x as f32, location.y as f32);
+        let gestures = &mut self.touch_state.gestures;
+        let controller = &mut self.camera_state.controller;
 
-            WindowEvent::Touch(Touch {
-                phase: TouchPhase::Moved,
-                location,
-                id,
-                ..
-            }) => {
-                if self.touch_state.touches.len() == 1 {
-                    self.camera_state.controller.rotating = true;
-                }
+        let gestures = match phase {
+            TouchPhase::Started => {
+                gestures.start(*id, position);
 
-                self.touch_state
-                    .touches
-                    .insert(*id, (location.x, location.y));
-            }
+                controller.rotating = false;
+                controller.stop_fling();
 
-            WindowEvent::Touch(Touch {
-                phase: TouchPhase::Ended,
-                id,
-                ..
-            }) => {
-                self.touch_state.touches.remove(id);
+                if gestures.fingers() == 1 {
+                    controller.process_cursor_moved(location.x, location.y);
+                }
 
-                self.camera_state.controller.rotating = false;
+                Vec::new()
+            }
+            TouchPhase::Moved => gestures.move_to(*id, position, Instant::now()),
+            TouchPhase::Ended => {
+                controller.rotating = false;
 
-                if self.touch_state.touches.len() < 2 {
-                    self.touch_state.distance = None;
-                }
+                gestures.end(*id, Instant::now()).into_iter().collect()
             }
+            TouchPhase::Cancelled => {
+                controller.rotating = false;
+                gestures.cancel(*id);
 
-            _ => return,
+                Vec::new()
+            }
         };
 
-        self.window.request_redraw();
-
-        if self.touch_state.touches.len() == 2 {
-            let touch_points: Vec<_> = self.touch_state.touches.values().collect();
-            let (x1, y1) = touch_points[0];
-            let (x2, y2) = touch_points[1];
-
-            let distance = ((x2 - x1).powi(2) + (y2 - y1).powi(2)).sqrt();
-
-            if let Some(last_distance) = self.touch_state.distance {
-                let zoom_factor = distance / last_distance;
-
-                if (zoom_factor - 1.0).abs() > 0.01 {
-                    let distance = if zoom_factor > 1.0 {
-                        distance
-                    } else {
-                        -distance
-                    };
-
-                    self.camera_state
-                        .controller
-                        .process_mouse_wheel(&MouseScrollDelta::LineDelta(0., distance as f32));
-                }
+        for gesture in gestures {
+            match gesture {
+                Gesture::Drag { from, to } => controller.process_touch_drag(from, to),
+                Gesture::Fling { velocity } => controller.process_fling(velocity),
+                Gesture::Pinch { center, scale } => controller.process_pinch(center, scale),
+                Gesture::Twist { angle } => controller.process_twist(angle),
+                Gesture::Tilt { distance } => controller.process_touch_tilt(distance),
             }
-
-            self.touch_state.distance = Some(distance);
         }
 
-        if self.touch_state.touches.len() == 1 {
-            let touch_point = self.touch_state.touches.values().next().unwrap();
-
-            self.camera_state
-                .controller
-                .process_cursor_moved(touch_point.0, touch_point.1);
-        }
+        self.window.request_redraw();
     }
 }
@@ -0,0 +1,222 @@
+use wasm_bindgen::JsValue;
+
+use crate::camera::View;
+
+use super::State;
+
+/// Names of the layers that can be toggled, as they appear in the address.
+const LIGHT_POLLUTION: &str = "lp";
+const TERRAIN: &str = "terrain";
+const VECTORS: &str = "vectors";
+const GRATICULE: &str = "graticule";
+const WIREFRAME: &str = "wireframe";
+
+/// The view, shown layers and subdivision level, kept in the hash of the
+/// page's address as `view=<view>&layers=<names>&subdivision=<level>` so
+/// that what is on screen can be shared by sharing the address.
+#[derive(Debug, Default, PartialEq)]
+struct UrlState {
+    view: Option<View>,
+    layers: Option<Vec<String>>,
+    subdivision: Option<usize>,
+}
+
+impl UrlState {
+    /// Reads `hash`, without its `#`, skipping anything it can not read.
+    fn parse(hash: &str) -> Self {
+        let mut state = UrlState::default();
+
+        for (key, value) in hash.split('&').filter_map(|pair| pair.split_once('=')) {
+            match key {
+                "view" => state.view = value.parse().ok(),
+                "layers" => {
+                    state.layers = Some(
+                        value
+                            .split(',')
+                            .filter(|name| !name.is_empty())
+                            .map(str::to_owned)
+                            .collect(),
+                    )
+                }
+                "subdivision" => state.subdivision = value.parse().ok(),
+                _ => {}
+            }
+        }
+
+        state
+    }
+
+    fn to_hash(&self) -> String {
+        let mut pairs = Vec::new();
+
+        if let Some(view) = self.view {
+            pairs.push(format!("view={view}"));
+        }
+
+        if let Some(layers) = &self.layers {
+            pairs.push(format!("layers={}", layers.join(",")));
+        }
+
+        if let Some(subdivision) = self.subdivision {
+            pairs.push(format!("subdivision={subdivision}"));
+        }
+
+        pairs.join("&")
+    }
+
+    fn has_layer(&self, name: &str) -> Option<bool> {
+        self.layers
+            .as_ref()
+            .map(|layers| layers.iter().any(|layer| layer == name))
+    }
+}
+
+impl State {
+    fn url_state(&self) -> UrlState {
+        let layers = [
+            (LIGHT_POLLUTION, self.earth_state.render_lp_map),
+            (TERRAIN, self.earth_state.render_terrain),
+            (VECTORS, self.vector_state.render_vectors),
+            (GRATICULE, self.vector_state.render_graticule),
+            (WIREFRAME, self.render_wireframe),
+        ];
+
+        UrlState {
+            view: Some(View::from_camera(&self.camera_state.controller.camera)),
+            layers: Some(
+                layers
+                    .into_iter()
+                    .filter(|(_, shown)| *shown)
+                    .map(|(name, _)| name.to_owned())
+                    .collect(),
+            ),
+            subdivision: Some(self.earth_state.subdivision_level()),
+        }
+    }
+
+    /// Restores what the address of the page says is on screen, leaving
+    /// anything it does not mention as it is. The view is clamped to the
+    /// distances the camera may zoom to, as when flying there.
+    pub fn restore_url_state(&mut self) {
+        let Some(hash) = web_sys::window().and_then(|window| window.location().hash().ok()) else {
+            return;
+        };
+
+        let state = UrlState::parse(hash.trim_start_matches('#'));
+
+        if let Some(view) = state.view {
+            self.camera_state.controller.fly_to(view, 0.);
+        }
+
+        if let Some(shown) = state.has_layer(LIGHT_POLLUTION) {
+            self.earth_state.set_render_lp_map(shown, &self.queue);
+        }
+
+        if let Some(shown) = state.has_layer(TERRAIN) {
+            self.earth_state.set_render_terrain(shown, &self.queue);
+        }
+
+        if let Some(shown) = state.has_layer(VECTORS) {
+            self.vector_state.set_render_vectors(shown);
+        }
+
+        if let Some(shown) = state.has_layer(GRATICULE) {
+            self.vector_state.set_render_graticule(shown);
+        }
+
+        if let Some(shown) = state.has_layer(WIREFRAME) {
+            self.set_render_wireframe(shown);
+        }
+
+        if let Some(subdivision) = state.subdivision {
+            crate::set_subdivision_level(subdivision as u32);
+        }
+
+        self.url_hash = state.to_hash();
+    }
+
+    /// Writes what is on screen to the address of the page, if it changed.
+    /// The entry in the history is replaced, so that going back leaves the
+    /// page instead of stepping through every movement of the camera.
+    pub fn update_url_state(&mut self) {
+        let hash = self.url_state().to_hash();
+
+        if hash == self.url_hash {
+            return;
+        }
+
+        let Some(history) = web_sys::window().and_then(|window| window.history().ok()) else {
+            return;
+        };
+
+        if history
+            .replace_state_with_url(&JsValue::NULL, "", Some(&format!("#{hash}")))
+            .is_ok()
+        {
+            self.url_hash = hash;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let state = UrlState {
+            view: Some(View {
+                lat: 52.37,
+                lon: 4.9,
+                heading: -12.5,
+                radius: 1.2,
+                tilt: 30.,
+            }),
+            layers: Some(vec![LIGHT_POLLUTION.to_owned(), GRATICULE.to_owned()]),
+            subdivision: Some(5),
+        };
+
+        assert_eq!(UrlState::parse(&state.to_hash()), state);
+
+        let empty = UrlState::default();
+        assert_eq!(empty.to_hash(), "");
+        assert_eq!(UrlState::parse(""), empty);
+    }
+
+    #[test]
+    fn empty_layers_hide_every_layer() {
+        let state = UrlState::parse("layers=");
+
+        assert_eq!(state.layers, Some(Vec::new()));
+        assert_eq!(state.has_layer(TERRAIN), Some(false));
+        assert_eq!(UrlState::parse("").has_layer(TERRAIN), None);
+    }
+
+    #[test]
+    fn skips_malformed_values() {
+        for hash in [
+            "view=1,2,3",
+            "view=1,2,3,4,5,6",
+            "view=NaN,0,0,1,0",
+            "view=0,inf,0,1,0",
+            "view=0,0,0,-inf,0",
+            "view=a,b,c,d,e",
+            "view",
+            "=view",
+            "subdivision=-1",
+            "subdivision=x",
+            "&&&",
+            "other=1&view=&subdivision=",
+        ] {
+            let state = UrlState::parse(hash);
+
+            assert_eq!(state.view, None, "{hash}");
+            assert_eq!(state.subdivision, None, "{hash}");
+        }
+
+        let state = UrlState::parse("view=1,2,x,4,5&subdivision=3&layers=terrain");
+        assert_eq!(state.view, None);
+        assert_eq!(state.subdivision, Some(3));
+        assert_eq!(state.has_layer(TERRAIN), Some(true));
+    }
+}
@@ -1,9 +1,13 @@
-use std::collections::HashMap;
+use std::{borrow::Cow, collections::HashMap, rc::Rc};
 
 use web_time::Instant;
 
-use common::{Bounds, TileMetadata, TileResponse};
-use geo::{coord, Coord, Rect};
+use bytemuck::Pod;
+use common::{
+    deserialize::GeoTree, Bounds, CompressedTileResponse, Layer, TileMetadata, TileResponse,
+    COMPRESSED_TILE_MEDIA_TYPE,
+};
+use geo::{coord, Coord, Intersects, Rect};
 use glam::{Quat, Vec3};
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
@@ -18,16 +22,39 @@ use winit::event_loop::EventLoopProxy;
 use crate::{
     app::CustomEvent,
     camera::{Camera, Projection},
-    utils::buffer::{BufferAllocator, BufferSlot, Level},
+    utils::{
+        buffer::{BufferAllocator, BufferSlot, Level},
+        remote::RemoteDatabase,
+    },
 };
 
-use super::Icosphere;
+use super::{Icosphere, QueryPoi};
 
 type Point = Vec3;
 
+/// Size of every texture layer. Tiles up to this size are supported,
+/// smaller tiles are padded.
 const TEXTURE_HEIGHT: u32 = 256;
 const TEXTURE_WIDTH: u32 = TEXTURE_HEIGHT;
 const BUFFER_SIZE: u32 = 256;
+/// Layers of the elevation texture, fewer than of the others as every
+/// vertex looks through them.
+const ELEVATION_LAYERS: u32 = 64;
+/// Vertical exaggeration of the terrain until the page sets another, as
+/// mountains are too small to see from above at their true height.
+const DEFAULT_TERRAIN_EXAGGERATION: f32 = 20.;
+
+/// The satellite layer as the backend stores it, so that its databases can
+/// be read in the browser.
+#[derive(Debug)]
+pub struct Satellite;
+
+impl Layer for Satellite {
+    type Type = [u8; 4];
+    type AggregateType = ();
+
+    const CHILDREN_PER_AXIS: usize = 2;
+}
 
 #[derive(Debug)]
 pub struct EarthState {
@@ -53,17 +80,35 @@ pub struct EarthState {
 
     buffer_allocator: BufferAllocator,
     tile_map: HashMap<(u32, u32, u32), TileResponse<[u8; 4]>>,
+    /// Tiles written to the textures, kept to read the values under the
+    /// cursor from.
+    resident_tiles: HashMap<(u32, u32, u32), TileResponse<[u8; 4]>>,
     // population_buffer_allocator: BufferAllocator,
     // population_tile_map: HashMap<(u32, u32, u32), TileResponse<f32>>,
     lp_tile_map: HashMap<(u32, u32, u32), TileResponse<f32>>,
+    resident_lp_tiles: HashMap<(u32, u32, u32), TileResponse<f32>>,
     lp_buffer_allocator: BufferAllocator,
     texture_buffer_2: wgpu::Texture,
     tile_metadata_buffer_2: Buffer,
+    elevation_tile_map: HashMap<(u32, u32, u32), TileResponse<f32>>,
+    resident_elevation_tiles: HashMap<(u32, u32, u32), TileResponse<f32>>,
+    elevation_buffer_allocator: BufferAllocator,
+    elevation_texture: wgpu::Texture,
+    elevation_metadata_buffer: Buffer,
+    /// The exaggeration of the terrain, or 0 when it is flat.
+    terrain_uniform: Buffer,
+    pub render_terrain: bool,
+    terrain_exaggeration: f32,
     last_buffer_write: Instant,
     pub render_lp_map: bool,
+    lp_timestep: Option<String>,
+    /// Satellite tiles are read from here instead of the server when set.
+    offline_database: Option<GeoTree<Satellite>>,
+    /// Satellite tiles are fetched from here instead of the server when set.
+    remote_database: Option<Rc<RemoteDatabase>>,
     shader_mode_uniform: Buffer,
     shader_mode: u32,
-    // pub query_poi: QueryPoi,
+    pub query_poi: QueryPoi,
 }
 
 impl EarthState {
@@ -75,10 +120,24 @@ impl EarthState {
     // self.population_tile_map.insert(id, data);
     // }
 
-    pub fn insert_lp_tile(&mut self, id: (u32, u32, u32), data: TileResponse<f32>) {
+    pub fn insert_lp_tile(
+        &mut self,
+        id: (u32, u32, u32),
+        timestep: Option<String>,
+        data: TileResponse<f32>,
+    ) {
+        // Drop tiles requested before the timestep changed
+        if timestep != self.lp_timestep {
+            return;
+        }
+
         self.lp_tile_map.insert(id, data);
     }
 
+    pub fn insert_elevation_tile(&mut self, id: (u32, u32, u32), data: TileResponse<f32>) {
+        self.elevation_tile_map.insert(id, data);
+    }
+
     pub fn rewrite_tiles(&mut self, queue: &Queue) {
         if self.last_buffer_write.elapsed().as_millis() < 10 {
             return;
@@ -87,6 +146,20 @@ impl EarthState {
         self.last_buffer_write = Instant::now();
 
         self.update_tile_buffer = false;
+
+        // Tiles whose slots were taken over are gone from the textures
+        let allocator = &self.buffer_allocator;
+        self.resident_tiles
+            .retain(|id, _| allocator.slot(id).is_some());
+
+        let allocator = &self.lp_buffer_allocator;
+        self.resident_lp_tiles
+            .retain(|id, _| allocator.slot(id).is_some());
+
+        let allocator = &self.elevation_buffer_allocator;
+        self.resident_elevation_tiles
+            .retain(|id, _| allocator.slot(id).is_some());
+
         let tiles = std::mem::take(&mut self.tile_map);
 
         for (id, tile) in tiles.into_iter() {
@@ -103,6 +176,7 @@ impl EarthState {
             let metadata = TileMetadata::from((&tile, id.0, 0));
 
             self.write_a_single_tile_to_buffer(&data, metadata, slot, queue);
+            self.resident_tiles.insert(id, tile);
         }
 
         // let tiles = std::mem::take(&mut self.population_tile_map);
@@ -139,6 +213,26 @@ impl EarthState {
             let metadata = TileMetadata::from((&tile, id.0, 2));
 
             self.write_a_single_tile_to_buffer(&data, metadata, slot, queue);
+            self.resident_lp_tiles.insert(id, tile);
+        }
+
+        let tiles = std::mem::take(&mut self.elevation_tile_map);
+
+        for (id, tile) in tiles.into_iter() {
+            let Some(&slot) = self.elevation_buffer_allocator.slot(&id) else {
+                continue;
+            };
+
+            let data = tile
+                .get_padded_tile(TEXTURE_WIDTH, TEXTURE_HEIGHT)
+                .into_iter()
+                .flatten()
+                .flat_map(|pixel| pixel.to_ne_bytes())
+                .collect::<Vec<u8>>();
+            let metadata = TileMetadata::from((&tile, id.0, 3));
+
+            self.write_a_single_tile_to_buffer(&data, metadata, slot, queue);
+            self.resident_elevation_tiles.insert(id, tile);
         }
     }
 
@@ -150,10 +244,10 @@ impl EarthState {
         queue: &Queue,
     ) {
         // let metadata = TileMetadata::from((&new_tile, id.0));
-        let (texture_buffer, tile_metadata_buffer) = if metadata.data_type == 2 {
-            (&self.texture_buffer_2, &self.tile_metadata_buffer_2)
-        } else {
-            (&self.texture_buffer, &self.tile_metadata_buffer)
+        let (texture_buffer, tile_metadata_buffer) = match metadata.data_type {
+            2 => (&self.texture_buffer_2, &self.tile_metadata_buffer_2),
+            3 => (&self.elevation_texture, &self.elevation_metadata_buffer),
+            _ => (&self.texture_buffer, &self.tile_metadata_buffer),
         };
         queue.write_texture(
             TexelCopyTextureInfo {
@@ -203,6 +297,19 @@ impl EarthState {
             mapped_at_creation: false,
         });
 
+        let elevation_metadata_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("elevation_metadata_buffer"),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            size: size_of::<TileMetadata>() as u64 * BUFFER_SIZE as u64,
+            mapped_at_creation: false,
+        });
+
+        let terrain_uniform = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("terrain"),
+            contents: bytemuck::bytes_of(&[0f32; 4]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
         let shader_mode_uniform = device.create_buffer(&BufferDescriptor {
             label: Some("shader mode"),
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
@@ -260,6 +367,26 @@ impl EarthState {
             ..Default::default()
         });
 
+        // Elevations in meters, read texel by texel in the vertex shader
+        let elevation_texture = device.create_texture(&TextureDescriptor {
+            label: Some("elevation_texture"),
+            size: wgpu::Extent3d {
+                depth_or_array_layers: ELEVATION_LAYERS,
+                ..texture_size
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R32Float,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let elevation_texture_view =
+            elevation_texture.create_view(&TextureViewDescriptor::default());
+
+        let query_poi = QueryPoi::new(device);
+
         // Initializing empty buffers is fine,
         // since we initialize new ones on update
         let vertex_buffer = device.create_buffer(&BufferDescriptor {
@@ -342,6 +469,46 @@ impl EarthState {
                         },
                         count: None,
                     },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 7,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 8,
+                        visibility: ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 9,
+                        visibility: ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 10,
+                        visibility: ShaderStages::VERTEX_FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ],
             });
 
@@ -377,6 +544,22 @@ impl EarthState {
                     binding: 6,
                     resource: shader_mode_uniform.as_entire_binding(),
                 },
+                BindGroupEntry {
+                    binding: 7,
+                    resource: query_poi.uniform().as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 8,
+                    resource: wgpu::BindingResource::TextureView(&elevation_texture_view),
+                },
+                BindGroupEntry {
+                    binding: 9,
+                    resource: elevation_metadata_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 10,
+                    resource: terrain_uniform.as_entire_binding(),
+                },
             ],
         });
 
@@ -428,12 +611,36 @@ impl EarthState {
             BufferAllocator::new(levels, BUFFER_SIZE as usize, 0)
         };
 
+        let elevation_buffer_allocator = {
+            let levels = (0..=7)
+                .map(|level| {
+                    Level::new(
+                        Bounds::new(Coord { x: -180., y: 90. }, Coord { x: 180., y: -90. }),
+                        2_usize.pow(level),
+                        2_usize.pow(level),
+                    )
+                })
+                .collect();
+
+            BufferAllocator::new(levels, ELEVATION_LAYERS as usize, 0)
+        };
+
         Self {
             tile_map: HashMap::new(),
+            resident_tiles: HashMap::new(),
             buffer_allocator,
 
             lp_tile_map: HashMap::new(),
+            resident_lp_tiles: HashMap::new(),
             lp_buffer_allocator,
+            elevation_tile_map: HashMap::new(),
+            resident_elevation_tiles: HashMap::new(),
+            elevation_buffer_allocator,
+            elevation_texture,
+            elevation_metadata_buffer,
+            terrain_uniform,
+            render_terrain: false,
+            terrain_exaggeration: DEFAULT_TERRAIN_EXAGGERATION,
             // population_tile_map: HashMap::new(),
             // population_buffer_allocator,
             eventloop,
@@ -455,10 +662,13 @@ impl EarthState {
             tile_metadata_buffer,
             tile_metadata_buffer_2,
             render_lp_map: false,
+            lp_timestep: None,
+            offline_database: None,
+            remote_database: None,
             last_buffer_write: web_time::Instant::now(),
             shader_mode_uniform,
             shader_mode: 0,
-            // query_poi: QueryPoi::new(&device),
+            query_poi,
         }
     }
 
@@ -497,6 +707,110 @@ impl EarthState {
         );
     }
 
+    /// Shows the terrain raised by its elevation and shaded by its slopes,
+    /// or the globe smooth again.
+    pub fn set_render_terrain(&mut self, render_terrain: bool, queue: &Queue) {
+        self.render_terrain = render_terrain;
+        self.elevation_buffer_allocator.reset();
+        self.elevation_tile_map = HashMap::new();
+        self.update_tile_buffer = true;
+
+        self.write_terrain_uniform(queue);
+    }
+
+    /// Raises the terrain by `exaggeration` times its true height.
+    pub fn set_terrain_exaggeration(&mut self, exaggeration: f32, queue: &Queue) {
+        self.terrain_exaggeration = exaggeration.max(0.);
+
+        self.write_terrain_uniform(queue);
+    }
+
+    fn write_terrain_uniform(&self, queue: &Queue) {
+        let exaggeration = if self.render_terrain {
+            self.terrain_exaggeration
+        } else {
+            0.
+        };
+
+        queue.write_buffer(
+            &self.terrain_uniform,
+            0,
+            bytemuck::bytes_of(&[exaggeration, 0., 0., 0.]),
+        );
+    }
+
+    /// Selects which timestep of the light pollution layer is fetched,
+    /// where `None` uses the default single snapshot.
+    pub fn set_lp_timestep(&mut self, timestep: Option<String>) {
+        if timestep == self.lp_timestep {
+            return;
+        }
+
+        self.lp_timestep = timestep;
+        self.lp_buffer_allocator.reset();
+        self.lp_tile_map = HashMap::new();
+        self.update_tile_buffer = true;
+    }
+
+    /// Reads satellite tiles from `database` instead of fetching them from
+    /// the server. Only as many levels as the database holds are shown. A
+    /// damaged database is ignored, so tiles keep coming from the server.
+    pub fn set_offline_database(&mut self, database: GeoTree<Satellite>) {
+        let Ok(info) = database.info() else {
+            return;
+        };
+
+        self.buffer_allocator.limit_levels(info.levels);
+        self.buffer_allocator.reset();
+        self.tile_map = HashMap::new();
+        self.offline_database = Some(database);
+        self.update_tile_buffer = true;
+    }
+
+    /// Fetches satellite tiles from `database` instead of the server. Only
+    /// as many levels as the database holds are shown.
+    pub fn set_remote_database(&mut self, database: RemoteDatabase) {
+        self.buffer_allocator.limit_levels(database.levels());
+        self.buffer_allocator.reset();
+        self.tile_map = HashMap::new();
+        self.remote_database = Some(Rc::new(database));
+        self.update_tile_buffer = true;
+    }
+
+    /// The colour of the satellite imagery at `coord`, in degrees, if a
+    /// tile covering it has been loaded.
+    pub fn sample_satellite(&self, coord: Coord<f32>) -> Option<[u8; 4]> {
+        sample(&self.resident_tiles, &self.buffer_allocator, coord)
+    }
+
+    /// The light pollution at `coord`, in degrees, if the layer is shown and
+    /// a tile covering it has been loaded.
+    pub fn sample_light_pollution(&self, coord: Coord<f32>) -> Option<f32> {
+        if !self.render_lp_map {
+            return None;
+        }
+
+        sample(&self.resident_lp_tiles, &self.lp_buffer_allocator, coord)
+    }
+
+    /// The elevation at `coord`, in degrees, in meters above sea level, if
+    /// the terrain is shown and a tile covering it has been loaded.
+    pub fn sample_elevation(&self, coord: Coord<f32>) -> Option<f32> {
+        if !self.render_terrain {
+            return None;
+        }
+
+        sample(
+            &self.resident_elevation_tiles,
+            &self.elevation_buffer_allocator,
+            coord,
+        )
+    }
+
+    pub fn subdivision_level(&self) -> usize {
+        self.current_subdivision_level
+    }
+
     pub fn set_subdivision_level(&mut self, level: usize) {
         self.current_subdivision_level = level;
     }
@@ -560,25 +874,80 @@ impl EarthState {
             &fov_intersections,
         );
 
+        let new_elevation_allocations = if self.render_terrain {
+            self.elevation_buffer_allocator.allocate(
+                self.elevation_buffer_allocator.current_level as u32,
+                &fov_intersections,
+            )
+        } else {
+            Vec::new()
+        };
+
+        let new_allocations = match &self.offline_database {
+            Some(database) => {
+                let mut tiles = Vec::new();
+                let mut damaged = Vec::new();
+
+                for id in new_allocations {
+                    match database.get_tile(id.2 as usize, id.1 as usize, id.0 as usize) {
+                        Ok(Some(tile)) => tiles.push((
+                            id,
+                            TileResponse {
+                                data: tile.data.into_iter().map(Cow::into_owned).collect(),
+                                bounds: tile.bounds,
+                            },
+                        )),
+                        Ok(None) => {}
+                        Err(_) => damaged.push(id),
+                    }
+                }
+
+                for (id, tile) in tiles {
+                    self.insert_tile(id, tile);
+                }
+
+                self.update_tile_buffer = true;
+
+                // Only damaged tiles are left to fetch
+                damaged
+            }
+            None => new_allocations,
+        };
+
         let should_fetch_lp_tiles = self.render_lp_map;
+        let lp_timestep = self.lp_timestep.clone();
+
+        let remote_database = self.remote_database.clone();
 
         let proxy = self.eventloop.clone();
         wasm_bindgen_futures::spawn_local(async move {
             for tile_id in new_allocations {
-                let tile: TileResponse<[u8; 4]> = bincode::deserialize(
-                    &gloo_net::http::Request::get(&format!(
-                        "/sat_tile/{}/{}/{}",
-                        tile_id.0, tile_id.1, tile_id.2
-                    ))
-                    // .cache(web_sys::RequestCache::ForceCache)
-                    .send()
-                    .await
-                    .unwrap()
-                    .binary()
-                    .await
-                    .unwrap(),
-                )
-                .unwrap();
+                let tile: TileResponse<[u8; 4]> = match &remote_database {
+                    Some(database) => {
+                        let tile = database
+                            .get_tile(tile_id.2 as usize, tile_id.1 as usize, tile_id.0 as usize)
+                            .await;
+
+                        let Some(tile) = tile else {
+                            continue;
+                        };
+
+                        tile
+                    }
+                    None => {
+                        let request = gloo_net::http::Request::get(&format!(
+                            "/sat_tile/{}/{}/{}",
+                            tile_id.0, tile_id.1, tile_id.2
+                        ));
+                        // .cache(web_sys::RequestCache::ForceCache)
+
+                        let Some(tile) = fetch_tile(request).await else {
+                            continue;
+                        };
+
+                        tile
+                    }
+                };
 
                 proxy
                     .send_event(CustomEvent::HttpResponse(
@@ -610,29 +979,57 @@ impl EarthState {
             //         .unwrap();
             // }
             //
+            // Empty unless the terrain is shown. Elevation may not reach as
+            // deep as the other layers, or not be served at all
+            for tile_id in new_elevation_allocations {
+                let request = gloo_net::http::Request::get(&format!(
+                    "/elevation_tile/{}/{}/{}",
+                    tile_id.0, tile_id.1, tile_id.2
+                ))
+                .cache(web_sys::RequestCache::ForceCache);
+
+                let Some(tile) = fetch_tile(request).await else {
+                    continue;
+                };
+
+                proxy
+                    .send_event(CustomEvent::HttpResponse(
+                        crate::app::CustomResponseType::Elevation(tile, tile_id),
+                    ))
+                    .unwrap();
+            }
+
             if !should_fetch_lp_tiles {
                 return;
             }
 
             for tile_id in new_lp_allocations {
-                let tile: TileResponse<f32> = bincode::deserialize(
-                    &gloo_net::http::Request::get(&format!(
-                        "/light_p_tile/{}/{}/{}",
-                        tile_id.0, tile_id.1, tile_id.2
-                    ))
-                    .cache(web_sys::RequestCache::ForceCache)
-                    .send()
-                    .await
-                    .unwrap()
-                    .binary()
-                    .await
-                    .unwrap(),
+                let url = match &lp_timestep {
+                    Some(time) => format!(
+                        "/layers/light_pollution/{}/tile/{}/{}/{}",
+                        String::from(web_sys::js_sys::encode_uri_component(time)),
+                        tile_id.0,
+                        tile_id.1,
+                        tile_id.2
+                    ),
+                    None => format!("/light_p_tile/{}/{}/{}", tile_id.0, tile_id.1, tile_id.2),
+                };
+
+                let Some(tile) = fetch_tile::<f32>(
+                    gloo_net::http::Request::get(&url).cache(web_sys::RequestCache::ForceCache),
                 )
-                .unwrap();
+                .await
+                else {
+                    continue;
+                };
 
                 proxy
                     .send_event(CustomEvent::HttpResponse(
-                        crate::app::CustomResponseType::LightPollution(tile, tile_id),
+                        crate::app::CustomResponseType::LightPollution(
+                            tile,
+                            tile_id,
+                            lp_timestep.clone(),
+                        ),
                     ))
                     .unwrap();
             }
@@ -644,6 +1041,8 @@ impl EarthState {
             self.rewrite_tiles(queue);
         }
 
+        self.query_poi.update(queue);
+
         if self.current_subdivision_level == self.previous_subdivision_level
             && self.previous_output_as_lines == self.current_output_as_lines
         {
@@ -682,8 +1081,14 @@ impl EarthState {
         self.previous_output_as_lines = self.current_output_as_lines;
     }
 
-    pub fn render(&self, render_pass: &mut RenderPass<'_>) -> u32 {
+    /// Binds the textures of the globe, including its elevation, which the
+    /// lines drawn on it are raised by as well.
+    pub fn bind_textures(&self, render_pass: &mut RenderPass<'_>) {
         render_pass.set_bind_group(1, &self.texture_bind_group, &[]);
+    }
+
+    pub fn render(&self, render_pass: &mut RenderPass<'_>) -> u32 {
+        self.bind_textures(render_pass);
         render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
 
@@ -691,7 +1096,66 @@ impl EarthState {
     }
 }
 
-fn icosahedron_to_wgs84(v: Point) -> Point {
+/// Sends `request` for a tile, accepting tiles compressed as they are
+/// stored on the server and decoding them here. Tiles the server does not
+/// have, or that arrive damaged, are `None`.
+async fn fetch_tile<T>(request: gloo_net::http::RequestBuilder) -> Option<TileResponse<T>>
+where
+    T: Pod + serde::de::DeserializeOwned,
+{
+    let response = request
+        .header("Accept", COMPRESSED_TILE_MEDIA_TYPE)
+        .send()
+        .await
+        .unwrap();
+
+    if !response.ok() {
+        return None;
+    }
+
+    let compressed = response
+        .headers()
+        .get("Content-Type")
+        .is_some_and(|content_type| content_type == COMPRESSED_TILE_MEDIA_TYPE);
+
+    let data = response.binary().await.unwrap();
+
+    if compressed {
+        bincode::deserialize::<CompressedTileResponse>(&data)
+            .ok()?
+            .decode()
+            .ok()
+    } else {
+        bincode::deserialize(&data).ok()
+    }
+}
+
+/// The value at `coord` in the most detailed of `tiles` still in the
+/// textures, the one the globe's shader shows there.
+fn sample<T>(
+    tiles: &HashMap<(u32, u32, u32), TileResponse<T>>,
+    allocator: &BufferAllocator,
+    coord: Coord<f32>,
+) -> Option<T>
+where
+    T: Copy,
+{
+    let (_, tile) = tiles
+        .iter()
+        .filter(|(id, tile)| allocator.slot(id).is_some() && tile.bounds.intersects(&coord))
+        .max_by_key(|((z, _, _), _)| *z)?;
+
+    let height = tile.data.len();
+    let width = tile.data.first()?.len();
+
+    // Rows run from north to south
+    let x = (coord.x - tile.bounds.min().x) / tile.bounds.width() * width as f32;
+    let y = (tile.bounds.max().y - coord.y) / tile.bounds.height() * height as f32;
+
+    Some(tile.data[(y as usize).min(height - 1)][(x as usize).min(width - 1)])
+}
+
+pub(crate) fn icosahedron_to_wgs84(v: Point) -> Point {
     let v = v.normalize();
     const EARTH_RADIUS: f32 = 1.;
     const FLATTENING: f32 = 1. / 298.257;
@@ -706,7 +1170,7 @@ fn icosahedron_to_wgs84(v: Point) -> Point {
     v * r
 }
 
-fn ray_intersects_sphere(
+pub(crate) fn ray_intersects_sphere(
     ray_origin: Vec3,
     ray_direction: Vec3,
     sphere_center: Vec3,
@@ -736,7 +1200,7 @@ fn ray_intersects_sphere(
     Some(ray_origin + closest_positive_t * ray_direction)
 }
 
-fn convert_point_on_surface_to_lat_lon(point: Point) -> Coord<f32> {
+pub(crate) fn convert_point_on_surface_to_lat_lon(point: Point) -> Coord<f32> {
     let point = point.normalize();
     let lon = if point.x == 0.0 && point.y == 0.0 {
         0.0
@@ -748,7 +1212,7 @@ fn convert_point_on_surface_to_lat_lon(point: Point) -> Coord<f32> {
     coord! {x:lon,y:lat}
 }
 
-fn calculate_camera_earth_view_bounding_box(
+pub(crate) fn calculate_camera_earth_view_bounding_box(
     camera_projection: &Projection,
     camera: &Camera,
     earth_position: Point,
@@ -2,10 +2,12 @@ pub mod icosphere;
 pub use icosphere::*;
 pub mod earth;
 pub use earth::*;
+pub mod vector;
+pub use vector::*;
 pub mod performance_metrics;
 pub use performance_metrics::*;
-// pub mod query_poi;
-// pub use query_poi::*;
+pub mod query_poi;
+pub use query_poi::*;
 use std::hash::{Hash, Hasher};
 
 use glam::Vec3;
@@ -1,12 +1,45 @@
+use common::{CircleQuery, Coordinate, EARTH_RADIUS_KM};
+use geo::{Coord, LineString, Polygon};
 use glam::Vec3;
-use wgpu::{
-    BufferDescriptor, BufferUsages, Device, Queue, };
+use wgpu::{Buffer, BufferDescriptor, BufferUsages, Device, Queue};
+
+use super::earth::convert_point_on_surface_to_lat_lon;
+
+/// Polygons with more vertices can be selected, but only this many are
+/// highlighted.
+const MAX_VERTICES: usize = 32;
+
+/// A selection as sent to be queried, in degrees. The backend splits it at
+/// the antimeridian and closes it over a pole where needed.
+#[derive(Debug)]
+pub enum Region {
+    Circle(CircleQuery),
+    Polygon(Polygon<f32>),
+}
 
+#[derive(Debug)]
+enum Selection {
+    None,
+    /// A circle around `center`, passing through `edge`, both on the unit
+    /// sphere.
+    Circle {
+        center: Vec3,
+        edge: Vec3,
+    },
+    /// Vertices in degrees, with `x` as longitude and `y` as latitude,
+    /// closed once they have been queried.
+    Polygon {
+        vertices: Vec<Coord<f32>>,
+        closed: bool,
+    },
+}
+
+/// A region selected on the globe, highlighted by the globe's shader and
+/// queried for the aggregate of the light pollution within it.
 #[derive(Debug)]
 pub struct QueryPoi {
-    querying: bool,
-    start_pos: Option<Vec3>,
-    radius: f32,
+    selection: Selection,
+    dragging: bool,
     query_poi_uniform: wgpu::Buffer,
     value_changed: bool,
 }
@@ -14,23 +47,54 @@ pub struct QueryPoi {
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct ShaderQueryPoi {
-    start_pos: [f32; 3],
-    radius: f32,
+    center: [f32; 3],
+    cos_radius: f32,
+    /// 0 without a selection, 1 for a circle and 2 for a polygon.
+    mode: u32,
+    vertex_count: u32,
+    _padding: [u32; 2],
+    /// Longitude and latitude in degrees, padded to the stride of uniform
+    /// arrays.
+    vertices: [[f32; 4]; MAX_VERTICES],
 }
 
-impl TryInto<ShaderQueryPoi> for &QueryPoi {
-    fn try_into(self) -> Result<ShaderQueryPoi, Self::Error> {
-        Ok(ShaderQueryPoi {
-            start_pos: self
-                .start_pos
-                .ok_or("No starting position")?
-                .clone()
-                .to_array(),
-            radius: self.radius,
-        })
-    }
+impl From<&QueryPoi> for ShaderQueryPoi {
+    fn from(query_poi: &QueryPoi) -> Self {
+        let mut uniform = ShaderQueryPoi {
+            center: [0.; 3],
+            cos_radius: 1.,
+            mode: 0,
+            vertex_count: 0,
+            _padding: [0; 2],
+            vertices: [[0.; 4]; MAX_VERTICES],
+        };
+
+        match &query_poi.selection {
+            Selection::None => {}
+            Selection::Circle { center, edge } => {
+                uniform.mode = 1;
+                uniform.center = center.to_array();
+                uniform.cos_radius = center.dot(*edge);
+            }
+            Selection::Polygon { vertices, .. } => {
+                uniform.mode = 2;
+                uniform.vertex_count = vertices.len().min(MAX_VERTICES) as u32;
 
-    type Error = &'static str;
+                // Unwrapped so that edges crossing the antimeridian go the
+                // short way around, as the backend takes them
+                let mut previous = vertices.first().map_or(0., |coord| coord.x);
+
+                for (vertex, coord) in uniform.vertices.iter_mut().zip(vertices) {
+                    let lon = coord.x + ((previous - coord.x) / 360.).round() * 360.;
+                    previous = lon;
+
+                    *vertex = [lon, coord.y, 0., 0.];
+                }
+            }
+        }
+
+        uniform
+    }
 }
 
 impl QueryPoi {
@@ -44,56 +108,129 @@ impl QueryPoi {
 
         QueryPoi {
             query_poi_uniform,
-            querying: false,
-            start_pos: None,
-            radius: 10.,
-            value_changed: false,
+            selection: Selection::None,
+            dragging: false,
+            value_changed: true,
         }
     }
 
-    fn calculate_intersection(&mut self, _mouse_dx: f64, _mouse_dy: f64) -> Option<Vec3> {
-        todo!()
-        // self.start_pos
+    pub fn uniform(&self) -> &Buffer {
+        &self.query_poi_uniform
     }
 
-    pub fn update(&self, queue: &Queue) {
+    pub fn update(&mut self, queue: &Queue) {
         if self.value_changed {
-            if let Ok(pos) = TryInto::<ShaderQueryPoi>::try_into(self) {
-                queue.write_buffer(&self.query_poi_uniform, 0, bytemuck::bytes_of(&pos));
-            }
+            self.value_changed = false;
+
+            queue.write_buffer(
+                &self.query_poi_uniform,
+                0,
+                bytemuck::bytes_of(&ShaderQueryPoi::from(&*self)),
+            );
         }
     }
 
-    pub fn process_cursor_moved(&mut self, mouse_dx: f64, mouse_dy: f64) {
-        if !self.querying {
-            return;
-        }
-        if self.start_pos.is_none() {
-            self.start_pos = self.calculate_intersection(mouse_dx, mouse_dy);
+    pub fn is_dragging(&self) -> bool {
+        self.dragging
+    }
 
-            return;
+    /// Starts dragging out a circle around `center`.
+    pub fn start_circle(&mut self, center: Vec3) {
+        self.selection = Selection::Circle {
+            center,
+            edge: center,
+        };
+        self.dragging = true;
+        self.value_changed = true;
+    }
+
+    pub fn process_cursor_moved(&mut self, point: Vec3) {
+        if let (true, Selection::Circle { edge, .. }) = (self.dragging, &mut self.selection) {
+            *edge = point;
+            self.value_changed = true;
         }
+    }
+
+    /// Stops dragging the circle, returning it to be queried.
+    pub fn end_circle(&mut self) -> Option<Region> {
+        self.dragging = false;
+
+        self.region()
+    }
 
-        if let (Some(end_pos), Some(start_pos)) = (
-            self.calculate_intersection(mouse_dx, mouse_dy),
-            self.start_pos,
-        ) {
-            let new_radius = (end_pos - start_pos).length();
+    /// Adds the point on the globe at `point` to the polygon being clicked
+    /// out, replacing any other selection.
+    pub fn add_vertex(&mut self, point: Vec3) {
+        let coord = convert_point_on_surface_to_lat_lon(point);
 
-            self.value_changed = self.radius != new_radius;
+        match &mut self.selection {
+            Selection::Polygon {
+                vertices,
+                closed: false,
+            } => vertices.push(coord),
+            selection => {
+                *selection = Selection::Polygon {
+                    vertices: vec![coord],
+                    closed: false,
+                }
+            }
         }
+
+        self.value_changed = true;
     }
-    pub fn start_query_poi(&mut self) {
-        self.querying = true;
+
+    /// Closes the polygon being clicked out, returning it to be queried.
+    /// Polygons with too few vertices are dropped.
+    pub fn end_polygon(&mut self) -> Option<Region> {
+        let Selection::Polygon { closed, .. } = &mut self.selection else {
+            return None;
+        };
+
+        if *closed {
+            return None;
+        }
+
+        *closed = true;
+
+        let region = self.region();
+
+        if region.is_none() {
+            self.cancel();
+        }
+
+        region
     }
-    pub fn end_query_poi(&mut self) {
-        self.querying = false;
 
-        //Request the data
-        //
+    pub fn cancel(&mut self) {
+        self.selection = Selection::None;
+        self.dragging = false;
+        self.value_changed = true;
     }
 
-    pub fn intersect_with_globe() {}
+    fn region(&self) -> Option<Region> {
+        match &self.selection {
+            Selection::None => None,
+            Selection::Circle { center, edge } => {
+                let radius = center.dot(*edge).clamp(-1., 1.).acos();
 
-    pub fn render_current_intersection() {}
+                if radius == 0. {
+                    return None;
+                }
+
+                let center = convert_point_on_surface_to_lat_lon(*center);
+
+                Some(Region::Circle(CircleQuery {
+                    center: Coordinate {
+                        lat: center.y,
+                        lon: center.x,
+                    },
+                    radius_km: radius * EARTH_RADIUS_KM,
+                }))
+            }
+            Selection::Polygon { vertices, .. } if vertices.len() >= 3 => Some(Region::Polygon(
+                Polygon::new(LineString::from(vertices.clone()), Vec::new()),
+            )),
+            Selection::Polygon { .. } => None,
+        }
+    }
 }
@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+
+use common::{Bounds, VectorTile};
+use geo::Coord;
+use glam::Vec3;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    Buffer, BufferAddress, BufferDescriptor, BufferUsages, Device, RenderPass, VertexAttribute,
+    VertexBufferLayout, VertexFormat, VertexStepMode,
+};
+use winit::event_loop::EventLoopProxy;
+
+use crate::{
+    app::{CustomEvent, CustomResponseType},
+    camera::{Camera, Projection},
+    utils::buffer::{BufferAllocator, Level},
+};
+
+use super::earth::{calculate_camera_earth_view_bounding_box, icosahedron_to_wgs84};
+
+/// The vector layers served by the backend and the colour they are drawn
+/// in. Layers the backend has not been configured with are skipped.
+const LAYERS: [(&str, [f32; 4]); 2] = [
+    ("borders", [1., 1., 1., 0.8]),
+    ("coastlines", [0.4, 0.8, 1., 0.9]),
+];
+
+const GRATICULE_COLOR: [f32; 4] = [1., 1., 1., 0.25];
+/// Degrees between the lines of the graticule.
+const GRATICULE_SPACING: i32 = 15;
+
+/// Lines are raised slightly above the globe so that they are not hidden
+/// by its faces, which lie below the surface between their corners. The
+/// line shader raises them further by the terrain, as the globe is.
+const ELEVATION: f32 = 1.0005;
+/// Segments are split to be at most this many degrees long, so that they
+/// follow the curve of the globe instead of cutting through it.
+const MAX_SEGMENT_LENGTH: f32 = 1.;
+
+const SLOTS: usize = 64;
+
+/// A tile by its id and the index of its layer in [`LAYERS`].
+type TileKey = ((u32, u32, u32), usize);
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+/// Borders, coastlines and a graticule drawn as lines on the globe.
+#[derive(Debug)]
+pub struct VectorState {
+    vertex_buffer: Buffer,
+    num_vertices: u32,
+    rebuild: bool,
+
+    graticule: Vec<LineVertex>,
+    pub render_graticule: bool,
+    pub render_vectors: bool,
+
+    allocator: BufferAllocator,
+    /// The lines of every tile of every layer.
+    tiles: HashMap<TileKey, Vec<LineVertex>>,
+    eventloop: EventLoopProxy<CustomEvent>,
+}
+
+impl VectorState {
+    pub fn create(device: &Device, eventloop: EventLoopProxy<CustomEvent>) -> Self {
+        let vertex_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("vector_vertex_buffer"),
+            size: 0,
+            usage: BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        });
+
+        let allocator = {
+            let levels = (0..=6)
+                .map(|level| {
+                    Level::new(
+                        Bounds::new(Coord { x: -180., y: 90. }, Coord { x: 180., y: -90. }),
+                        2_usize.pow(level),
+                        2_usize.pow(level),
+                    )
+                })
+                .collect();
+
+            BufferAllocator::new(levels, SLOTS, 0)
+        };
+
+        Self {
+            vertex_buffer,
+            num_vertices: 0,
+            rebuild: true,
+            graticule: graticule(),
+            render_graticule: true,
+            render_vectors: true,
+            allocator,
+            tiles: HashMap::new(),
+            eventloop,
+        }
+    }
+
+    pub fn descriptor() -> VertexBufferLayout<'static> {
+        VertexBufferLayout {
+            array_stride: std::mem::size_of::<LineVertex>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &[
+                VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: VertexFormat::Float32x3,
+                },
+                VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 3]>() as BufferAddress,
+                    shader_location: 1,
+                    format: VertexFormat::Float32x4,
+                },
+            ],
+        }
+    }
+
+    pub fn set_render_graticule(&mut self, render_graticule: bool) {
+        self.render_graticule = render_graticule;
+        self.rebuild = true;
+    }
+
+    pub fn set_render_vectors(&mut self, render_vectors: bool) {
+        self.render_vectors = render_vectors;
+        self.rebuild = true;
+    }
+
+    pub fn insert_tile(&mut self, id: (u32, u32, u32), layer: usize, tile: VectorTile) {
+        // Dropped while the request was in flight
+        if self.allocator.slot(&id).is_none() {
+            return;
+        }
+
+        let color = LAYERS[layer].1;
+
+        // Replaces the tile if it was fetched before
+        let mut vertices = Vec::new();
+
+        for line in &tile {
+            for segment in line.lines() {
+                push_segment(&mut vertices, segment.start, segment.end, color);
+            }
+        }
+
+        self.tiles.insert((id, layer), vertices);
+
+        self.rebuild = true;
+    }
+
+    pub fn update_visible_tiles(&mut self, projection: &Projection, camera: &Camera) {
+        if !self.render_vectors {
+            return;
+        }
+
+        let fov_intersections =
+            calculate_camera_earth_view_bounding_box(projection, camera, Vec3::ZERO);
+
+        let level = self.allocator.current_level;
+        let new_allocations = self.allocator.allocate(level as u32, &fov_intersections);
+
+        if level != self.allocator.current_level || !new_allocations.is_empty() {
+            self.rebuild = true;
+        }
+
+        if new_allocations.is_empty() {
+            return;
+        }
+
+        let proxy = self.eventloop.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            for tile_id in new_allocations {
+                for (layer, (name, _)) in LAYERS.iter().enumerate() {
+                    let Some(tile) = fetch_vector_tile(name, tile_id).await else {
+                        continue;
+                    };
+
+                    let _ = proxy.send_event(CustomEvent::HttpResponse(
+                        CustomResponseType::Vector(tile, tile_id, layer),
+                    ));
+                }
+            }
+        });
+    }
+
+    pub fn update(&mut self, device: &Device) {
+        if !self.rebuild {
+            return;
+        }
+
+        self.rebuild = false;
+
+        // Tiles whose slots were taken over are gone for good
+        self.tiles
+            .retain(|(id, _), _| self.allocator.slot(id).is_some());
+
+        let mut vertices = Vec::new();
+
+        if self.render_graticule {
+            vertices.extend_from_slice(&self.graticule);
+        }
+
+        if self.render_vectors {
+            let level = self.allocator.current_level as u32;
+
+            for (_, tile) in self.tiles.iter().filter(|(((z, _, _), _), _)| *z == level) {
+                vertices.extend_from_slice(tile);
+            }
+        }
+
+        self.num_vertices = vertices.len() as u32;
+
+        self.vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("vector_vertex_buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: BufferUsages::VERTEX,
+        });
+    }
+
+    pub fn render(&self, render_pass: &mut RenderPass<'_>) -> u32 {
+        // Empty buffers can not be bound
+        if self.num_vertices == 0 {
+            return 0;
+        }
+
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+
+        self.num_vertices
+    }
+}
+
+async fn fetch_vector_tile(name: &str, (z, y, x): (u32, u32, u32)) -> Option<VectorTile> {
+    let response = gloo_net::http::Request::get(&format!("/vector/{name}/{z}/{y}/{x}"))
+        .send()
+        .await
+        .ok()?;
+
+    if !response.ok() {
+        return None;
+    }
+
+    bincode::deserialize(&response.binary().await.ok()?).ok()
+}
+
+/// Meridians and parallels every [`GRATICULE_SPACING`] degrees, leaving
+/// out the poles where all meridians meet.
+fn graticule() -> Vec<LineVertex> {
+    let mut vertices = Vec::new();
+    let limit = 90 - GRATICULE_SPACING;
+
+    for lon in (-180..180).step_by(GRATICULE_SPACING as usize) {
+        push_segment(
+            &mut vertices,
+            Coord {
+                x: lon as f32,
+                y: -limit as f32,
+            },
+            Coord {
+                x: lon as f32,
+                y: limit as f32,
+            },
+            GRATICULE_COLOR,
+        );
+    }
+
+    for lat in (-limit..=limit).step_by(GRATICULE_SPACING as usize) {
+        push_segment(
+            &mut vertices,
+            Coord {
+                x: -180.,
+                y: lat as f32,
+            },
+            Coord {
+                x: 180.,
+                y: lat as f32,
+            },
+            GRATICULE_COLOR,
+        );
+    }
+
+    vertices
+}
+
+/// Pushes the segment from `start` to `end`, given in degrees, as pairs of
+/// vertices on the surface of the globe.
+fn push_segment(
+    vertices: &mut Vec<LineVertex>,
+    start: Coord<f32>,
+    end: Coord<f32>,
+    color: [f32; 4],
+) {
+    let delta = end - start;
+    let steps = (delta.x.abs().max(delta.y.abs()) / MAX_SEGMENT_LENGTH)
+        .ceil()
+        .max(1.) as usize;
+
+    let point = |step: usize| {
+        let coord = start + delta * (step as f32 / steps as f32);
+
+        LineVertex {
+            position: to_surface(coord).to_array(),
+            color,
+        }
+    };
+
+    for step in 0..steps {
+        vertices.push(point(step));
+        vertices.push(point(step + 1));
+    }
+}
+
+/// The point on the globe at `coord`, in degrees, the inverse of how the
+/// globe's shader finds the latitude and longitude of a point.
+fn to_surface(coord: Coord<f32>) -> Vec3 {
+    let (lon, lat) = (coord.x.to_radians(), coord.y.to_radians());
+
+    let direction = Vec3::new(-lon.sin() * lat.cos(), lon.cos() * lat.cos(), -lat.sin());
+
+    icosahedron_to_wgs84(direction) * ELEVATION
+}
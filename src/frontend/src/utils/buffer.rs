@@ -66,6 +66,12 @@ impl BufferAllocator {
         }
     }
 
+    /// Never allocates tiles below the first `levels` levels.
+    pub fn limit_levels(&mut self, levels: usize) {
+        self.levels.truncate(levels.max(1));
+        self.current_level = self.current_level.min(self.levels.len() - 1);
+    }
+
     pub fn reset(&mut self) {
         self.visible = HashSet::new();
         self.allocated = HashMap::new();
@@ -0,0 +1,87 @@
+use std::{cell::RefCell, collections::HashMap, ops::Range, rc::Rc};
+
+use bytemuck::Pod;
+use common::{deserialize::remote::RemoteIndex, TileResponse};
+
+/// A database on any static file server, of which only the index and the
+/// tiles that are shown are fetched, using HTTP range requests. The
+/// entries of a block of neighbouring tiles are fetched the first time one
+/// of them is.
+#[derive(Debug)]
+pub struct RemoteDatabase {
+    url: String,
+    index: RemoteIndex,
+    blocks: RefCell<HashMap<Block, Rc<Vec<u8>>>>,
+}
+
+/// A block of entries by its level, row and index in the row.
+type Block = (usize, usize, usize);
+
+impl RemoteDatabase {
+    /// Fetches the index of the database at `url`. Returns `None` if the
+    /// server does not answer range requests or the file has no index.
+    pub async fn open(url: String) -> Option<Self> {
+        let footer = fetch(&url, format!("bytes=-{}", RemoteIndex::FOOTER_SIZE)).await?;
+        let table = RemoteIndex::table_range(&footer).ok()??;
+        let index = RemoteIndex::new(&fetch_range(&url, table).await?);
+
+        Some(Self {
+            url,
+            index,
+            blocks: RefCell::default(),
+        })
+    }
+
+    pub fn levels(&self) -> usize {
+        self.index.levels()
+    }
+
+    /// Fetches the tile at `x`, `y` and `z`, returning `None` if there is
+    /// none, or if it could not be fetched or read.
+    pub async fn get_tile<T>(&self, x: usize, y: usize, z: usize) -> Option<TileResponse<T>>
+    where
+        T: Pod,
+    {
+        let block = self.block(x, y, z).await?;
+        let entry = self.index.entry(&block, x, y, z).ok()??;
+
+        RemoteIndex::tile(&entry, &fetch_range(&self.url, entry.tile_range()).await?).ok()?
+    }
+
+    /// The entries of the block holding the tile at `x`, `y` and `z`.
+    async fn block(&self, x: usize, y: usize, z: usize) -> Option<Rc<Vec<u8>>> {
+        let key = (z, y, x / RemoteIndex::BLOCK);
+
+        if let Some(block) = self.blocks.borrow().get(&key) {
+            return Some(block.clone());
+        }
+
+        let block = Rc::new(fetch_range(&self.url, self.index.block_range(x, y, z)?).await?);
+        self.blocks.borrow_mut().insert(key, block.clone());
+
+        Some(block)
+    }
+}
+
+async fn fetch_range(url: &str, range: Range<u64>) -> Option<Vec<u8>> {
+    if range.is_empty() {
+        return Some(Vec::new());
+    }
+
+    fetch(url, format!("bytes={}-{}", range.start, range.end - 1)).await
+}
+
+async fn fetch(url: &str, range: String) -> Option<Vec<u8>> {
+    let response = gloo_net::http::Request::get(url)
+        .header("Range", &range)
+        .send()
+        .await
+        .ok()?;
+
+    // Servers ignoring the range answer with the whole file instead
+    if response.status() != 206 {
+        return None;
+    }
+
+    response.binary().await.ok()
+}
@@ -0,0 +1,168 @@
+//! Functions the page calls to move the camera, and the named views it
+//! keeps, to build guided tours and share what is on screen. Saved views
+//! are kept in the local storage of the browser, so that they are still
+//! there on the next visit.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::BTreeMap,
+};
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    app::CustomEvent,
+    camera::{OrbitMode, View},
+    send_to_app as send,
+};
+
+thread_local! {
+    /// The view the camera is moving to, as of the last frame.
+    static CURRENT: Cell<Option<View>> = const { Cell::new(None) };
+    static SAVED: RefCell<BTreeMap<String, View>> = RefCell::new(load_views());
+}
+
+/// Prefixes the key of every saved view in local storage, followed by its
+/// name.
+const STORAGE_PREFIX: &str = "view:";
+
+fn storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+/// The views saved on earlier visits, skipping any that can not be read.
+fn load_views() -> BTreeMap<String, View> {
+    let Some(storage) = storage() else {
+        return BTreeMap::new();
+    };
+
+    let length = storage.length().unwrap_or(0);
+
+    (0..length)
+        .filter_map(|i| {
+            let key = storage.key(i).ok()??;
+            let name = key.strip_prefix(STORAGE_PREFIX)?.to_owned();
+            let view = storage.get_item(&key).ok()??.parse().ok()?;
+
+            Some((name, view))
+        })
+        .collect()
+}
+
+pub fn set_current_view(view: View) {
+    CURRENT.with(|current| current.set(Some(view)));
+}
+
+/// Flies the camera to look down at `lat` and `lon`, in degrees, from
+/// `radius` radii of the globe away, tilted by `tilt` degrees, with north
+/// up. The flight takes `duration` seconds, or none at all if it is 0.
+/// Returns whether all values are finite numbers.
+#[wasm_bindgen]
+pub fn fly_to(lat: f32, lon: f32, radius: f32, tilt: f32, duration: f32) -> bool {
+    if ![lat, lon, radius, tilt, duration]
+        .iter()
+        .all(|value| value.is_finite())
+    {
+        return false;
+    }
+
+    send(CustomEvent::FlyTo(
+        View {
+            lat,
+            lon,
+            heading: 0.,
+            radius,
+            tilt,
+        },
+        duration,
+    ));
+
+    true
+}
+
+/// Flies the camera to a view as returned by `save_view`, which may come
+/// from another visit. Returns whether it could be read.
+#[wasm_bindgen]
+pub fn fly_to_view(view: &str, duration: f32) -> bool {
+    let Ok(view) = view.parse() else {
+        return false;
+    };
+
+    if !duration.is_finite() {
+        return false;
+    }
+
+    send(CustomEvent::FlyTo(view, duration));
+
+    true
+}
+
+/// Saves the current view as `name`, replacing any view saved as it, and
+/// returns it written out to be shared.
+#[wasm_bindgen]
+pub fn save_view(name: String) -> Option<String> {
+    let view = CURRENT.with(Cell::get)?;
+
+    if let Some(storage) = storage() {
+        let _ = storage.set_item(&format!("{STORAGE_PREFIX}{name}"), &view.to_string());
+    }
+
+    SAVED.with(|saved| saved.borrow_mut().insert(name, view));
+
+    Some(view.to_string())
+}
+
+/// Flies the camera to the view saved as `name`, if there is one and the
+/// duration is finite.
+#[wasm_bindgen]
+pub fn restore_view(name: &str, duration: f32) -> bool {
+    let Some(view) = SAVED.with(|saved| saved.borrow().get(name).copied()) else {
+        return false;
+    };
+
+    if !duration.is_finite() {
+        return false;
+    }
+
+    send(CustomEvent::FlyTo(view, duration));
+
+    true
+}
+
+#[wasm_bindgen]
+pub fn remove_view(name: &str) -> bool {
+    if let Some(storage) = storage() {
+        let _ = storage.remove_item(&format!("{STORAGE_PREFIX}{name}"));
+    }
+
+    SAVED.with(|saved| saved.borrow_mut().remove(name).is_some())
+}
+
+/// The names of the saved views, in alphabetical order.
+#[wasm_bindgen]
+pub fn view_names() -> Vec<String> {
+    SAVED.with(|saved| saved.borrow().keys().cloned().collect())
+}
+
+/// Turns the camera so that north is up, keeping the point in the center of
+/// the screen.
+#[wasm_bindgen]
+pub fn reset_north() {
+    send(CustomEvent::ResetNorth);
+}
+
+/// Makes dragging turn the globe freely with `arcball`, or only change the
+/// latitude and longitude with north kept up with `north_up`. Returns
+/// whether the mode is known.
+#[wasm_bindgen]
+pub fn set_orbit_mode(mode: &str) -> bool {
+    let orbit_mode = match mode {
+        "arcball" => OrbitMode::Arcball,
+        "north_up" => OrbitMode::NorthUp,
+        _ => return false,
+    };
+
+    send(CustomEvent::SetOrbitMode(orbit_mode));
+
+    true
+}